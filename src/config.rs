@@ -0,0 +1,565 @@
+use crate::embed;
+use crate::error::SquadError;
+use crate::locale;
+use crate::locale::{Locale, Text};
+use crate::squad;
+use crate::store;
+use crate::store::GuildSettings;
+use serenity::model::channel::ChannelType;
+use serenity::model::interactions::application_command::{
+    ApplicationCommand, ApplicationCommandInteraction, ApplicationCommandInteractionDataOption,
+    ApplicationCommandInteractionDataOptionValue, ApplicationCommandOptionType,
+};
+use serenity::model::interactions::InteractionResponseType;
+use serenity::model::Permissions;
+use serenity::prelude::Context;
+use serenity::prelude::Mentionable;
+use serenity::Error;
+use std::fmt::Display;
+
+/// Longest lifetime in seconds that a server can allow squads to have.
+const MAX_DURATION_LIMIT: u64 = 7 * 24 * 60 * 60;
+/// Longest time in seconds that an empty voice channel of a filled squad can be kept.
+const MAX_VOICE_CLEANUP: u64 = 24 * 60 * 60;
+/// Settings that /squadconfig reset can reset one at a time.
+const SETTINGS: [&str; 16] = [
+    "default_size",
+    "min_size",
+    "max_size",
+    "default_duration",
+    "max_duration",
+    "announce_channel",
+    "colour",
+    "creator_roles",
+    "dms",
+    "voice_category",
+    "threads",
+    "voice_cleanup",
+    "events",
+    "reminders",
+    "availability",
+    "locale",
+];
+
+/// Globally register /squadconfig command, along with its names and descriptions in
+/// the languages SquadBot speaks. Only members with the Manage Server permission are
+/// shown the command, and it can't be used in DMs.
+pub async fn register_config_command(ctx: Context) -> Result<ApplicationCommand, Error> {
+    ApplicationCommand::create_global_application_command(&ctx.http, |command| {
+        command
+            .name("squadconfig")
+            .description("Configure squads in this server")
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .create_option(|option| {
+                option
+                    .name("view")
+                    .description("Show the squad settings of this server")
+                    .kind(ApplicationCommandOptionType::SubCommand)
+            })
+            .create_option(|option| {
+                option
+                    .name("set")
+                    .description("Change squad settings of this server")
+                    .kind(ApplicationCommandOptionType::SubCommand)
+                    .create_sub_option(|sub| {
+                        sub.name("default_size")
+                            .description("Size of squads created without a size")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(store::SIZE_RANGE.0)
+                            .max_int_value(store::SIZE_RANGE.1)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("min_size")
+                            .description("Smallest size a squad can have")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(store::SIZE_RANGE.0)
+                            .max_int_value(store::SIZE_RANGE.1)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("max_size")
+                            .description("Largest size a squad can have")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(store::SIZE_RANGE.0)
+                            .max_int_value(store::SIZE_RANGE.1)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("default_duration")
+                            .description("How long squads stay open by default, e.g. 10h")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("max_duration")
+                            .description("Longest a squad can stay open, e.g. 24h")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("announce_channel")
+                            .description("Channel in which new squads are announced")
+                            .kind(ApplicationCommandOptionType::Channel)
+                            .channel_types(&[ChannelType::Text, ChannelType::News])
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("colour")
+                            .description("Colour of squad postings as a hex code, e.g. #3BA55D")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("add_creator_role")
+                            .description("Allow a role to create squads")
+                            .kind(ApplicationCommandOptionType::Role)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("remove_creator_role")
+                            .description("Stop allowing a role to create squads")
+                            .kind(ApplicationCommandOptionType::Role)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("dms")
+                            .description("Whether squad members are sent direct messages")
+                            .kind(ApplicationCommandOptionType::Boolean)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("voice_category")
+                            .description("Category in which filled squads get a voice channel")
+                            .kind(ApplicationCommandOptionType::Channel)
+                            .channel_types(&[ChannelType::Category])
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("threads")
+                            .description("Whether filled squads get a thread off their posting")
+                            .kind(ApplicationCommandOptionType::Boolean)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("voice_cleanup")
+                            .description("How long an empty squad voice channel is kept, e.g. 15m")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("events")
+                            .description(
+                                "Whether squads get a Discord event once scheduled or filled",
+                            )
+                            .kind(ApplicationCommandOptionType::Boolean)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("reminders")
+                            .description(
+                                "Whether members and owners get a DM before their time runs out",
+                            )
+                            .kind(ApplicationCommandOptionType::Boolean)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("availability")
+                            .description("Times members can pick how long they're available from, e.g. 30m 1h 2h")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("locale")
+                            .description("Language of squad postings and of messages to members")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(false);
+                        for locale in Locale::ALL {
+                            sub.add_string_choice(locale.name(), locale.as_str());
+                        }
+                        sub
+                    })
+            })
+            .create_option(|option| {
+                option
+                    .name("reset")
+                    .description("Reset squad settings of this server to the defaults")
+                    .kind(ApplicationCommandOptionType::SubCommand)
+                    .create_sub_option(|sub| {
+                        sub.name("setting")
+                            .description("Setting to reset, all of them if left out")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(false);
+                        for setting in SETTINGS {
+                            sub.add_string_choice(setting, setting);
+                        }
+                        sub
+                    })
+            });
+        locale::localize_command(command, locale::CONFIG_COMMAND_TRANSLATIONS)
+    })
+    .await
+}
+
+/// View, change or reset the squad settings of a guild. Members without the Manage
+/// Server permission and invalid settings fail with the error to answer them with.
+pub async fn handle_config_command(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+) -> Result<(), SquadError> {
+    let guild_id = match command.guild_id {
+        Some(guild_id) => guild_id,
+        None => {
+            return Err(SquadError::GuildOnly);
+        }
+    };
+    let manager = command
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.manage_guild());
+    if !manager {
+        return Err(SquadError::NotManager);
+    }
+    let subcommand = match command.data.options.first() {
+        Some(subcommand) => subcommand,
+        None => {
+            return Err(SquadError::UnknownCommand);
+        }
+    };
+    let store = store::get_store(ctx).await?;
+    let settings = store.get_guild_settings(guild_id).await?;
+    let settings = match subcommand.name.as_str() {
+        "view" => settings,
+        "set" => set_settings(settings, &subcommand.options)?,
+        "reset" => reset_settings(settings, &subcommand.options),
+        _ => {
+            return Err(SquadError::UnknownCommand);
+        }
+    };
+    if subcommand.name != "view" {
+        store.set_guild_settings(guild_id, &settings).await?;
+    }
+    // Replies follow a change of language right away
+    let text = locale::interaction_locale(ctx, command.guild_id, &command.locale)
+        .await
+        .text();
+    let title = match subcommand.name.as_str() {
+        "set" => text.settings_updated,
+        "reset" => text.settings_reset,
+        _ => text.settings_title,
+    };
+    command
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|m| {
+                    m.embed(|e| {
+                        e.title(title);
+                        e.description(describe_settings(&settings, text));
+                        e.colour(settings.colour());
+                        e
+                    })
+                    .ephemeral(true)
+                })
+        })
+        .await?;
+    Ok(())
+}
+
+/// Apply the options given to /squadconfig set to the settings of a guild
+fn set_settings(
+    mut settings: GuildSettings,
+    options: &[ApplicationCommandInteractionDataOption],
+) -> Result<GuildSettings, SquadError> {
+    for option in options {
+        let value = match option.resolved.as_ref() {
+            Some(value) => value,
+            None => continue,
+        };
+        match (option.name.as_str(), value) {
+            ("default_size", ApplicationCommandInteractionDataOptionValue::Integer(size)) => {
+                settings.default_size = Some(u8::try_from(*size)?);
+            }
+            ("min_size", ApplicationCommandInteractionDataOptionValue::Integer(size)) => {
+                settings.min_size = Some(u8::try_from(*size)?);
+            }
+            ("max_size", ApplicationCommandInteractionDataOptionValue::Integer(size)) => {
+                settings.max_size = Some(u8::try_from(*size)?);
+            }
+            ("default_duration", ApplicationCommandInteractionDataOptionValue::String(input)) => {
+                let seconds = squad::parse_duration(&input.to_lowercase())?;
+                settings.default_duration = Some(seconds as u64);
+            }
+            ("max_duration", ApplicationCommandInteractionDataOptionValue::String(input)) => {
+                let seconds = squad::parse_duration(&input.to_lowercase())?;
+                settings.max_duration = Some(seconds as u64);
+            }
+            (
+                "announce_channel",
+                ApplicationCommandInteractionDataOptionValue::Channel(channel),
+            ) => {
+                settings.announce_channel = Some(channel.id);
+            }
+            ("colour", ApplicationCommandInteractionDataOptionValue::String(input)) => {
+                settings.colour = Some(parse_colour(input)?);
+            }
+            ("add_creator_role", ApplicationCommandInteractionDataOptionValue::Role(role)) => {
+                if !settings.creator_roles.contains(&role.id) {
+                    settings.creator_roles.push(role.id);
+                }
+            }
+            ("remove_creator_role", ApplicationCommandInteractionDataOptionValue::Role(role)) => {
+                settings.creator_roles.retain(|creator| *creator != role.id);
+            }
+            ("dms", ApplicationCommandInteractionDataOptionValue::Boolean(dms)) => {
+                settings.dms = Some(*dms);
+            }
+            ("voice_category", ApplicationCommandInteractionDataOptionValue::Channel(channel)) => {
+                settings.voice_category = Some(channel.id);
+            }
+            ("threads", ApplicationCommandInteractionDataOptionValue::Boolean(threads)) => {
+                settings.threads = Some(*threads);
+            }
+            ("voice_cleanup", ApplicationCommandInteractionDataOptionValue::String(input)) => {
+                let seconds = squad::parse_duration(&input.to_lowercase())?;
+                settings.voice_cleanup = Some(seconds as u64);
+            }
+            ("events", ApplicationCommandInteractionDataOptionValue::Boolean(events)) => {
+                settings.events = Some(*events);
+            }
+            ("reminders", ApplicationCommandInteractionDataOptionValue::Boolean(reminders)) => {
+                settings.reminders = Some(*reminders);
+            }
+            ("availability", ApplicationCommandInteractionDataOptionValue::String(input)) => {
+                settings.availability = parse_presets(input)?;
+            }
+            ("locale", ApplicationCommandInteractionDataOptionValue::String(code)) => {
+                settings.locale = Some(Locale::parse(code).ok_or_else(|| {
+                    SquadError::invalid_with(|text| text.unknown_locale, &[code])
+                })?);
+            }
+            _ => {
+                return Err(SquadError::unparsable(&option.name));
+            }
+        }
+    }
+    check_settings(&settings)?;
+    Ok(settings)
+}
+
+/// Reset the setting named by /squadconfig reset, or all settings if none was named
+fn reset_settings(
+    mut settings: GuildSettings,
+    options: &[ApplicationCommandInteractionDataOption],
+) -> GuildSettings {
+    let setting = options
+        .iter()
+        .find(|option| option.name == "setting")
+        .and_then(|option| option.resolved.as_ref());
+    match setting {
+        Some(ApplicationCommandInteractionDataOptionValue::String(setting)) => {
+            match setting.as_str() {
+                "default_size" => settings.default_size = None,
+                "min_size" => settings.min_size = None,
+                "max_size" => settings.max_size = None,
+                "default_duration" => settings.default_duration = None,
+                "max_duration" => settings.max_duration = None,
+                "announce_channel" => settings.announce_channel = None,
+                "colour" => settings.colour = None,
+                "creator_roles" => settings.creator_roles.clear(),
+                "dms" => settings.dms = None,
+                "voice_category" => settings.voice_category = None,
+                "threads" => settings.threads = None,
+                "voice_cleanup" => settings.voice_cleanup = None,
+                "events" => settings.events = None,
+                "reminders" => settings.reminders = None,
+                "availability" => settings.availability.clear(),
+                "locale" => settings.locale = None,
+                _ => {}
+            }
+            settings
+        }
+        _ => GuildSettings::default(),
+    }
+}
+
+/// Check that changed settings are consistent with each other
+fn check_settings(settings: &GuildSettings) -> Result<(), SquadError> {
+    if settings.min_size() > settings.max_size() {
+        return Err(SquadError::invalid(|text| text.size_order));
+    }
+    if let Some(size) = settings.default_size {
+        if size < settings.min_size() || size > settings.max_size() {
+            return Err(SquadError::invalid(|text| text.default_size_range));
+        }
+    }
+    if settings.max_duration() > MAX_DURATION_LIMIT {
+        return Err(SquadError::invalid(|text| text.max_duration_limit));
+    }
+    let durations = [settings.default_duration, settings.max_duration];
+    if durations
+        .iter()
+        .flatten()
+        .any(|duration| *duration < squad::MIN_DURATION_SECONDS)
+    {
+        return Err(SquadError::invalid(|text| text.too_short));
+    }
+    if settings.voice_cleanup() > MAX_VOICE_CLEANUP {
+        return Err(SquadError::invalid(|text| text.voice_cleanup_limit));
+    }
+    if settings
+        .availability
+        .last()
+        .is_some_and(|preset| *preset > settings.max_duration())
+    {
+        return Err(SquadError::invalid(|text| text.presets_too_long));
+    }
+    if let Some(duration) = settings.default_duration {
+        if duration > settings.max_duration() {
+            return Err(SquadError::invalid(|text| text.default_duration_limit));
+        }
+    }
+    Ok(())
+}
+
+/// Parse availability presets such as "30m 1h 1h30m 2h", separated by spaces or
+/// commas, into seconds, shortest first
+fn parse_presets(input: &str) -> Result<Vec<u64>, SquadError> {
+    let mut presets = Vec::new();
+    for preset in input
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|preset| !preset.is_empty())
+    {
+        let seconds = squad::parse_duration(&preset.to_lowercase())
+            .map_err(|_| SquadError::invalid_with(|text| text.invalid_preset, &[&preset]))?;
+        presets.push(seconds as u64);
+    }
+    presets.sort_unstable();
+    presets.dedup();
+    if presets.is_empty() {
+        return Err(SquadError::invalid(|text| text.no_presets));
+    }
+    if presets.len() > store::MAX_AVAILABILITY_PRESETS {
+        return Err(SquadError::invalid_with(
+            |text| text.too_many_presets,
+            &[&store::MAX_AVAILABILITY_PRESETS],
+        ));
+    }
+    Ok(presets)
+}
+
+/// Parse a hex colour code such as "#3BA55D" or "3ba55d"
+fn parse_colour(input: &str) -> Result<u32, SquadError> {
+    let hex = input.trim().trim_start_matches('#');
+    match u32::from_str_radix(hex, 16) {
+        Ok(colour) if hex.len() == 6 => Ok(colour),
+        _ => Err(SquadError::invalid(|text| text.invalid_colour)),
+    }
+}
+
+/// List the settings of a guild in the given language, marking those that were left
+/// at their default
+fn describe_settings(settings: &GuildSettings, text: &Text) -> String {
+    let marked = |value: &dyn Display, unset: bool| match unset {
+        true => format!("{}{}", value, text.setting_default),
+        false => value.to_string(),
+    };
+    let channel = match settings.announce_channel {
+        Some(channel_id) => channel_id.mention().to_string(),
+        None => String::from(text.setting_none),
+    };
+    let roles = match settings.creator_roles.is_empty() {
+        true => String::from(text.setting_everyone),
+        false => settings
+            .creator_roles
+            .iter()
+            .map(|role| role.mention().to_string())
+            .collect::<Vec<String>>()
+            .join(" "),
+    };
+    let on_off = |on: bool| {
+        if on {
+            text.setting_on
+        } else {
+            text.setting_off
+        }
+    };
+    let presets = settings
+        .availability()
+        .iter()
+        .map(|seconds| embed::format_availability(*seconds))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let language = match settings.locale {
+        Some(locale) => locale.name(),
+        None => text.setting_language_default,
+    };
+    let voice = match settings.voice_category {
+        Some(category_id) => category_id.mention().to_string(),
+        None => String::from(text.setting_none),
+    };
+    let sizes = locale::fill(
+        text.size_range_value,
+        &[&settings.min_size(), &settings.max_size()],
+    );
+    let colour = format!("#{:06X}", settings.colour());
+    locale::fill(
+        text.settings_description,
+        &[
+            &marked(&settings.default_size(), settings.default_size.is_none()),
+            &marked(
+                &sizes,
+                settings.min_size.is_none() && settings.max_size.is_none(),
+            ),
+            &marked(
+                &embed::format_ttl(settings.default_duration()),
+                settings.default_duration.is_none(),
+            ),
+            &marked(
+                &embed::format_ttl(settings.max_duration()),
+                settings.max_duration.is_none(),
+            ),
+            &channel,
+            &marked(&colour, settings.colour.is_none()),
+            &roles,
+            &marked(&on_off(settings.dms()), settings.dms.is_none()),
+            &voice,
+            &marked(&on_off(settings.threads()), settings.threads.is_none()),
+            &marked(
+                &embed::format_ttl(settings.voice_cleanup()),
+                settings.voice_cleanup.is_none(),
+            ),
+            &marked(&on_off(settings.events()), settings.events.is_none()),
+            &marked(&on_off(settings.reminders()), settings.reminders.is_none()),
+            &marked(&presets, settings.availability.is_empty()),
+            &marked(&language, settings.locale.is_none()),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_presets() {
+        assert_eq!(parse_presets("2h, 30m 1H 30m").unwrap(), [1800, 3600, 7200]);
+        assert!(parse_presets("").is_err());
+        assert!(parse_presets("30m soon").is_err());
+        let too_many: Vec<String> = (1..=store::MAX_AVAILABILITY_PRESETS + 1)
+            .map(|hours| format!("{}h", hours))
+            .collect();
+        assert!(parse_presets(&too_many.join(" ")).is_err());
+    }
+
+    #[test]
+    fn parses_colours() {
+        assert_eq!(parse_colour("#3BA55D").unwrap(), 0x3BA55D);
+        assert_eq!(parse_colour("3ba55d").unwrap(), 0x3BA55D);
+        for input in ["", "#fff", "zzzzzz", "#3BA55D00"] {
+            assert!(parse_colour(input).is_err(), "{:?} parsed", input);
+        }
+    }
+}
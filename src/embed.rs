@@ -1,104 +1,451 @@
-use crate::redis_io;
-use crate::redis_io::SquadStatus;
-use redis;
+use crate::error::SquadError;
+use crate::events;
+use crate::locale;
+use crate::locale::Text;
+use crate::store;
+use crate::store::{Composition, GuildSettings, SquadStatus, SquadStore, StoreError};
+use chrono::Utc;
 use serenity::builder::{
-    CreateActionRow, CreateButton, CreateComponents, CreateInteractionResponseData, EditMessage,
+    CreateActionRow, CreateButton, CreateComponents, CreateEmbed, CreateInteractionResponseData,
+    CreateMessage, EditMessage,
 };
 use serenity::client::Context;
 use serenity::model::id::RoleId;
-use serenity::model::id::{ChannelId, MessageId, UserId};
-use serenity::model::interactions::message_component::ButtonStyle;
+use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+use serenity::model::interactions::message_component::{ButtonStyle, InputTextStyle};
 use serenity::model::mention::Mention;
 use serenity::prelude::Mentionable;
 use serenity::utils::Colour;
-use std::collections::HashMap;
-use std::error::Error;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use typemap_rev::TypeMapKey;
+
+/// Number of squads shown on each page of /squad list.
+const LIST_PAGE_SIZE: usize = 5;
+/// Version of the custom ids of components sent by SquadBot, prefixed to each of them.
+/// Components sent before custom ids were versioned are still understood, see
+/// squad::parse_component_id.
+pub const COMPONENT_VERSION: &str = "sq1";
+
+pub struct RenderedPostings;
+
+/// Globally available TypeMapKey to store the colour and description each posting was
+/// last rendered with, so that postings are only edited when their content changes.
+impl TypeMapKey for RenderedPostings {
+    type Value = Arc<Mutex<HashMap<MessageId, String>>>;
+}
 
 pub enum ButtonChoice {
-    Hours(u8),
-    Leave(String),
+    /// Seconds picked from the availability menu of a posting.
+    Availability(u32),
+    /// The custom option of the availability menu, opening the form to give an exact
+    /// end time.
+    CustomAvailability,
+    Commit,
+    Leave,
+    /// A role slot picked from the slot select menu.
+    Slot(String),
+    /// Owner control to fill the squad early with whoever has joined.
+    Close,
+    /// Owner control to cancel the squad.
+    Cancel,
+    /// Owner control to bring up the menu of members to kick.
+    Kick,
+    /// A member picked from the kick menu, along with the posting it was opened from.
+    KickMember {
+        message_id: String,
+        user_id: String,
+    },
+    /// A page of the /squad list picked with its Previous and Next buttons.
+    Page(usize),
+    /// Reminder control for a member to stay available for longer in the given squad.
+    Extend(String),
+    /// Reminder control for the owner to keep the given squad open for longer.
+    ExtendSquad(String),
+    /// Reminder control for the owner to fill the given squad with whoever has joined.
+    CloseSquad(String),
+    /// Owner control to approve the given request to post their squad elsewhere.
+    ApproveLink(u64),
+    /// Owner control to deny the given request to post their squad elsewhere.
+    DenyLink(u64),
+    /// A posting picked from the menu of postings of a squad, for its owner to remove.
+    Unlink {
+        squad_id: String,
+        message_id: String,
+    },
+}
+
+/// Versioned custom id of a component, e.g. "sq1:leave" for "leave" or
+/// "sq1:kick:<message id>" for "kick:<message id>".
+pub fn component_id(id: impl std::fmt::Display) -> String {
+    format!("{}:{}", COMPONENT_VERSION, id)
 }
 
-/// Creates a message component button, which can either be an hour selection or a
-/// "Leave Squad" button.
-fn button(choice: ButtonChoice) -> CreateButton {
+/// Creates a message component button, such as the "Join" button for scheduled squads,
+/// the "Leave Squad" button or an owner control, labelled in the given language.
+fn button(choice: ButtonChoice, text: &Text) -> CreateButton {
     let mut b = CreateButton::default();
     match choice {
-        ButtonChoice::Hours(hours) => {
-            b.custom_id(hours.to_string().to_ascii_lowercase());
-            b.label(hours.to_string());
+        ButtonChoice::Commit => {
+            b.custom_id(component_id("commit"));
+            b.label(text.join_button);
+            b.style(ButtonStyle::Success);
+        }
+        ButtonChoice::Leave => {
+            b.custom_id(component_id("leave"));
+            b.label(text.leave_button);
+            b.style(ButtonStyle::Danger);
+        }
+        ButtonChoice::Close => {
+            b.custom_id(component_id("close"));
+            b.label(text.close_button);
+            b.style(ButtonStyle::Secondary);
+        }
+        ButtonChoice::Kick => {
+            b.custom_id(component_id("kick"));
+            b.label(text.kick_button);
+            b.style(ButtonStyle::Secondary);
+        }
+        ButtonChoice::Cancel => {
+            b.custom_id(component_id("cancel"));
+            b.label(text.cancel_button);
+            b.style(ButtonStyle::Danger);
+        }
+        ButtonChoice::Extend(squad_id) => {
+            b.custom_id(component_id(format!("extend:{}", squad_id)));
+            b.label(text.extend_button);
+            b.style(ButtonStyle::Primary);
+        }
+        ButtonChoice::ExtendSquad(squad_id) => {
+            b.custom_id(component_id(format!("extend_squad:{}", squad_id)));
+            b.label(text.extend_squad_button);
             b.style(ButtonStyle::Primary);
         }
-        ButtonChoice::Leave(s) => {
-            b.custom_id(&s);
-            b.label(&s);
+        ButtonChoice::CloseSquad(squad_id) => {
+            b.custom_id(component_id(format!("close_squad:{}", squad_id)));
+            b.label(text.close_squad_button);
+            b.style(ButtonStyle::Success);
+        }
+        ButtonChoice::ApproveLink(request) => {
+            b.custom_id(component_id(format!("link_approve:{}", request)));
+            b.label(text.approve_button);
+            b.style(ButtonStyle::Success);
+        }
+        ButtonChoice::DenyLink(request) => {
+            b.custom_id(component_id(format!("link_deny:{}", request)));
+            b.label(text.deny_button);
             b.style(ButtonStyle::Danger);
         }
+        ButtonChoice::Availability(_)
+        | ButtonChoice::CustomAvailability
+        | ButtonChoice::Slot(_)
+        | ButtonChoice::KickMember { .. }
+        | ButtonChoice::Unlink { .. } => {
+            unreachable!("Picked from a select menu.")
+        }
+        ButtonChoice::Page(_) => {
+            unreachable!("Built along with the /squad list page.")
+        }
     }
     b
 }
 
-/// Build first row of message component buttons (Maximum 5).
-fn hours_selection_row_1() -> CreateActionRow {
+/// Build the row of buttons sent along with a DM, such as a reminder.
+pub fn dm_row(buttons: Vec<ButtonChoice>, text: &Text) -> CreateActionRow {
     let mut ar = CreateActionRow::default();
-    ar.add_button(button(ButtonChoice::Hours(1)));
-    ar.add_button(button(ButtonChoice::Hours(2)));
-    ar.add_button(button(ButtonChoice::Hours(3)));
-    ar.add_button(button(ButtonChoice::Hours(4)));
-    ar.add_button(button(ButtonChoice::Hours(5)));
+    for choice in buttons {
+        ar.add_button(button(choice, text));
+    }
     ar
 }
 
-/// Build second row of message component buttons (Maximum 5).
-fn hours_selection_row_2() -> CreateActionRow {
+/// Display name of a role slot, e.g. "Tank" for the slot tank.
+pub fn slot_label(slot: &str) -> String {
+    let mut chars = slot.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Build the row holding the select menu to pick a role slot from.
+fn slot_menu_row(composition: &Composition, text: &Text) -> CreateActionRow {
     let mut ar = CreateActionRow::default();
-    ar.add_button(button(ButtonChoice::Hours(6)));
-    ar.add_button(button(ButtonChoice::Hours(7)));
-    ar.add_button(button(ButtonChoice::Hours(8)));
-    ar.add_button(button(ButtonChoice::Hours(9)));
-    ar.add_button(button(ButtonChoice::Hours(10)));
+    ar.create_select_menu(|menu| {
+        menu.custom_id(component_id("slot"));
+        menu.placeholder(text.role_placeholder);
+        menu.options(|options| {
+            for (slot, seats) in composition {
+                options.create_option(|option| {
+                    option.label(format!("{} ({})", slot_label(slot), seats));
+                    option.value(slot)
+                });
+            }
+            options
+        })
+    });
     ar
 }
 
-/// Build last row of message component buttons.
-fn options_row() -> CreateActionRow {
+/// Short form of an availability preset in seconds, e.g. "30m", "2h" or "1h 30m".
+pub fn format_availability(seconds: u64) -> String {
+    let minutes = seconds / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}m", minutes),
+        (hours, 0) => format!("{}h", hours),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
+
+/// Build the row holding the select menu to pick how long to be available from,
+/// offering the presets of the guild followed by a custom option.
+fn availability_menu_row(presets: &[u64], text: &Text) -> CreateActionRow {
     let mut ar = CreateActionRow::default();
-    ar.add_button(button(ButtonChoice::Leave(String::from("Leave Squad"))));
+    ar.create_select_menu(|menu| {
+        menu.custom_id(component_id("join"));
+        menu.placeholder(text.availability_placeholder);
+        menu.options(|options| {
+            for seconds in presets.iter().take(store::MAX_AVAILABILITY_PRESETS) {
+                options.create_option(|option| {
+                    option.label(format_availability(*seconds));
+                    option.value(seconds)
+                });
+            }
+            options.create_option(|option| {
+                option.label(text.custom_availability);
+                option.description(text.custom_availability_hint);
+                option.value("custom")
+            })
+        })
+    });
     ar
 }
 
-/// Assemble all rows of action buttons into one component.
-fn action_rows(c: &mut CreateComponents) -> &mut CreateComponents {
-    c.add_action_row(hours_selection_row_1());
-    c.add_action_row(hours_selection_row_2());
-    c.add_action_row(options_row());
-    c
+/// Build the form opened from the custom option of the availability menu, asking
+/// until when the user is available. The form remembers the posting it was opened
+/// from.
+pub fn availability_modal<'a, 'b>(
+    m: &'b mut CreateInteractionResponseData<'a>,
+    message_id: MessageId,
+    text: &Text,
+) -> &'b mut CreateInteractionResponseData<'a> {
+    m.custom_id(component_id(format!("until:{}", message_id.as_u64())));
+    m.title(text.availability_title);
+    m.components(|c| {
+        c.create_action_row(|ar| {
+            ar.create_input_text(|input| {
+                input.custom_id("until");
+                input.label(text.availability_label);
+                input.placeholder(text.availability_example);
+                input.style(InputTextStyle::Short);
+                input.max_length(50);
+                input.required(true)
+            })
+        })
+    });
+    m
 }
 
-/// Color of side of embed
-fn get_colour() -> Colour {
-    Colour::from_rgb(59, 165, 93)
+/// Build last row of message component buttons, including the owner controls.
+/// Scheduled squads are joined from this row rather than by choosing hours.
+fn options_row(scheduled: bool, text: &Text) -> CreateActionRow {
+    let mut ar = CreateActionRow::default();
+    if scheduled {
+        ar.add_button(button(ButtonChoice::Commit, text));
+    }
+    ar.add_button(button(ButtonChoice::Leave, text));
+    ar.add_button(button(ButtonChoice::Close, text));
+    ar.add_button(button(ButtonChoice::Kick, text));
+    ar.add_button(button(ButtonChoice::Cancel, text));
+    ar
 }
 
-/// Base description included on forming squad postings.
-pub fn create_description(capacity: u8, role_id: Option<RoleId>) -> String {
-    match role_id {
-        Some(r) => {
-            format!(
-                "{}\n\n1️⃣ Use the number reacts to indicate for how many hours you are available.\n\n\
-                SquadBot will message you when at least {} people are ready.\n\n",
-                r.mention(),
-                capacity.to_string()
-            )
-        }
-        None => {
-            format!(
-                "1️⃣ Use the number reacts to indicate for how many hours you are available.\n\n\
-                SquadBot will message you when at least {} people are ready.\n\n",
-                capacity.to_string()
-            )
+/// Build the ephemeral message listing the members of a squad that its owner can
+/// kick, in the given language. The menu remembers the posting it was opened from.
+pub fn kick_menu<'a, 'b>(
+    m: &'b mut CreateInteractionResponseData<'a>,
+    message_id: &str,
+    members: &[(UserId, String)],
+    text: &Text,
+) -> &'b mut CreateInteractionResponseData<'a> {
+    m.content(text.kick_prompt);
+    m.ephemeral(true);
+    m.components(|c| {
+        c.create_action_row(|ar| {
+            ar.create_select_menu(|menu| {
+                menu.custom_id(component_id(format!("kick:{}", message_id)));
+                menu.placeholder(text.kick_placeholder);
+                menu.options(|options| {
+                    for (user_id, name) in members {
+                        options.create_option(|option| {
+                            option.label(name);
+                            option.value(user_id.as_u64().to_string())
+                        });
+                    }
+                    options
+                })
+            })
+        })
+    });
+    m
+}
+
+/// Build the ephemeral message answering /squad links: the given description of the
+/// postings of a squad, along with a menu to remove one of them. Each posting is
+/// given with its message id and a label naming its server and channel. The menu is
+/// labelled in the given language.
+pub fn links_menu<'a, 'b>(
+    m: &'b mut CreateInteractionResponseData<'a>,
+    squad_id: &str,
+    description: &str,
+    postings: &[(MessageId, String)],
+    text: &Text,
+) -> &'b mut CreateInteractionResponseData<'a> {
+    m.content(description);
+    m.ephemeral(true);
+    if postings.is_empty() {
+        return m;
+    }
+    m.components(|c| {
+        c.create_action_row(|ar| {
+            ar.create_select_menu(|menu| {
+                menu.custom_id(component_id(format!("unlink:{}", squad_id)));
+                menu.placeholder(text.unlink_placeholder);
+                menu.options(|options| {
+                    // Select menus hold at most 25 options
+                    for (message_id, label) in postings.iter().take(25) {
+                        options.create_option(|option| {
+                            option.label(label.chars().take(100).collect::<String>());
+                            option.value(message_id.as_u64().to_string())
+                        });
+                    }
+                    options
+                })
+            })
+        })
+    });
+    m
+}
+
+/// Line of /squad list describing a forming squad, with a link to its posting, in the
+/// given language.
+pub fn list_line(
+    capacity: u8,
+    members: usize,
+    ttl: u64,
+    role_id: Option<RoleId>,
+    link: &str,
+    text: &Text,
+) -> String {
+    let role = match role_id {
+        Some(r) => format!(" · {}", r.mention()),
+        None => String::new(),
+    };
+    locale::fill(
+        text.list_line,
+        &[&members, &capacity, &format_ttl(ttl), &role, &link],
+    )
+}
+
+/// Build a page of the ephemeral /squad list, with buttons to the pages before and
+/// after it when the list doesn't fit on one page, in the given language.
+pub fn list_page<'a, 'b>(
+    m: &'b mut CreateInteractionResponseData<'a>,
+    lines: &[String],
+    page: usize,
+    text: &Text,
+) -> &'b mut CreateInteractionResponseData<'a> {
+    let pages = lines.len().div_ceil(LIST_PAGE_SIZE).max(1);
+    let page = page.min(pages - 1);
+    let description = match lines.is_empty() {
+        true => String::from(text.list_empty),
+        false => lines
+            .iter()
+            .skip(page * LIST_PAGE_SIZE)
+            .take(LIST_PAGE_SIZE)
+            .cloned()
+            .collect::<Vec<String>>()
+            .join("\n\n"),
+    };
+    m.ephemeral(true);
+    m.embed(|e| {
+        e.title(text.list_title);
+        e.description(description);
+        e.footer(|f| f.text(locale::fill(text.list_page, &[&(page + 1), &pages])));
+        e
+    });
+    m.components(|c| {
+        if pages > 1 {
+            c.create_action_row(|ar| {
+                ar.create_button(|b| {
+                    b.custom_id(component_id(format!("list:{}", page.saturating_sub(1))));
+                    b.label(text.previous_button);
+                    b.style(ButtonStyle::Secondary);
+                    b.disabled(page == 0)
+                });
+                ar.create_button(|b| {
+                    b.custom_id(component_id(format!("list:{}", page + 1)));
+                    b.label(text.next_button);
+                    b.style(ButtonStyle::Secondary);
+                    b.disabled(page + 1 == pages)
+                })
+            });
         }
+        c
+    });
+    m
+}
+
+/// Assemble all rows of action buttons into one component. Squads with role slots
+/// get a select menu to pick a slot from above the buttons, and squads without a
+/// start time a select menu to pick how long to be available from the presets of the
+/// guild. Components are labelled in the language of the guild.
+fn action_rows<'a>(
+    c: &'a mut CreateComponents,
+    scheduled: bool,
+    composition: &Composition,
+    settings: &GuildSettings,
+) -> &'a mut CreateComponents {
+    let text = settings.locale().text();
+    if !composition.is_empty() {
+        c.add_action_row(slot_menu_row(composition, text));
+    }
+    if !scheduled {
+        c.add_action_row(availability_menu_row(&settings.availability(), text));
+    }
+    c.add_action_row(options_row(scheduled, text));
+    c
+}
+
+/// Base description included on forming squad postings, telling members how many
+/// of them have to join before SquadBot messages them.
+pub fn create_description(
+    notify_at: u8,
+    role_id: Option<RoleId>,
+    start: Option<i64>,
+    has_slots: bool,
+    text: &Text,
+) -> String {
+    let mut mention = match role_id {
+        Some(r) => format!("{}\n\n", r.mention()),
+        None => String::new(),
+    };
+    if has_slots {
+        mention.push_str(&format!("{}\n\n", text.pick_role));
     }
+    let instructions = match start {
+        Some(start) => locale::fill(
+            text.scheduled_instructions,
+            &[
+                &discord_timestamp(start, 'F'),
+                &discord_timestamp(start, 'R'),
+                &notify_at,
+            ],
+        ),
+        None => locale::fill(text.instructions, &[&notify_at]),
+    };
+    format!("{}{}\n\n", mention, instructions)
 }
 
 /// Formats seconds to readable time syntax.
@@ -112,74 +459,282 @@ pub fn format_ttl(ttl: u64) -> String {
     }
 }
 
-/// Used to build the initial squad posting
+/// Formats a time-to-live in seconds as a Discord timestamp, which clients render in
+/// the reader's timezone and keep up to date. Rounded to the minute so that repeated
+/// renders of the same expiry produce the same text.
+pub fn format_timestamp(ttl: u64, style: char) -> String {
+    let expires = Utc::now().timestamp() + ttl as i64;
+    discord_timestamp((expires + 30) / 60 * 60, style)
+}
+
+/// Formats a unix timestamp as a Discord timestamp in the given style, e.g. 'F' for
+/// the full date and time or 'R' for relative time.
+pub fn discord_timestamp(timestamp: i64, style: char) -> String {
+    format!("<t:{}:{}>", timestamp, style)
+}
+
+/// Used to build the initial squad posting. Members are told that they'll be messaged
+/// once notify_at of them have joined: the quorum of the squad if it has one,
+/// otherwise its capacity.
 pub fn build_embed<'a, 'b>(
     m: &'b mut CreateInteractionResponseData<'a>,
-    squad_id: &String,
-    capacity: u8,
+    squad_id: &str,
+    notify_at: u8,
     role_id: Option<RoleId>,
+    start: Option<i64>,
+    composition: &Composition,
+    settings: &GuildSettings,
 ) -> &'b mut CreateInteractionResponseData<'a> {
-    let description = create_description(capacity, role_id);
     m.embed(|e| {
-        e.title("Assemble your squad!");
-        e.description(description);
-        e.colour(get_colour());
-        e.footer(|f| f.text(format!("ID: {}", &squad_id)));
-        e
+        posting_embed(
+            e,
+            squad_id,
+            notify_at,
+            role_id,
+            start,
+            composition,
+            settings,
+        )
+    });
+    m.components(|c| action_rows(c, start.is_some(), composition, settings));
+    m
+}
+
+/// Used to build the posting of a squad sent to a channel outside of an interaction,
+/// such as a link approved by the squad owner. See build_embed.
+pub fn build_posting_message<'a, 'b>(
+    m: &'b mut CreateMessage<'a>,
+    squad_id: &str,
+    notify_at: u8,
+    role_id: Option<RoleId>,
+    start: Option<i64>,
+    composition: &Composition,
+    settings: &GuildSettings,
+) -> &'b mut CreateMessage<'a> {
+    m.embed(|e| {
+        posting_embed(
+            e,
+            squad_id,
+            notify_at,
+            role_id,
+            start,
+            composition,
+            settings,
+        )
     });
-    m.components(|c| action_rows(c));
+    m.components(|c| action_rows(c, start.is_some(), composition, settings));
     m
 }
 
+/// Embed of the initial squad posting.
+fn posting_embed<'a>(
+    e: &'a mut CreateEmbed,
+    squad_id: &str,
+    notify_at: u8,
+    role_id: Option<RoleId>,
+    start: Option<i64>,
+    composition: &Composition,
+    settings: &GuildSettings,
+) -> &'a mut CreateEmbed {
+    let text = settings.locale().text();
+    let has_slots = !composition.is_empty();
+    let description = create_description(notify_at, role_id, start, has_slots, text);
+    e.title(text.posting_title);
+    e.description(description);
+    e.colour(Colour::new(settings.colour()));
+    e.footer(|f| f.text(format!("ID: {}", &squad_id)));
+    e
+}
+
+/// Name of a channel along with its server, e.g. "#lfg (My Server)", for channels of
+/// other servers that users can't open. Falls back to a mention when the channel is
+/// missing from the cache.
+pub fn channel_label(ctx: &Context, channel_id: ChannelId) -> String {
+    match ctx.cache.guild_channel(channel_id) {
+        Some(channel) => match channel.guild_id.name(&ctx.cache) {
+            Some(guild) => format!("#{} ({})", channel.name, guild),
+            None => format!("#{}", channel.name),
+        },
+        None => channel_id.mention().to_string(),
+    }
+}
+
+/// Reference to a channel for the given user: a mention if the user is in the server
+/// of the channel, otherwise its name along with its server, since mentions of
+/// channels in other servers can't be opened.
+pub async fn channel_reference(ctx: &Context, channel_id: ChannelId, user_id: UserId) -> String {
+    match ctx.cache.guild_channel(channel_id) {
+        Some(channel) if channel.guild_id.member(ctx, user_id).await.is_err() => {
+            channel_label(ctx, channel_id)
+        }
+        _ => channel_id.mention().to_string(),
+    }
+}
+
+/// Name of the server each member of a squad joined through, for squads posted in
+/// more than one server. Empty for squads posted in a single server, whose members
+/// all joined through it.
+pub async fn member_servers(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+) -> Result<HashMap<UserId, String>, StoreError> {
+    let guilds: HashSet<GuildId> = store
+        .get_channels(squad_id)
+        .await?
+        .into_iter()
+        .filter_map(|channel_id| ctx.cache.guild_channel(channel_id))
+        .map(|channel| channel.guild_id)
+        .collect();
+    if guilds.len() < 2 {
+        return Ok(HashMap::new());
+    }
+    let servers = store
+        .get_origins(squad_id)
+        .await?
+        .into_iter()
+        .filter_map(|(user_id, channel_id)| {
+            let guild_id = ctx.cache.guild_channel(channel_id)?.guild_id;
+            Some((user_id, guild_id.name(&ctx.cache)?))
+        })
+        .collect();
+    Ok(servers)
+}
+
+/// Mention of a squad member, tagged with the server they joined through if known.
+pub fn member_mention(user_id: UserId, servers: &HashMap<UserId, String>) -> String {
+    match servers.get(&user_id) {
+        Some(server) => format!("{} · {}", Mention::from(user_id), server),
+        None => Mention::from(user_id).to_string(),
+    }
+}
+
+/// Roster line of a forming squad member. Members of scheduled squads are committed
+/// to the start time, other members are shown with the time they're available until.
+fn member_line(
+    user_id: UserId,
+    ttl: u64,
+    start: Option<i64>,
+    servers: &HashMap<UserId, String>,
+    text: &Text,
+) -> String {
+    let mention = member_mention(user_id, servers);
+    match start {
+        Some(_) => format!("{}\n", mention),
+        None => format!(
+            "{}\n",
+            locale::fill(
+                text.available_until,
+                &[&mention, &format_timestamp(ttl, 't')]
+            )
+        ),
+    }
+}
+
 /// Build embed description dependent upon squad status
 /// Forming squad: Displays current squad members, their availability, and remaining
 ///     duration of the squad posting
-/// Filled squad: Displays the filled squad roster.
+/// Filled squad: Displays the filled squad roster, along with the voice channel and
+///     thread opened for the posting, if any.
+/// Cancelled squad: Notes that the owner cancelled the squad.
 /// Expired squad: Mostly blank embed.
-pub fn build_description(
-    con: &mut redis::Connection,
-    squad_id: &String,
+/// Members are tagged with the server they joined through, as given by member_servers,
+/// and the description is written in the given language.
+pub async fn build_description(
+    store: &dyn SquadStore,
+    squad_id: &str,
     squad_status: &SquadStatus,
-    message_id: &String,
-) -> Result<String, redis::RedisError> {
+    message_id: &str,
+    servers: &HashMap<UserId, String>,
+    text: &Text,
+) -> Result<String, StoreError> {
     // Build description based on squad status.
     let description = match squad_status {
-        SquadStatus::Expired => String::from("🔴 This squad has expired."),
+        SquadStatus::Expired => String::from(text.status_expired),
+        SquadStatus::Cancelled => String::from(text.status_cancelled),
         SquadStatus::Forming => {
-            let capacity: u8 = redis_io::get_capacity(con, &squad_id)?;
-            let members: HashMap<UserId, u64> = redis_io::get_members(con, &squad_id)?;
-            let squad_ttl = redis_io::get_ttl(con, &squad_id)?;
-            let posting_id = redis_io::posting_id(&message_id);
-            let role_id = redis_io::get_role_id(con, &posting_id)?;
-            let base_description = create_description(capacity, role_id);
+            let capacity: u8 = store.get_capacity(squad_id).await?;
+            let members: HashMap<UserId, u64> = store.get_members(squad_id).await?;
+            let squad_ttl = store.get_squad_ttl(squad_id).await?;
+            let role_id = store.get_role_id(message_id).await?;
+            let start = store.get_start(squad_id).await?;
+            let composition = store.get_composition(squad_id).await?;
+            let quorum = store.get_quorum(squad_id).await?;
+            let notify_at = quorum.unwrap_or(capacity);
+            let base_description =
+                create_description(notify_at, role_id, start, !composition.is_empty(), text);
             let mut roster = String::new();
-            for (key, value) in &members {
-                let mention = format!("{}", Mention::from(*key));
-                let ttl = format_ttl(*value);
-                let line = &format!("{} available for {}\n", mention, ttl)[..];
-                roster.push_str(line);
+            if composition.is_empty() {
+                for (key, value) in &members {
+                    roster.push_str(&member_line(*key, *value, start, servers, text));
+                }
+            } else {
+                // Group members by slot, showing the seats still open in each
+                let slots = store.get_slots(squad_id).await?;
+                for (slot, seats) in &composition {
+                    let in_slot: Vec<(&UserId, &u64)> = members
+                        .iter()
+                        .filter(|(key, _)| slots.get(key) == Some(slot))
+                        .collect();
+                    roster.push_str(&format!(
+                        "**{}** ({}/{})\n",
+                        slot_label(slot),
+                        in_slot.len(),
+                        seats
+                    ));
+                    for (key, value) in &in_slot {
+                        roster.push_str(&member_line(**key, **value, start, servers, text));
+                    }
+                    for _ in in_slot.len()..usize::from(*seats) {
+                        roster.push_str(&format!("{}\n", text.open_seat));
+                    }
+                }
             }
-            let status = format!(
-                "🟡 This squad is still forming. Time left: {}",
-                format_ttl(squad_ttl),
-            );
+            // Users waiting for a spot are listed in the order they will be promoted
+            let waitlist = store.get_waitlist(squad_id).await?;
+            let mut bench = String::new();
+            if !waitlist.is_empty() {
+                bench.push_str(&format!("{}\n", text.waitlist));
+                for (position, user_id) in waitlist.iter().enumerate() {
+                    let line = format!("{}. {}\n", position + 1, Mention::from(*user_id));
+                    bench.push_str(&line);
+                }
+                bench.push('\n');
+            }
+            // Squads that reached their quorum keep taking late joiners until full
+            let status = match store.is_ready(squad_id).await? {
+                true => locale::fill(
+                    text.status_playing,
+                    &[&members.len(), &capacity, &format_timestamp(squad_ttl, 'R')],
+                ),
+                false => locale::fill(text.status_forming, &[&format_timestamp(squad_ttl, 'R')]),
+            };
             format!(
-                "{}**Current Squad**\n{}\n{}",
-                base_description, roster, status
+                "{}{}\n{}\n{}{}",
+                base_description, text.current_squad, roster, bench, status
             )
         }
         SquadStatus::Filled => {
             let mut roster = String::new();
-            let members: HashMap<UserId, u64> = redis_io::get_members(con, &squad_id)?;
-            for (key, _value) in &members {
-                let mention = format!("{}", Mention::from(*key));
-                let line = &format!("{}\n", mention)[..];
+            let members: HashMap<UserId, u64> = store.get_members(squad_id).await?;
+            for key in members.keys() {
+                let line = &format!("{}\n", member_mention(*key, servers))[..];
                 roster.push_str(line);
             }
+            let rooms = store.get_rooms(message_id).await?;
+            let mut links = String::new();
+            if let Some(voice) = rooms.voice {
+                links.push_str(&format!("🔊 {}\n", Mention::from(voice)));
+            }
+            if let Some(thread) = rooms.thread {
+                links.push_str(&format!("💬 {}\n", Mention::from(thread)));
+            }
+            if !links.is_empty() {
+                links.push('\n');
+            }
             format!(
-                "**Squad**\n{}\n{}",
-                roster,
-                String::from("🟢 This squad has been filled!")
+                "{}\n{}\n{}{}",
+                text.squad, roster, links, text.status_filled
             )
         }
     };
@@ -187,21 +742,93 @@ pub fn build_description(
     Ok(description)
 }
 
+/// Build the description of /squad info for a squad that hasn't expired: its status,
+/// owner, start time, members and the channels it was posted in, as seen by the given
+/// user, in the given language.
+pub async fn build_info(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+    squad_status: &SquadStatus,
+    user_id: UserId,
+    text: &Text,
+) -> Result<String, StoreError> {
+    let servers = member_servers(ctx, store, squad_id).await?;
+    let capacity = store.get_capacity(squad_id).await?;
+    let members: HashMap<UserId, u64> = store.get_members(squad_id).await?;
+    let start = store.get_start(squad_id).await?;
+    let ready = store.is_ready(squad_id).await?;
+    let status = match squad_status {
+        SquadStatus::Forming if ready => {
+            let closes = format_timestamp(store.get_squad_ttl(squad_id).await?, 'R');
+            locale::fill(text.info_playing, &[&closes])
+        }
+        SquadStatus::Forming => {
+            let closes = format_timestamp(store.get_squad_ttl(squad_id).await?, 'R');
+            locale::fill(text.info_forming, &[&closes])
+        }
+        SquadStatus::Filled => String::from(text.info_filled),
+        SquadStatus::Cancelled => String::from(text.info_cancelled),
+        SquadStatus::Expired => String::from(text.info_expired),
+    };
+    let mut info = format!("{}\n", locale::fill(text.info_status, &[&status]));
+    if let Some(owner) = store.get_owner(squad_id).await? {
+        let owner = locale::fill(text.info_owner, &[&Mention::from(owner)]);
+        info.push_str(&format!("{}\n", owner));
+    }
+    if let Some(start) = start {
+        let start = locale::fill(text.info_start, &[&discord_timestamp(start, 'F')]);
+        info.push_str(&format!("{}\n", start));
+    }
+    if let Some(quorum) = store.get_quorum(squad_id).await? {
+        info.push_str(&format!(
+            "{}\n",
+            locale::fill(text.info_minimum, &[&quorum])
+        ));
+    }
+    let heading = locale::fill(text.info_members, &[&members.len(), &capacity]);
+    info.push_str(&format!("\n{}\n", heading));
+    let slots = store.get_slots(squad_id).await?;
+    for (key, value) in &members {
+        if let Some(slot) = slots.get(key) {
+            info.push_str(&format!("{} · ", slot_label(slot)));
+        }
+        info.push_str(&member_line(*key, *value, start, &servers, text));
+    }
+    let waitlist = store.get_waitlist(squad_id).await?;
+    if !waitlist.is_empty() {
+        let waiting = locale::fill(text.info_waiting, &[&waitlist.len()]);
+        info.push_str(&format!("\n{}\n", waiting));
+    }
+    info.push_str(&format!("\n{}\n", text.channels));
+    for channel_id in store.get_channels(squad_id).await? {
+        let channel = channel_reference(ctx, channel_id, user_id).await;
+        info.push_str(&format!("{}\n", channel));
+    }
+    Ok(info)
+}
+
 /// Used to update the posting after it has been created.
-/// Forming squad: Displays buttons to join and leave squad.
+/// Forming squad: Displays a menu to join for a chosen time and buttons to leave the
+///     squad, where scheduled squads are joined with a single button rather than by
+///     choosing a time, and a menu to pick a role slot for squads with slots.
 /// Filled squad: Buttons are removed.
+/// Cancelled squad: Buttons are removed.
 /// Expired squad: Buttons are removed.
 pub fn update_embed<'a, 'b>(
     m: &'b mut EditMessage<'a>,
-    squad_id: &String,
+    squad_id: &str,
     squad_status: SquadStatus,
     description: &String,
+    scheduled: bool,
+    composition: &Composition,
+    settings: &GuildSettings,
 ) -> &'b mut EditMessage<'a> {
     // Build embed
     m.embed(|e| {
-        e.title("Assemble your squad!");
+        e.title(settings.locale().text().posting_title);
         e.description(description);
-        e.colour(get_colour());
+        e.colour(Colour::new(settings.colour()));
         e.footer(|f| f.text(format!("ID: {}", &squad_id)));
         e
     });
@@ -209,7 +836,7 @@ pub fn update_embed<'a, 'b>(
     // Add or remove interaction buttons based on squad status.
     match squad_status {
         SquadStatus::Forming => {
-            m.components(|c| action_rows(c));
+            m.components(|c| action_rows(c, scheduled, composition, settings));
         }
         _ => {
             m.set_components(CreateComponents(Vec::new()));
@@ -218,21 +845,86 @@ pub fn update_embed<'a, 'b>(
     m
 }
 
-/// Sends updated squad posting to channel.
+/// Retrieve the record of rendered postings from the global data context.
+pub async fn get_rendered_postings(ctx: &Context) -> Arc<Mutex<HashMap<MessageId, String>>> {
+    let mut data = ctx.data.write().await;
+    let rendered = data
+        .entry::<RenderedPostings>()
+        .or_insert_with(|| Arc::new(Mutex::new(HashMap::new())));
+    Arc::clone(rendered)
+}
+
+/// Sends every updated posting of a squad to its channel. Postings that fail to update
+/// are logged and skipped.
+pub async fn build_messages(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+) -> Result<(), SquadError> {
+    let postings = store.get_squad_postings(squad_id).await?;
+    for (key, value) in &postings {
+        let result = build_message(ctx, value, store, &key.to_string()).await;
+        if let Err(why) = result {
+            eprintln!("Error building message: {}", why);
+        };
+    }
+    Ok(())
+}
+
+/// Sends updated squad posting to channel, unless it already shows the same content,
+/// and updates the scheduled event of the posting along with it.
 pub async fn build_message(
     ctx: &Context,
     channel_id: &ChannelId,
-    con: &mut redis::Connection,
-    message_id: &String,
-) -> Result<(), Box<dyn Error>> {
-    let squad_id = redis_io::get_squad_id(con, &message_id)?;
-    let squad_status = redis_io::get_squad_status(con, &squad_id)?;
-    let description = build_description(con, &squad_id, &squad_status, &message_id)?;
-    let message_id_u64 = message_id.parse()?;
+    store: &dyn SquadStore,
+    message_id: &str,
+) -> Result<(), SquadError> {
+    let squad_id = store.get_squad_id(message_id).await?;
+    let squad_status = store.get_squad_status(&squad_id).await?;
+    let servers = member_servers(ctx, store, &squad_id).await?;
+    let settings = store::get_channel_settings(ctx, store, *channel_id).await?;
+    let text = settings.locale().text();
+    let description =
+        build_description(store, &squad_id, &squad_status, message_id, &servers, text).await?;
+    let (scheduled, composition) = match squad_status {
+        SquadStatus::Forming => (
+            store.get_start(&squad_id).await?.is_some(),
+            store.get_composition(&squad_id).await?,
+        ),
+        _ => (false, Vec::new()),
+    };
+    let message_id = MessageId(message_id.parse()?);
+    // Postings are rendered again when their colour, availability presets or language
+    // change
+    let rendering = format!(
+        "{:06x}{}{}{}",
+        settings.colour(),
+        settings.locale().as_str(),
+        store::format_availability(&settings.availability()),
+        description
+    );
+    let rendered = get_rendered_postings(ctx).await;
+    if rendered.lock().await.get(&message_id) == Some(&rendering) {
+        return Ok(());
+    }
     channel_id
-        .edit_message(&ctx, MessageId(message_id_u64), |m| {
-            update_embed(m, &squad_id, squad_status, &description)
+        .edit_message(&ctx, message_id, |m| {
+            update_embed(
+                m,
+                &squad_id,
+                squad_status,
+                &description,
+                scheduled,
+                &composition,
+                &settings,
+            )
         })
         .await?;
+    rendered.lock().await.insert(message_id, rendering);
+    // Squad events follow the content of their posting
+    let result = events::sync_event(ctx, store, *channel_id, &message_id.to_string()).await;
+    if let Err(why) = result {
+        eprintln!("Error updating squad event: {}", why);
+    }
     Ok(())
 }
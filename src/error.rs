@@ -0,0 +1,153 @@
+use crate::locale::{Key, Locale, Message};
+use std::error::Error;
+use std::fmt;
+use std::num::{ParseIntError, TryFromIntError};
+
+/// Errors of squad commands, interactions and storage. Errors caused by what a user
+/// asked for are answered with an ephemeral message explaining what went wrong; the
+/// rest are logged and answered with a generic apology.
+#[derive(Debug)]
+pub enum SquadError {
+    /// No live squad or posting matches the given id, e.g. a mistyped or expired id.
+    UnknownSquad,
+    /// The squad has already been filled.
+    SquadFilled,
+    /// The squad was cancelled by its owner.
+    SquadCancelled,
+    /// The user isn't a member of the squad.
+    NotMember,
+    /// The user tried an owner control of a squad they don't own.
+    NotOwner,
+    /// The owner of the squad doesn't let anyone else post it elsewhere.
+    LinksDisabled,
+    /// The squad isn't posted where the user tried to join it by id, and its owner
+    /// doesn't let anyone post it elsewhere without asking.
+    NotPosted,
+    /// The squad has no role slot by the given name.
+    UnknownSlot,
+    /// Every seat of the picked role slot is taken.
+    SlotTaken,
+    /// The user may not create squads in this server.
+    NotCreator,
+    /// The user lacks the Manage Server permission.
+    NotManager,
+    /// The command can only be used in a server.
+    GuildOnly,
+    /// An option of a command was given a value that can't be used, explained by a
+    /// message of the catalogue.
+    InvalidInput(Message),
+    /// The command isn't one SquadBot knows how to handle.
+    UnknownCommand,
+    /// The component has a custom id that SquadBot doesn't know, e.g. from a message
+    /// it didn't send or a version it no longer reads.
+    UnknownComponent,
+    /// The storage backend failed.
+    Store(Box<dyn Error + Send + Sync>),
+    /// A request to Discord failed.
+    Discord(Box<serenity::Error>),
+}
+
+impl SquadError {
+    /// Build an error for an option value that can't be used, explained by the given
+    /// message of the catalogue.
+    pub fn invalid(key: Key) -> SquadError {
+        SquadError::invalid_with(key, &[])
+    }
+
+    /// Build an error for an option value that can't be used, explained by the given
+    /// message of the catalogue filled in with the given values.
+    pub fn invalid_with(key: Key, values: &[&dyn fmt::Display]) -> SquadError {
+        SquadError::InvalidInput(Message::new(key, values))
+    }
+
+    /// Build an error for a value of the given option that fails to parse.
+    pub fn unparsable(option: &str) -> SquadError {
+        SquadError::invalid_with(|text| text.invalid_option, &[&option])
+    }
+
+    /// Build an error for a failure of the storage backend.
+    pub fn store(error: impl Into<Box<dyn Error + Send + Sync>>) -> SquadError {
+        SquadError::Store(error.into())
+    }
+
+    /// Whether the error is a failure of SquadBot rather than of what the user asked.
+    pub fn is_internal(&self) -> bool {
+        matches!(
+            self,
+            SquadError::UnknownCommand | SquadError::Store(_) | SquadError::Discord(_)
+        )
+    }
+
+    /// Message shown to the user whose command or click failed, in the given language.
+    pub fn reply(&self, locale: Locale) -> String {
+        let text = locale.text();
+        let message = match self {
+            SquadError::UnknownSquad => text.unknown_squad,
+            SquadError::SquadFilled => text.squad_filled,
+            SquadError::SquadCancelled => text.squad_cancelled,
+            SquadError::NotMember => text.not_member,
+            SquadError::NotOwner => text.not_owner,
+            SquadError::LinksDisabled => text.links_disabled,
+            SquadError::NotPosted => text.not_posted,
+            SquadError::UnknownSlot => text.unknown_slot,
+            SquadError::SlotTaken => text.slot_taken,
+            SquadError::NotCreator => text.not_creator,
+            SquadError::NotManager => text.not_manager,
+            SquadError::GuildOnly => text.guild_only,
+            SquadError::InvalidInput(message) => return message.render(locale),
+            SquadError::UnknownComponent => text.unknown_component,
+            SquadError::UnknownCommand | SquadError::Store(_) | SquadError::Discord(_) => {
+                text.internal_error
+            }
+        };
+        String::from(message)
+    }
+}
+
+impl fmt::Display for SquadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SquadError::UnknownCommand => write!(f, "Unknown command."),
+            SquadError::Store(why) => write!(f, "Store error: {}", why),
+            SquadError::Discord(why) => write!(f, "Discord error: {}", why),
+            _ => write!(f, "{}", self.reply(Locale::English)),
+        }
+    }
+}
+
+impl Error for SquadError {}
+
+impl From<redis::RedisError> for SquadError {
+    fn from(error: redis::RedisError) -> SquadError {
+        SquadError::Store(Box::new(error))
+    }
+}
+
+impl From<rusqlite::Error> for SquadError {
+    /// Lookups that find no row are lookups of squads or postings that don't exist.
+    fn from(error: rusqlite::Error) -> SquadError {
+        match error {
+            rusqlite::Error::QueryReturnedNoRows => SquadError::UnknownSquad,
+            error => SquadError::Store(Box::new(error)),
+        }
+    }
+}
+
+impl From<serenity::Error> for SquadError {
+    fn from(error: serenity::Error) -> SquadError {
+        SquadError::Discord(Box::new(error))
+    }
+}
+
+impl From<ParseIntError> for SquadError {
+    /// Ids and numbers read back from storage that fail to parse.
+    fn from(error: ParseIntError) -> SquadError {
+        SquadError::Store(Box::new(error))
+    }
+}
+
+impl From<TryFromIntError> for SquadError {
+    fn from(error: TryFromIntError) -> SquadError {
+        SquadError::Store(Box::new(error))
+    }
+}
@@ -0,0 +1,162 @@
+use crate::error::SquadError;
+use crate::locale;
+use crate::locale::Text;
+use crate::rooms;
+use crate::store::{SquadStatus, SquadStore};
+use chrono::Utc;
+use serenity::client::Context;
+use serenity::model::guild::ScheduledEventType;
+use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+use serenity::model::Timestamp;
+
+/// Seconds from now at which the event of a squad that filled without a start time
+/// starts, since Discord only accepts events that start in the future.
+const EVENT_LEAD_SECONDS: i64 = 60;
+/// Shortest time in seconds that the event of a squad lasts.
+const MIN_EVENT_SECONDS: i64 = 15 * 60;
+/// Longest description Discord accepts for an event.
+const MAX_DESCRIPTION_LENGTH: usize = 1000;
+
+/// Create, update or delete the Discord scheduled event of a squad posting, as far as
+/// the guild of the posting opted into events. Scheduled squads get an event as soon
+/// as they are posted, other squads once they fill or reach their quorum. Events
+/// follow the roster and voice channel of the squad, are given in the language of the
+/// guild and are deleted when the squad is cancelled.
+pub async fn sync_event(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    channel_id: ChannelId,
+    message_id: &str,
+) -> Result<(), SquadError> {
+    let guild_id = match ctx.cache.guild_channel(channel_id) {
+        Some(channel) => channel.guild_id,
+        None => return Ok(()),
+    };
+    let settings = store.get_guild_settings(guild_id).await?;
+    if !settings.events() {
+        return Ok(());
+    }
+    let text = settings.locale().text();
+    let squad_id = store.get_squad_id(message_id).await?;
+    let event_id = store.get_event(message_id).await?;
+    let start = store.get_start(&squad_id).await?;
+    let ready = store.is_ready(&squad_id).await?;
+    match store.get_squad_status(&squad_id).await? {
+        SquadStatus::Cancelled => {
+            if let Some(event_id) = event_id {
+                store.set_event(message_id, None).await?;
+                guild_id.delete_scheduled_event(&ctx.http, event_id).await?;
+            }
+            return Ok(());
+        }
+        SquadStatus::Expired => return Ok(()),
+        // Squads without a start time only get an event once they fill or reach
+        // their quorum
+        SquadStatus::Forming if start.is_none() && !ready => return Ok(()),
+        SquadStatus::Forming | SquadStatus::Filled => {}
+    }
+    let name = rooms::room_name(&squad_id, text);
+    let description = event_description(
+        ctx, store, &squad_id, channel_id, guild_id, message_id, text,
+    )
+    .await?;
+    let voice = store.get_rooms(message_id).await?.voice;
+    let location = MessageId(message_id.parse()?).link(channel_id, Some(guild_id));
+    let (start, end) = event_window(store, &squad_id, start).await?;
+    match event_id {
+        Some(event_id) => {
+            // The start time is left alone, as it can't change once the event started
+            guild_id
+                .edit_scheduled_event(&ctx.http, event_id, |e| {
+                    e.name(&name).description(&description).end_time(end);
+                    match voice {
+                        Some(voice) => e.kind(ScheduledEventType::Voice).channel_id(voice),
+                        None => e.kind(ScheduledEventType::External).location(&location),
+                    }
+                })
+                .await?;
+        }
+        None => {
+            let event = guild_id
+                .create_scheduled_event(&ctx.http, |e| {
+                    e.name(&name)
+                        .description(&description)
+                        .start_time(start)
+                        .end_time(end);
+                    match voice {
+                        Some(voice) => e.kind(ScheduledEventType::Voice).channel_id(voice),
+                        None => e.kind(ScheduledEventType::External).location(&location),
+                    }
+                })
+                .await?;
+            store.set_event(message_id, Some(event.id)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Time window of the event of a squad. Scheduled squads run from their start time
+/// until they expire. Other squads run from now for as long as every member is
+/// available.
+async fn event_window(
+    store: &dyn SquadStore,
+    squad_id: &str,
+    start: Option<i64>,
+) -> Result<(Timestamp, Timestamp), SquadError> {
+    let now = Utc::now().timestamp();
+    let (start, end) = match start {
+        Some(start) => (start, now + store.get_squad_ttl(squad_id).await? as i64),
+        None => {
+            let available = store
+                .get_members(squad_id)
+                .await?
+                .into_values()
+                .min()
+                .unwrap_or(0);
+            (now + EVENT_LEAD_SECONDS, now + available as i64)
+        }
+    };
+    let start = start.max(now + EVENT_LEAD_SECONDS);
+    let end = end.max(start + MIN_EVENT_SECONDS);
+    Ok((timestamp(start), timestamp(end)))
+}
+
+/// Description of the event of a squad in the given language: its roster and a link
+/// to the posting. Names are used rather than mentions, which events don't render.
+async fn event_description(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+    channel_id: ChannelId,
+    guild_id: GuildId,
+    message_id: &str,
+    text: &Text,
+) -> Result<String, SquadError> {
+    let capacity = store.get_capacity(squad_id).await?;
+    let members: Vec<UserId> = store.get_members(squad_id).await?.into_keys().collect();
+    let mut names = Vec::new();
+    for user_id in &members {
+        match user_id.to_user(ctx).await {
+            Ok(user) => names.push(user.name),
+            Err(_) => names.push(String::from(text.unknown_member)),
+        }
+    }
+    let link = MessageId(message_id.parse()?).link(channel_id, Some(guild_id));
+    let mut description = locale::fill(
+        text.event_description,
+        &[&members.len(), &capacity, &names.join(", "), &link],
+    );
+    if description.len() > MAX_DESCRIPTION_LENGTH {
+        let mut end = MAX_DESCRIPTION_LENGTH;
+        while !description.is_char_boundary(end) {
+            end -= 1;
+        }
+        description.truncate(end);
+    }
+    Ok(description)
+}
+
+/// Timestamp of Discord for a unix timestamp in seconds.
+fn timestamp(unix: i64) -> Timestamp {
+    Timestamp::from_unix_timestamp(unix).unwrap_or_else(|_| Timestamp::now())
+}
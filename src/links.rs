@@ -0,0 +1,350 @@
+use crate::embed;
+use crate::error::SquadError;
+use crate::events;
+use crate::locale;
+use crate::locale::{Locale, Text};
+use crate::notify;
+use crate::squad;
+use crate::store;
+use crate::store::{GuildSettings, LinkPolicy, SquadStatus, SquadStore};
+use serenity::client::Context;
+use serenity::model::id::{ChannelId, GuildId, MessageId, RoleId, UserId};
+use serenity::model::interactions::application_command::ApplicationCommandInteraction;
+use serenity::model::interactions::InteractionResponseType;
+use serenity::model::prelude::message_component::MessageComponentInteraction;
+use serenity::model::prelude::Message;
+use serenity::prelude::Mentionable;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use typemap_rev::TypeMapKey;
+
+/// A request to post a squad in a channel, waiting for the owner of the squad to
+/// approve or deny it. The user who asked is told the answer in the language they
+/// asked in.
+#[derive(Clone)]
+pub struct PendingLink {
+    squad_id: String,
+    channel_id: ChannelId,
+    guild_id: Option<GuildId>,
+    user_id: UserId,
+    role_id: Option<RoleId>,
+    locale: Locale,
+    requested: Instant,
+}
+
+pub struct PendingLinks;
+
+/// Globally available TypeMapKey to store the link requests waiting for squad owners,
+/// by the id of the command that made them.
+impl TypeMapKey for PendingLinks {
+    type Value = Arc<Mutex<HashMap<u64, PendingLink>>>;
+}
+
+/// Ask the owner of a squad by DM to approve posting it in the channel of the given
+/// /squad create command, and tell the user who ran it. The owner is asked in the
+/// language of their DMs about the squad. Fails if the owner can't be sent DMs.
+pub async fn request_link(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    squad_id: &str,
+    owner: UserId,
+    role_id: Option<RoleId>,
+) -> Result<(), SquadError> {
+    let store = store::get_store(ctx).await?;
+    let locale = locale::interaction_locale(ctx, command.guild_id, &command.locale).await;
+    let request = command.id.0;
+    let link = PendingLink {
+        squad_id: squad_id.to_string(),
+        channel_id: command.channel_id,
+        guild_id: command.guild_id,
+        user_id: command.user.id,
+        role_id,
+        locale,
+        requested: Instant::now(),
+    };
+    let pending = get_pending_links(ctx).await;
+    {
+        let mut pending = pending.lock().await;
+        // Squads never outlive their maximum duration, and neither do their requests
+        let max_age = Duration::from_secs(store::MAX_SQUAD_TTL);
+        pending.retain(|_, link| link.requested.elapsed() < max_age);
+        pending.insert(request, link);
+    }
+    let owner_text = locale::member_locale(ctx, store.as_ref(), squad_id, owner)
+        .await?
+        .text();
+    let channel = embed::channel_label(ctx, command.channel_id);
+    let description = locale::fill(
+        owner_text.link_request,
+        &[&command.user.mention(), &squad_id, &channel],
+    );
+    let buttons = vec![
+        embed::ButtonChoice::ApproveLink(request),
+        embed::ButtonChoice::DenyLink(request),
+    ];
+    let sent = match owner.create_dm_channel(&ctx.http).await {
+        Ok(dm_channel) => dm_channel
+            .send_message(&ctx.http, |m| {
+                m.embed(|e| {
+                    e.title(owner_text.link_request_title)
+                        .description(&description)
+                });
+                m.components(|c| c.add_action_row(embed::dm_row(buttons, owner_text)))
+            })
+            .await
+            .is_ok(),
+        Err(_) => false,
+    };
+    if !sent {
+        pending.lock().await.remove(&request);
+        return Err(SquadError::invalid(|text| text.link_unreachable));
+    }
+    squad::respond_command(ctx, command, locale.text().link_requested).await?;
+    Ok(())
+}
+
+/// Post a squad where its owner approved, picked from the link request DM of the
+/// owner, and tell the user who asked. Returns the explanation shown to the owner in
+/// the given language. Squads whose owner has since disabled links are not posted.
+/// The request is kept if posting fails, so that the owner can try again.
+pub async fn handle_approve_link(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    request: u64,
+    text: &'static Text,
+) -> Result<Option<&'static str>, SquadError> {
+    let store = store::get_store(ctx).await?;
+    let link = get_pending_link(ctx, store.as_ref(), interaction, request).await?;
+    squad::check_forming(store.get_squad_status(&link.squad_id).await?)?;
+    if store.get_link_policy(&link.squad_id).await? == LinkPolicy::Disabled {
+        forget_pending_link(ctx, request).await;
+        return Err(SquadError::LinksDisabled);
+    }
+    let response = post_link(ctx, store.as_ref(), &link).await?;
+    forget_pending_link(ctx, request).await;
+    let requester_text = link.locale.text();
+    let channel = embed::channel_label(ctx, link.channel_id);
+    let posting = response.id.link(link.channel_id, link.guild_id);
+    let description = locale::fill(
+        requester_text.link_approved,
+        &[&link.squad_id, &channel, &posting],
+    );
+    let title = requester_text.link_approved_title;
+    notify::send_dm(ctx, link.user_id, title, &description).await;
+    Ok(Some(text.link_approved_reply))
+}
+
+/// Drop a request to post a squad, picked from the link request DM of its owner, and
+/// tell the user who asked. Returns the explanation shown to the owner in the given
+/// language.
+pub async fn handle_deny_link(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    request: u64,
+    text: &'static Text,
+) -> Result<Option<&'static str>, SquadError> {
+    let store = store::get_store(ctx).await?;
+    let link = get_pending_link(ctx, store.as_ref(), interaction, request).await?;
+    forget_pending_link(ctx, request).await;
+    let requester_text = link.locale.text();
+    let channel = embed::channel_label(ctx, link.channel_id);
+    let description = locale::fill(requester_text.link_denied, &[&link.squad_id, &channel]);
+    let title = requester_text.link_denied_title;
+    notify::send_dm(ctx, link.user_id, title, &description).await;
+    Ok(Some(text.link_denied_reply))
+}
+
+/// Get a link request that the interacting user answered, if they own its squad.
+async fn get_pending_link(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    interaction: &MessageComponentInteraction,
+    request: u64,
+) -> Result<PendingLink, SquadError> {
+    let pending = get_pending_links(ctx).await;
+    let link = pending.lock().await.get(&request).cloned();
+    let link = match link {
+        Some(link) => link,
+        None => return Err(SquadError::invalid(|text| text.link_expired)),
+    };
+    if store.get_owner(&link.squad_id).await? != Some(interaction.user.id) {
+        return Err(SquadError::NotOwner);
+    }
+    Ok(link)
+}
+
+/// Drop a link request once it has been answered.
+async fn forget_pending_link(ctx: &Context, request: u64) {
+    get_pending_links(ctx).await.lock().await.remove(&request);
+}
+
+/// Post a squad in the channel of an approved link request, the way /squad create
+/// would have, and record the posting.
+async fn post_link(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    link: &PendingLink,
+) -> Result<Message, SquadError> {
+    let settings = match link.guild_id {
+        Some(guild_id) => store.get_guild_settings(guild_id).await?,
+        None => GuildSettings::default(),
+    };
+    let squad_id = &link.squad_id;
+    let capacity = store.get_capacity(squad_id).await?;
+    let notify_at = store.get_quorum(squad_id).await?.unwrap_or(capacity);
+    let start = store.get_start(squad_id).await?;
+    let composition = store.get_composition(squad_id).await?;
+    if let Some(role) = link.role_id {
+        let message = locale::fill(settings.locale().text().squad_forming, &[&role.mention()]);
+        link.channel_id.say(&ctx.http, message).await?;
+    }
+    let response = link
+        .channel_id
+        .send_message(&ctx.http, |m| {
+            embed::build_posting_message(
+                m,
+                squad_id,
+                notify_at,
+                link.role_id,
+                start,
+                &composition,
+                &settings,
+            )
+        })
+        .await?;
+    let ttl = store.get_squad_ttl(squad_id).await? + store::POSTING_GRACE;
+    let channel_id = link.channel_id.as_u64().to_string();
+    let message_id = response.id.as_u64().to_string();
+    store
+        .build_posting(
+            &channel_id,
+            link.guild_id,
+            &message_id,
+            link.role_id,
+            squad_id,
+            ttl,
+        )
+        .await?;
+    squad::announce_squad(
+        ctx,
+        &settings,
+        link.user_id,
+        link.channel_id,
+        link.guild_id,
+        &response,
+    )
+    .await;
+    if let Err(why) = events::sync_event(ctx, store, link.channel_id, &message_id).await {
+        eprintln!("Error creating squad event: {}", why);
+    }
+    Ok(response)
+}
+
+/// Answer /squad links with the link policy and postings of a squad the user owns,
+/// along with a menu to remove postings, after changing its link policy if given.
+pub async fn handle_links_command(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+) -> Result<(), SquadError> {
+    let squad_id = squad::required_squad_id(command).await?;
+    let policy = squad::parse_link_policy(command).await?;
+    let store = store::get_store(ctx).await?;
+    if let SquadStatus::Expired = store.get_squad_status(&squad_id).await? {
+        return Err(SquadError::UnknownSquad);
+    }
+    if store.get_owner(&squad_id).await? != Some(command.user.id) {
+        return Err(SquadError::NotOwner);
+    }
+    if let Some(policy) = policy {
+        store.set_link_policy(&squad_id, policy).await?;
+    }
+    let text = locale::interaction_locale(ctx, command.guild_id, &command.locale)
+        .await
+        .text();
+    let policy = match store.get_link_policy(&squad_id).await? {
+        LinkPolicy::Open => text.link_policy_open,
+        LinkPolicy::Approval => text.link_policy_approval,
+        LinkPolicy::Disabled => text.link_policy_disabled,
+    };
+    let mut postings: Vec<(MessageId, ChannelId)> = store
+        .get_squad_postings(&squad_id)
+        .await?
+        .into_iter()
+        .collect();
+    postings.sort();
+    let mut description = locale::fill(text.links_summary, &[&policy]);
+    let mut labels = Vec::new();
+    for (message_id, channel_id) in postings {
+        let label = embed::channel_label(ctx, channel_id);
+        let guild_id = ctx.cache.guild_channel(channel_id).map(|c| c.guild_id);
+        let link = message_id.link(channel_id, guild_id);
+        description.push_str(&format!("{}: {}\n", label, link));
+        labels.push((message_id, label));
+    }
+    command
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|m| {
+                    embed::links_menu(m, &squad_id, &description, &labels, text)
+                })
+        })
+        .await?;
+    Ok(())
+}
+
+/// Remove the posting picked from the menu of /squad links from its squad, if the
+/// interacting user owns the squad. The posting is marked as removed and its event
+/// deleted. Returns the explanation shown to the user in the given language.
+pub async fn handle_unlink(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    squad_id: &str,
+    message_id: &str,
+    text: &'static Text,
+) -> Result<&'static str, SquadError> {
+    let store = store::get_store(ctx).await?;
+    squad::check_owner(store.as_ref(), squad_id, interaction).await?;
+    let message = MessageId(message_id.parse()?);
+    let channel_id = match store.get_squad_postings(squad_id).await?.get(&message) {
+        Some(channel_id) => *channel_id,
+        None => return Err(SquadError::UnknownSquad),
+    };
+    let event_id = store.get_event(message_id).await?;
+    if !store.delete_posting(message_id).await? {
+        return Err(SquadError::UnknownSquad);
+    }
+    if let (Some(event_id), Some(channel)) = (event_id, ctx.cache.guild_channel(channel_id)) {
+        if let Err(why) = channel
+            .guild_id
+            .delete_scheduled_event(&ctx.http, event_id)
+            .await
+        {
+            eprintln!("Error deleting squad event: {}", why);
+        }
+    }
+    let posting_text = locale::channel_locale(ctx, store.as_ref(), channel_id)
+        .await?
+        .text();
+    let result = channel_id
+        .edit_message(&ctx.http, message, |m| {
+            m.embed(|e| e.description(posting_text.posting_removed));
+            m.components(|c| c)
+        })
+        .await;
+    if let Err(why) = result {
+        eprintln!("Error marking posting as removed: {}", why);
+    }
+    Ok(text.posting_unlinked)
+}
+
+/// Retrieve the link requests waiting for squad owners from the global data context.
+async fn get_pending_links(ctx: &Context) -> Arc<Mutex<HashMap<u64, PendingLink>>> {
+    let mut data = ctx.data.write().await;
+    let pending = data
+        .entry::<PendingLinks>()
+        .or_insert_with(|| Arc::new(Mutex::new(HashMap::new())));
+    Arc::clone(pending)
+}
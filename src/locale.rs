@@ -0,0 +1,1432 @@
+use crate::store;
+use crate::store::{GuildSettings, SquadStore, StoreResult};
+use serenity::builder::CreateApplicationCommand;
+use serenity::client::Context;
+use serenity::json::Value;
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use std::fmt::Display;
+
+/// Languages SquadBot can speak. Servers pick theirs with /squadconfig; replies to
+/// interactions in servers that haven't picked one follow the language of the user.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    English,
+    Spanish,
+    Portuguese,
+}
+
+impl Locale {
+    /// Every language, in the order they are offered.
+    pub const ALL: [Locale; 3] = [Locale::English, Locale::Spanish, Locale::Portuguese];
+
+    /// Code of the language, as stored and given to commands.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::English => "en",
+            Locale::Spanish => "es",
+            Locale::Portuguese => "pt",
+        }
+    }
+
+    /// Parse a language code as stored, or a Discord locale such as "es-ES", "es-419"
+    /// or "pt-BR". None for languages SquadBot doesn't speak.
+    pub fn parse(code: &str) -> Option<Locale> {
+        match code.split('-').next().unwrap_or_default() {
+            "en" => Some(Locale::English),
+            "es" => Some(Locale::Spanish),
+            "pt" => Some(Locale::Portuguese),
+            _ => None,
+        }
+    }
+
+    /// Name of the language in the language itself.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Locale::English => "English",
+            Locale::Spanish => "Español",
+            Locale::Portuguese => "Português",
+        }
+    }
+
+    /// Catalogue of the messages shown to users in the language.
+    pub fn text(&self) -> &'static Text {
+        match self {
+            Locale::English => &ENGLISH,
+            Locale::Spanish => &SPANISH,
+            Locale::Portuguese => &PORTUGUESE,
+        }
+    }
+}
+
+/// Messages shown to users, in one language. Messages with placeholders are filled
+/// in with fill, {0} being the first value.
+pub struct Text {
+    // Squad postings
+    pub posting_title: &'static str,
+    pub pick_role: &'static str,
+    /// {0} start time, {1} start time relative to now, {2} number of members.
+    pub scheduled_instructions: &'static str,
+    /// {0} number of members.
+    pub instructions: &'static str,
+    /// {0} member, {1} time.
+    pub available_until: &'static str,
+    pub open_seat: &'static str,
+    pub current_squad: &'static str,
+    pub squad: &'static str,
+    pub waitlist: &'static str,
+    /// {0} members, {1} capacity, {2} closing time relative to now.
+    pub status_playing: &'static str,
+    /// {0} closing time relative to now.
+    pub status_forming: &'static str,
+    pub status_filled: &'static str,
+    pub status_cancelled: &'static str,
+    pub status_expired: &'static str,
+    pub posting_removed: &'static str,
+    /// {0} role.
+    pub squad_forming: &'static str,
+    // Components
+    pub availability_placeholder: &'static str,
+    pub custom_availability: &'static str,
+    pub custom_availability_hint: &'static str,
+    pub role_placeholder: &'static str,
+    pub join_button: &'static str,
+    pub leave_button: &'static str,
+    pub close_button: &'static str,
+    pub kick_button: &'static str,
+    pub cancel_button: &'static str,
+    pub extend_button: &'static str,
+    pub extend_squad_button: &'static str,
+    pub close_squad_button: &'static str,
+    pub approve_button: &'static str,
+    pub deny_button: &'static str,
+    pub availability_title: &'static str,
+    pub availability_label: &'static str,
+    pub availability_example: &'static str,
+    // Replies
+    /// {0} time.
+    pub available_reply: &'static str,
+    pub joined: &'static str,
+    pub already_member: &'static str,
+    pub waitlisted: &'static str,
+    pub closed: &'static str,
+    pub no_slot: &'static str,
+    pub no_slot_option: &'static str,
+    pub left: &'static str,
+    /// {0} time.
+    pub available_now: &'static str,
+    pub another_hour: &'static str,
+    pub role_changed: &'static str,
+    pub role_picked: &'static str,
+    // Errors
+    pub unknown_squad: &'static str,
+    pub squad_filled: &'static str,
+    pub squad_cancelled: &'static str,
+    pub not_member: &'static str,
+    pub not_owner: &'static str,
+    pub links_disabled: &'static str,
+    pub not_posted: &'static str,
+    pub unknown_slot: &'static str,
+    pub slot_taken: &'static str,
+    pub not_creator: &'static str,
+    pub not_manager: &'static str,
+    pub guild_only: &'static str,
+    pub unknown_component: &'static str,
+    pub internal_error: &'static str,
+    pub invalid_availability: &'static str,
+    pub availability_too_long: &'static str,
+    /// {0} option.
+    pub invalid_option: &'static str,
+    /// {0} option, {1} smallest value, {2} largest value.
+    pub option_range: &'static str,
+    pub invalid_duration: &'static str,
+    pub invalid_start: &'static str,
+    pub start_in_past: &'static str,
+    pub start_too_far: &'static str,
+    /// {0} number of members.
+    pub too_many_members: &'static str,
+    /// {0} smallest size, {1} largest size.
+    pub size_range: &'static str,
+    pub too_short: &'static str,
+    /// {0} duration.
+    pub too_long: &'static str,
+    /// {0} option.
+    pub option_on_link: &'static str,
+    pub links_on_link: &'static str,
+    pub min_too_large: &'static str,
+    pub missing_id: &'static str,
+    pub scheduled_extend: &'static str,
+    /// {0} slot.
+    pub slot_format: &'static str,
+    /// {0} slot.
+    pub slot_name: &'static str,
+    /// {0} slot.
+    pub slot_repeated: &'static str,
+    /// {0} slot.
+    pub slot_size: &'static str,
+    /// {0} slot.
+    pub slot_empty: &'static str,
+    /// {0} number of slots.
+    pub too_many_slots: &'static str,
+    pub link_expired: &'static str,
+    pub link_unreachable: &'static str,
+    /// {0} language code.
+    pub unknown_locale: &'static str,
+    pub size_order: &'static str,
+    pub default_size_range: &'static str,
+    pub max_duration_limit: &'static str,
+    pub voice_cleanup_limit: &'static str,
+    pub presets_too_long: &'static str,
+    pub default_duration_limit: &'static str,
+    /// {0} preset.
+    pub invalid_preset: &'static str,
+    pub no_presets: &'static str,
+    /// {0} number of presets.
+    pub too_many_presets: &'static str,
+    pub invalid_colour: &'static str,
+    // Direct messages
+    pub members: &'static str,
+    pub channels: &'static str,
+    pub join_rooms: &'static str,
+    pub ready_title: &'static str,
+    /// {0} member, {1} duration.
+    pub available_for: &'static str,
+    pub late_title: &'static str,
+    pub late_description: &'static str,
+    pub promoted_title: &'static str,
+    pub promoted_description: &'static str,
+    pub cancelled_title: &'static str,
+    pub cancelled_description: &'static str,
+    pub member_reminder_title: &'static str,
+    /// {0} squad id, {1} time relative to now.
+    pub member_reminder: &'static str,
+    pub squad_reminder_title: &'static str,
+    /// {0} squad id, {1} time relative to now.
+    pub squad_reminder: &'static str,
+    // Owner controls
+    pub kick_prompt: &'static str,
+    pub kick_placeholder: &'static str,
+    pub nobody_to_kick: &'static str,
+    pub member_kicked: &'static str,
+    pub squad_set: &'static str,
+    pub nobody_joined: &'static str,
+    pub squad_extended: &'static str,
+    pub not_cancellable: &'static str,
+    /// {0} user, {1} channel, {2} link to the posting.
+    pub announce: &'static str,
+    // Squad info and lists
+    pub info_title: &'static str,
+    /// {0} closing time relative to now.
+    pub info_playing: &'static str,
+    /// {0} closing time relative to now.
+    pub info_forming: &'static str,
+    pub info_filled: &'static str,
+    pub info_cancelled: &'static str,
+    pub info_expired: &'static str,
+    // States of squads suggested for id options
+    pub choice_forming: &'static str,
+    pub choice_filled: &'static str,
+    pub choice_cancelled: &'static str,
+    pub choice_expired: &'static str,
+    /// {0} status.
+    pub info_status: &'static str,
+    /// {0} owner.
+    pub info_owner: &'static str,
+    /// {0} start time.
+    pub info_start: &'static str,
+    /// {0} number of members.
+    pub info_minimum: &'static str,
+    /// {0} members, {1} capacity.
+    pub info_members: &'static str,
+    /// {0} number of users.
+    pub info_waiting: &'static str,
+    /// {0} members, {1} capacity, {2} time left, {3} role if any, {4} link to the posting.
+    pub list_line: &'static str,
+    pub list_title: &'static str,
+    pub list_empty: &'static str,
+    /// {0} page, {1} number of pages.
+    pub list_page: &'static str,
+    pub previous_button: &'static str,
+    pub next_button: &'static str,
+    // Links
+    pub link_policy_open: &'static str,
+    pub link_policy_approval: &'static str,
+    pub link_policy_disabled: &'static str,
+    /// {0} link policy.
+    pub links_summary: &'static str,
+    pub unlink_placeholder: &'static str,
+    pub posting_unlinked: &'static str,
+    pub link_request_title: &'static str,
+    /// {0} user, {1} squad id, {2} channel.
+    pub link_request: &'static str,
+    pub link_requested: &'static str,
+    pub link_approved_title: &'static str,
+    /// {0} squad id, {1} channel, {2} link to the posting.
+    pub link_approved: &'static str,
+    pub link_approved_reply: &'static str,
+    pub link_denied_title: &'static str,
+    /// {0} squad id, {1} channel.
+    pub link_denied: &'static str,
+    pub link_denied_reply: &'static str,
+    // Events and rooms
+    /// {0} last digits of the squad id.
+    pub room_name: &'static str,
+    /// {0} members, {1} capacity, {2} member names, {3} link to the posting.
+    pub event_description: &'static str,
+    pub unknown_member: &'static str,
+    // Settings
+    pub settings_title: &'static str,
+    pub settings_updated: &'static str,
+    pub settings_reset: &'static str,
+    /// {0} to {14} the values of the settings, in the order they are listed.
+    pub settings_description: &'static str,
+    /// {0} smallest size, {1} largest size.
+    pub size_range_value: &'static str,
+    pub setting_default: &'static str,
+    pub setting_none: &'static str,
+    pub setting_everyone: &'static str,
+    pub setting_on: &'static str,
+    pub setting_off: &'static str,
+    pub setting_language_default: &'static str,
+}
+
+static ENGLISH: Text = Text {
+    posting_title: "Assemble your squad!",
+    pick_role: "🛡️ Pick your role from the menu before joining.",
+    scheduled_instructions:
+        "📅 Starts {0} ({1}).\n\n✅ Use the Join button to commit to this time slot.\n\n\
+        SquadBot will message you at the start time if at least {2} people have joined.",
+    instructions:
+        "⏱️ Pick how long you are available from the menu, or Custom… to give an end time.\n\n\
+        SquadBot will message you when at least {0} people are ready.",
+    available_until: "{0} available until {1}",
+    open_seat: "▫️ Open",
+    current_squad: "**Current Squad**",
+    squad: "**Squad**",
+    waitlist: "**Waitlist**",
+    status_playing: "🟢 This squad is playing ({0}/{1}), late joiners are welcome. Closes {2}",
+    status_forming: "🟡 This squad is still forming. Closes {0}",
+    status_filled: "🟢 This squad has been filled!",
+    status_cancelled: "⚫ This squad was cancelled by its owner.",
+    status_expired: "🔴 This squad has expired.",
+    posting_removed: "⚫ This posting was removed by the squad owner.",
+    squad_forming: "Squad forming! {0}",
+    availability_placeholder: "Join: how long are you available?",
+    custom_availability: "Custom…",
+    custom_availability_hint: "Give the time you are available until",
+    role_placeholder: "Pick your role",
+    join_button: "Join",
+    leave_button: "Leave Squad",
+    close_button: "Close Early",
+    kick_button: "Kick",
+    cancel_button: "Cancel Squad",
+    extend_button: "Stay 1 more hour",
+    extend_squad_button: "Keep open 1 more hour",
+    close_squad_button: "Go with who we have",
+    approve_button: "Approve",
+    deny_button: "Deny",
+    availability_title: "How long are you available?",
+    availability_label: "Available until",
+    availability_example: "e.g. 11:30pm, until 23:00 utc+2 or 1h45m",
+    available_reply: "You're available until {0}.",
+    joined: "You've joined the squad.",
+    already_member: "You're already in this squad, your availability was updated.",
+    waitlisted: "This squad is full, so you've been put on the waitlist. \
+        SquadBot will message you if a spot opens up.",
+    closed: "This squad is no longer accepting members.",
+    no_slot: "Pick your role from the menu before joining.",
+    no_slot_option: "Pick your role with the slot option to join this squad.",
+    left: "You've left the squad.",
+    available_now: "You're now available until {0}.",
+    another_hour: "You're in for another hour.",
+    role_changed: "Your role has been changed.",
+    role_picked: "Role picked, now join the squad to take it.",
+    unknown_squad: "That squad doesn't exist or has expired.",
+    squad_filled: "This squad is already full.",
+    squad_cancelled: "This squad was cancelled by its owner.",
+    not_member: "You're not in that squad.",
+    not_owner: "Only the squad owner can do that.",
+    links_disabled: "The owner of this squad doesn't allow posting it elsewhere.",
+    not_posted: "This squad can only be joined in servers where it is posted.",
+    unknown_slot: "This squad has no such role slot.",
+    slot_taken: "That role is already taken, pick another one.",
+    not_creator: "You don't have a role that can create squads in this server.",
+    not_manager: "You need the Manage Server permission to configure squads.",
+    guild_only: "That can only be done in a server.",
+    unknown_component: "SquadBot doesn't recognise this control anymore.",
+    internal_error: "Something went wrong, please try again later.",
+    invalid_availability:
+        "Unable to parse your availability, try e.g. 11:30pm, until 23:00 or 1h45m.",
+    availability_too_long: "You can be available for at most 24 hours.",
+    invalid_option: "Unable to parse {0}.",
+    option_range: "{0} has to be between {1} and {2}.",
+    invalid_duration: "Unable to parse the duration, try e.g. 45m, 2h or 1h30m.",
+    invalid_start: "Unable to parse start time, try e.g. 21:00, 9pm, tomorrow 8pm or in 2h.",
+    start_in_past: "Start time must be in the future.",
+    start_too_far: "Squads can be scheduled at most 7 days ahead.",
+    too_many_members: "A squad can have at most {0} members.",
+    size_range: "Squads in this server need between {0} and {1} members.",
+    too_short: "Squads need to last at least 5 minutes.",
+    too_long: "Squads in this server can last at most {0}.",
+    option_on_link: "{0} can only be given when creating a new squad.",
+    links_on_link: "Change who may post a squad with /squad links.",
+    min_too_large: "The minimum has to be smaller than the squad size.",
+    missing_id: "Missing id.",
+    scheduled_extend: "Members of scheduled squads stay until it ends.",
+    slot_format: "Slot {0} should look like name:count.",
+    slot_name: "Invalid slot name {0}.",
+    slot_repeated: "Slot {0} is listed more than once.",
+    slot_size: "Invalid size for slot {0}.",
+    slot_empty: "Slot {0} needs at least one seat.",
+    too_many_slots: "A squad can have at most {0} slots.",
+    link_expired: "This link request has expired or was already answered.",
+    link_unreachable: "The owner of this squad approves where it's posted, but can't be reached.",
+    unknown_locale: "SquadBot doesn't speak {0}.",
+    size_order: "The minimum size can't be larger than the maximum size.",
+    default_size_range: "The default size has to be within the size range.",
+    max_duration_limit: "Squads can last at most 7 days.",
+    voice_cleanup_limit: "Empty voice channels can be kept for at most 24 hours.",
+    presets_too_long: "Availability presets can't be longer than the maximum duration.",
+    default_duration_limit: "The default duration can't be longer than the maximum duration.",
+    invalid_preset: "Unable to parse availability preset {0}.",
+    no_presets: "Give availability presets such as 30m 1h 2h.",
+    too_many_presets: "Give at most {0} availability presets.",
+    invalid_colour: "Colours are hex codes such as #3BA55D.",
+    members: "**Members**",
+    channels: "**Channels**",
+    join_rooms: "**Join the squad**",
+    ready_title: "**Your squad is ready!**",
+    available_for: "{0} available for {1}",
+    late_title: "**Your squad is already playing!**",
+    late_description: "You joined a squad that's already playing.",
+    promoted_title: "**You're off the waitlist!**",
+    promoted_description: "A spot opened up and you've joined the squad.",
+    cancelled_title: "**Squad cancelled**",
+    cancelled_description: "The owner cancelled a squad you had joined.",
+    member_reminder_title: "**Still up for it?**",
+    member_reminder: "Your spot in {0} runs out {1}.",
+    squad_reminder_title: "**Your squad is running out of time**",
+    squad_reminder: "Your squad {0} hasn't filled and expires {1}.",
+    kick_prompt: "Pick a member to kick from the squad.",
+    kick_placeholder: "Member to kick",
+    nobody_to_kick: "There is nobody to kick.",
+    member_kicked: "Member kicked from the squad.",
+    squad_set: "Your squad is set, its members have been notified.",
+    nobody_joined: "Nobody has joined this squad yet, cancel it instead.",
+    squad_extended: "Your squad stays open for another hour.",
+    not_cancellable: "This squad can no longer be cancelled.",
+    announce: "{0} started a squad in {1}: {2}",
+    info_title: "Squad info",
+    info_playing: "🟢 Playing, open to late joiners, closes {0}",
+    info_forming: "🟡 Forming, closes {0}",
+    info_filled: "🟢 Filled",
+    info_cancelled: "⚫ Cancelled by its owner",
+    info_expired: "🔴 Expired",
+    choice_forming: "forming",
+    choice_filled: "filled",
+    choice_cancelled: "cancelled",
+    choice_expired: "expired",
+    info_status: "**Status:** {0}",
+    info_owner: "**Owner:** {0}",
+    info_start: "**Starts:** {0}",
+    info_minimum: "**Minimum:** {0}",
+    info_members: "**Members** ({0}/{1})",
+    info_waiting: "**Waitlist:** {0} waiting",
+    list_line: "**{0}/{1}** joined · {2} left{3}\n[Jump to posting]({4})",
+    list_title: "Forming squads",
+    list_empty: "No squads are forming in this server right now.",
+    list_page: "Page {0} of {1}",
+    previous_button: "Previous",
+    next_button: "Next",
+    link_policy_open: "Anyone can post this squad in other channels and servers.",
+    link_policy_approval: "Others need your approval to post this squad in other channels and servers.",
+    link_policy_disabled: "Only you can post this squad in other channels and servers.",
+    links_summary: "**Links:** {0}\n\n**Postings**\n",
+    unlink_placeholder: "Posting to remove",
+    posting_unlinked: "Posting removed from the squad.",
+    link_request_title: "**Link request**",
+    link_request: "{0} wants to post your squad {1} in {2}.",
+    link_requested: "The owner of this squad approves where it's posted. They've been asked to approve posting it here.",
+    link_approved_title: "**Link approved**",
+    link_approved: "The owner of squad {0} approved posting it in {1}: {2}",
+    link_approved_reply: "Link approved, the squad has been posted.",
+    link_denied_title: "**Link denied**",
+    link_denied: "The owner of squad {0} declined posting it in {1}.",
+    link_denied_reply: "Link denied.",
+    room_name: "Squad {0}",
+    event_description: "Squad ({0}/{1}): {2}\n\nJoin or leave the squad here: {3}",
+    unknown_member: "Unknown member",
+    settings_title: "Squad settings",
+    settings_updated: "Squad settings updated",
+    settings_reset: "Squad settings reset",
+    settings_description: "**Default size:** {0}\n\
+        **Size range:** {1}\n\
+        **Default duration:** {2}\n\
+        **Maximum duration:** {3}\n\
+        **Announcement channel:** {4}\n\
+        **Colour:** {5}\n\
+        **Creator roles:** {6}\n\
+        **DMs:** {7}\n\
+        **Voice channel category:** {8}\n\
+        **Threads:** {9}\n\
+        **Empty voice channels kept for:** {10}\n\
+        **Events:** {11}\n\
+        **Reminders:** {12}\n\
+        **Availability presets:** {13}\n\
+        **Language:** {14}",
+    size_range_value: "{0} to {1}",
+    setting_default: " (default)",
+    setting_none: "None",
+    setting_everyone: "Everyone",
+    setting_on: "On",
+    setting_off: "Off",
+    setting_language_default: "English, replies in the language of each member",
+};
+
+static SPANISH: Text = Text {
+    posting_title: "¡Arma tu escuadra!",
+    pick_role: "🛡️ Elige tu rol en el menú antes de unirte.",
+    scheduled_instructions: "📅 Empieza {0} ({1}).\n\n✅ Usa el botón Unirse para comprometerte con este horario.\n\n\
+        SquadBot te enviará un mensaje a la hora de inicio si se han unido al menos {2} personas.",
+    instructions: "⏱️ Elige en el menú cuánto tiempo estás disponible, o Personalizado… para indicar una hora de fin.\n\n\
+        SquadBot te enviará un mensaje cuando al menos {0} personas estén listas.",
+    available_until: "{0} disponible hasta las {1}",
+    open_seat: "▫️ Libre",
+    current_squad: "**Escuadra actual**",
+    squad: "**Escuadra**",
+    waitlist: "**Lista de espera**",
+    status_playing: "🟢 Esta escuadra está jugando ({0}/{1}), quien llegue tarde es bienvenido. Cierra {2}",
+    status_forming: "🟡 Esta escuadra aún se está formando. Cierra {0}",
+    status_filled: "🟢 ¡Esta escuadra está completa!",
+    status_cancelled: "⚫ Esta escuadra fue cancelada por su dueño.",
+    status_expired: "🔴 Esta escuadra ha expirado.",
+    posting_removed: "⚫ El dueño de la escuadra quitó esta publicación.",
+    squad_forming: "¡Escuadra en formación! {0}",
+    availability_placeholder: "Unirse: ¿cuánto tiempo estás disponible?",
+    custom_availability: "Personalizado…",
+    custom_availability_hint: "Indica hasta qué hora estás disponible",
+    role_placeholder: "Elige tu rol",
+    join_button: "Unirse",
+    leave_button: "Salir de la escuadra",
+    close_button: "Cerrar ya",
+    kick_button: "Expulsar",
+    cancel_button: "Cancelar escuadra",
+    extend_button: "Quedarme 1 hora más",
+    extend_squad_button: "Mantener abierta 1 hora más",
+    close_squad_button: "Jugar con los que hay",
+    approve_button: "Aprobar",
+    deny_button: "Rechazar",
+    availability_title: "¿Cuánto tiempo estás disponible?",
+    availability_label: "Disponible hasta",
+    availability_example: "p. ej. 11:30pm, hasta 23:00 utc+2 o 1h45m",
+    available_reply: "Estás disponible hasta las {0}.",
+    joined: "Te uniste a la escuadra.",
+    already_member: "Ya estás en esta escuadra, se actualizó tu disponibilidad.",
+    waitlisted: "Esta escuadra está llena, así que quedaste en la lista de espera. \
+        SquadBot te enviará un mensaje si se libera un lugar.",
+    closed: "Esta escuadra ya no acepta miembros.",
+    no_slot: "Elige tu rol en el menú antes de unirte.",
+    no_slot_option: "Elige tu rol con la opción slot para unirte a esta escuadra.",
+    left: "Saliste de la escuadra.",
+    available_now: "Ahora estás disponible hasta las {0}.",
+    another_hour: "Te quedas una hora más.",
+    role_changed: "Tu rol cambió.",
+    role_picked: "Rol elegido, ahora únete a la escuadra para ocuparlo.",
+    unknown_squad: "Esa escuadra no existe o ha expirado.",
+    squad_filled: "Esta escuadra ya está completa.",
+    squad_cancelled: "Esta escuadra fue cancelada por su dueño.",
+    not_member: "No estás en esa escuadra.",
+    not_owner: "Solo el dueño de la escuadra puede hacer eso.",
+    links_disabled: "El dueño de esta escuadra no permite publicarla en otros lugares.",
+    not_posted: "Solo puedes unirte a esta escuadra en servidores donde está publicada.",
+    unknown_slot: "Esta escuadra no tiene ese rol.",
+    slot_taken: "Ese rol ya está ocupado, elige otro.",
+    not_creator: "No tienes un rol que pueda crear escuadras en este servidor.",
+    not_manager: "Necesitas el permiso Gestionar servidor para configurar las escuadras.",
+    guild_only: "Eso solo se puede hacer en un servidor.",
+    unknown_component: "SquadBot ya no reconoce este control.",
+    internal_error: "Algo salió mal, inténtalo de nuevo más tarde.",
+    invalid_availability: "No se pudo entender tu disponibilidad, prueba p. ej. 11:30pm, hasta 23:00 o 1h45m.",
+    availability_too_long: "Puedes estar disponible como máximo 24 horas.",
+    invalid_option: "No se pudo entender {0}.",
+    option_range: "{0} tiene que estar entre {1} y {2}.",
+    invalid_duration: "No se pudo entender la duración, prueba p. ej. 45m, 2h o 1h30m.",
+    invalid_start: "No se pudo entender la hora de inicio, prueba p. ej. 21:00, 9pm, tomorrow 8pm o in 2h.",
+    start_in_past: "La hora de inicio tiene que ser en el futuro.",
+    start_too_far: "Las escuadras se pueden programar como máximo con 7 días de antelación.",
+    too_many_members: "Una escuadra puede tener como máximo {0} miembros.",
+    size_range: "Las escuadras de este servidor necesitan entre {0} y {1} miembros.",
+    too_short: "Las escuadras tienen que durar al menos 5 minutos.",
+    too_long: "Las escuadras de este servidor pueden durar como máximo {0}.",
+    option_on_link: "{0} solo se puede indicar al crear una escuadra nueva.",
+    links_on_link: "Cambia quién puede publicar una escuadra con /squad links.",
+    min_too_large: "El mínimo tiene que ser menor que el tamaño de la escuadra.",
+    missing_id: "Falta el id.",
+    scheduled_extend: "Los miembros de escuadras programadas se quedan hasta que terminan.",
+    slot_format: "El rol {0} debería tener la forma nombre:cantidad.",
+    slot_name: "Nombre de rol no válido: {0}.",
+    slot_repeated: "El rol {0} aparece más de una vez.",
+    slot_size: "Tamaño no válido para el rol {0}.",
+    slot_empty: "El rol {0} necesita al menos un lugar.",
+    too_many_slots: "Una escuadra puede tener como máximo {0} roles.",
+    link_expired: "Esta solicitud de enlace expiró o ya fue respondida.",
+    link_unreachable: "El dueño de esta escuadra aprueba dónde se publica, pero no se le puede contactar.",
+    unknown_locale: "SquadBot no habla {0}.",
+    size_order: "El tamaño mínimo no puede ser mayor que el máximo.",
+    default_size_range: "El tamaño por defecto tiene que estar dentro del rango de tamaño.",
+    max_duration_limit: "Las escuadras pueden durar como máximo 7 días.",
+    voice_cleanup_limit: "Los canales de voz vacíos se pueden conservar como máximo 24 horas.",
+    presets_too_long: "Las opciones de disponibilidad no pueden ser más largas que la duración máxima.",
+    default_duration_limit: "La duración por defecto no puede ser mayor que la duración máxima.",
+    invalid_preset: "No se pudo entender la opción de disponibilidad {0}.",
+    no_presets: "Indica opciones de disponibilidad como 30m 1h 2h.",
+    too_many_presets: "Indica como máximo {0} opciones de disponibilidad.",
+    invalid_colour: "Los colores son códigos hexadecimales como #3BA55D.",
+    members: "**Miembros**",
+    channels: "**Canales**",
+    join_rooms: "**Únete a la escuadra**",
+    ready_title: "**¡Tu escuadra está lista!**",
+    available_for: "{0} disponible por {1}",
+    late_title: "**¡Tu escuadra ya está jugando!**",
+    late_description: "Te uniste a una escuadra que ya está jugando.",
+    promoted_title: "**¡Saliste de la lista de espera!**",
+    promoted_description: "Se liberó un lugar y te uniste a la escuadra.",
+    cancelled_title: "**Escuadra cancelada**",
+    cancelled_description: "El dueño canceló una escuadra a la que te habías unido.",
+    member_reminder_title: "**¿Sigues disponible?**",
+    member_reminder: "Tu lugar en {0} vence {1}.",
+    squad_reminder_title: "**A tu escuadra se le acaba el tiempo**",
+    squad_reminder: "Tu escuadra {0} no se ha completado y expira {1}.",
+    kick_prompt: "Elige a un miembro para expulsarlo de la escuadra.",
+    kick_placeholder: "Miembro a expulsar",
+    nobody_to_kick: "No hay nadie a quien expulsar.",
+    member_kicked: "Miembro expulsado de la escuadra.",
+    squad_set: "Tu escuadra está lista, se avisó a sus miembros.",
+    nobody_joined: "Nadie se ha unido a esta escuadra todavía, cancélala en su lugar.",
+    squad_extended: "Tu escuadra sigue abierta una hora más.",
+    not_cancellable: "Esta escuadra ya no se puede cancelar.",
+    announce: "{0} empezó una escuadra en {1}: {2}",
+    info_title: "Información de la escuadra",
+    info_playing: "🟢 Jugando, abierta a quienes lleguen tarde, cierra {0}",
+    info_forming: "🟡 En formación, cierra {0}",
+    info_filled: "🟢 Completa",
+    info_cancelled: "⚫ Cancelada por su dueño",
+    info_expired: "🔴 Expirada",
+    choice_forming: "formándose",
+    choice_filled: "completa",
+    choice_cancelled: "cancelada",
+    choice_expired: "expirada",
+    info_status: "**Estado:** {0}",
+    info_owner: "**Dueño:** {0}",
+    info_start: "**Empieza:** {0}",
+    info_minimum: "**Mínimo:** {0}",
+    info_members: "**Miembros** ({0}/{1})",
+    info_waiting: "**Lista de espera:** {0} esperando",
+    list_line: "**{0}/{1}** unidos · quedan {2}{3}\n[Ir a la publicación]({4})",
+    list_title: "Escuadras en formación",
+    list_empty: "No hay escuadras formándose en este servidor ahora mismo.",
+    list_page: "Página {0} de {1}",
+    previous_button: "Anterior",
+    next_button: "Siguiente",
+    link_policy_open: "Cualquiera puede publicar esta escuadra en otros canales y servidores.",
+    link_policy_approval: "Los demás necesitan tu aprobación para publicar esta escuadra en otros canales y servidores.",
+    link_policy_disabled: "Solo tú puedes publicar esta escuadra en otros canales y servidores.",
+    links_summary: "**Enlaces:** {0}\n\n**Publicaciones**\n",
+    unlink_placeholder: "Publicación a quitar",
+    posting_unlinked: "Publicación quitada de la escuadra.",
+    link_request_title: "**Solicitud de enlace**",
+    link_request: "{0} quiere publicar tu escuadra {1} en {2}.",
+    link_requested: "El dueño de esta escuadra aprueba dónde se publica. Se le pidió que apruebe publicarla aquí.",
+    link_approved_title: "**Enlace aprobado**",
+    link_approved: "El dueño de la escuadra {0} aprobó publicarla en {1}: {2}",
+    link_approved_reply: "Enlace aprobado, la escuadra fue publicada.",
+    link_denied_title: "**Enlace rechazado**",
+    link_denied: "El dueño de la escuadra {0} rechazó publicarla en {1}.",
+    link_denied_reply: "Enlace rechazado.",
+    room_name: "Escuadra {0}",
+    event_description: "Escuadra ({0}/{1}): {2}\n\nÚnete o sal de la escuadra aquí: {3}",
+    unknown_member: "Miembro desconocido",
+    settings_title: "Ajustes de escuadras",
+    settings_updated: "Ajustes de escuadras actualizados",
+    settings_reset: "Ajustes de escuadras restablecidos",
+    settings_description: "**Tamaño por defecto:** {0}\n\
+        **Rango de tamaño:** {1}\n\
+        **Duración por defecto:** {2}\n\
+        **Duración máxima:** {3}\n\
+        **Canal de anuncios:** {4}\n\
+        **Color:** {5}\n\
+        **Roles que crean escuadras:** {6}\n\
+        **Mensajes directos:** {7}\n\
+        **Categoría de canales de voz:** {8}\n\
+        **Hilos:** {9}\n\
+        **Canales de voz vacíos se conservan:** {10}\n\
+        **Eventos:** {11}\n\
+        **Recordatorios:** {12}\n\
+        **Opciones de disponibilidad:** {13}\n\
+        **Idioma:** {14}",
+    size_range_value: "{0} a {1}",
+    setting_default: " (por defecto)",
+    setting_none: "Ninguno",
+    setting_everyone: "Todos",
+    setting_on: "Activado",
+    setting_off: "Desactivado",
+    setting_language_default: "Inglés, respuestas en el idioma de cada miembro",
+};
+
+static PORTUGUESE: Text = Text {
+    posting_title: "Monte seu esquadrão!",
+    pick_role: "🛡️ Escolha sua função no menu antes de entrar.",
+    scheduled_instructions: "📅 Começa {0} ({1}).\n\n✅ Use o botão Entrar para se comprometer com este horário.\n\n\
+        O SquadBot vai te mandar uma mensagem no horário de início se pelo menos {2} pessoas tiverem entrado.",
+    instructions: "⏱️ Escolha no menu por quanto tempo você está disponível, ou Personalizado… para informar um horário de término.\n\n\
+        O SquadBot vai te mandar uma mensagem quando pelo menos {0} pessoas estiverem prontas.",
+    available_until: "{0} disponível até {1}",
+    open_seat: "▫️ Livre",
+    current_squad: "**Esquadrão atual**",
+    squad: "**Esquadrão**",
+    waitlist: "**Lista de espera**",
+    status_playing: "🟢 Este esquadrão está jogando ({0}/{1}), quem chegar depois é bem-vindo. Fecha {2}",
+    status_forming: "🟡 Este esquadrão ainda está se formando. Fecha {0}",
+    status_filled: "🟢 Este esquadrão está completo!",
+    status_cancelled: "⚫ Este esquadrão foi cancelado pelo dono.",
+    status_expired: "🔴 Este esquadrão expirou.",
+    posting_removed: "⚫ O dono do esquadrão removeu esta publicação.",
+    squad_forming: "Esquadrão se formando! {0}",
+    availability_placeholder: "Entrar: por quanto tempo você está disponível?",
+    custom_availability: "Personalizado…",
+    custom_availability_hint: "Informe até que horas você está disponível",
+    role_placeholder: "Escolha sua função",
+    join_button: "Entrar",
+    leave_button: "Sair do esquadrão",
+    close_button: "Fechar agora",
+    kick_button: "Expulsar",
+    cancel_button: "Cancelar esquadrão",
+    extend_button: "Ficar mais 1 hora",
+    extend_squad_button: "Manter aberto mais 1 hora",
+    close_squad_button: "Jogar com quem tem",
+    approve_button: "Aprovar",
+    deny_button: "Recusar",
+    availability_title: "Por quanto tempo você está disponível?",
+    availability_label: "Disponível até",
+    availability_example: "ex. 11:30pm, até 23:00 utc-3 ou 1h45m",
+    available_reply: "Você está disponível até {0}.",
+    joined: "Você entrou no esquadrão.",
+    already_member: "Você já está neste esquadrão, sua disponibilidade foi atualizada.",
+    waitlisted: "Este esquadrão está cheio, então você entrou na lista de espera. \
+        O SquadBot vai te mandar uma mensagem se abrir uma vaga.",
+    closed: "Este esquadrão não aceita mais membros.",
+    no_slot: "Escolha sua função no menu antes de entrar.",
+    no_slot_option: "Escolha sua função com a opção slot para entrar neste esquadrão.",
+    left: "Você saiu do esquadrão.",
+    available_now: "Agora você está disponível até {0}.",
+    another_hour: "Você fica mais uma hora.",
+    role_changed: "Sua função foi alterada.",
+    role_picked: "Função escolhida, agora entre no esquadrão para ocupá-la.",
+    unknown_squad: "Esse esquadrão não existe ou expirou.",
+    squad_filled: "Este esquadrão já está completo.",
+    squad_cancelled: "Este esquadrão foi cancelado pelo dono.",
+    not_member: "Você não está nesse esquadrão.",
+    not_owner: "Só o dono do esquadrão pode fazer isso.",
+    links_disabled: "O dono deste esquadrão não permite publicá-lo em outros lugares.",
+    not_posted: "Você só pode entrar neste esquadrão em servidores onde ele está publicado.",
+    unknown_slot: "Este esquadrão não tem essa função.",
+    slot_taken: "Essa função já está ocupada, escolha outra.",
+    not_creator: "Você não tem um cargo que possa criar esquadrões neste servidor.",
+    not_manager: "Você precisa da permissão Gerenciar servidor para configurar os esquadrões.",
+    guild_only: "Isso só pode ser feito em um servidor.",
+    unknown_component: "O SquadBot não reconhece mais este controle.",
+    internal_error: "Algo deu errado, tente novamente mais tarde.",
+    invalid_availability: "Não foi possível entender sua disponibilidade, tente ex. 11:30pm, até 23:00 ou 1h45m.",
+    availability_too_long: "Você pode ficar disponível por no máximo 24 horas.",
+    invalid_option: "Não foi possível entender {0}.",
+    option_range: "{0} precisa estar entre {1} e {2}.",
+    invalid_duration: "Não foi possível entender a duração, tente ex. 45m, 2h ou 1h30m.",
+    invalid_start: "Não foi possível entender o horário de início, tente ex. 21:00, 9pm, tomorrow 8pm ou in 2h.",
+    start_in_past: "O horário de início precisa ser no futuro.",
+    start_too_far: "Esquadrões podem ser agendados com no máximo 7 dias de antecedência.",
+    too_many_members: "Um esquadrão pode ter no máximo {0} membros.",
+    size_range: "Esquadrões neste servidor precisam ter entre {0} e {1} membros.",
+    too_short: "Esquadrões precisam durar pelo menos 5 minutos.",
+    too_long: "Esquadrões neste servidor podem durar no máximo {0}.",
+    option_on_link: "{0} só pode ser informado ao criar um novo esquadrão.",
+    links_on_link: "Altere quem pode publicar um esquadrão com /squad links.",
+    min_too_large: "O mínimo precisa ser menor que o tamanho do esquadrão.",
+    missing_id: "Falta o id.",
+    scheduled_extend: "Membros de esquadrões agendados ficam até o fim.",
+    slot_format: "A função {0} deveria ter a forma nome:quantidade.",
+    slot_name: "Nome de função inválido: {0}.",
+    slot_repeated: "A função {0} aparece mais de uma vez.",
+    slot_size: "Tamanho inválido para a função {0}.",
+    slot_empty: "A função {0} precisa de pelo menos uma vaga.",
+    too_many_slots: "Um esquadrão pode ter no máximo {0} funções.",
+    link_expired: "Este pedido de link expirou ou já foi respondido.",
+    link_unreachable: "O dono deste esquadrão aprova onde ele é publicado, mas não pode ser contatado.",
+    unknown_locale: "O SquadBot não fala {0}.",
+    size_order: "O tamanho mínimo não pode ser maior que o máximo.",
+    default_size_range: "O tamanho padrão precisa estar dentro da faixa de tamanho.",
+    max_duration_limit: "Esquadrões podem durar no máximo 7 dias.",
+    voice_cleanup_limit: "Canais de voz vazios podem ser mantidos por no máximo 24 horas.",
+    presets_too_long: "As opções de disponibilidade não podem ser maiores que a duração máxima.",
+    default_duration_limit: "A duração padrão não pode ser maior que a duração máxima.",
+    invalid_preset: "Não foi possível entender a opção de disponibilidade {0}.",
+    no_presets: "Informe opções de disponibilidade como 30m 1h 2h.",
+    too_many_presets: "Informe no máximo {0} opções de disponibilidade.",
+    invalid_colour: "Cores são códigos hexadecimais como #3BA55D.",
+    members: "**Membros**",
+    channels: "**Canais**",
+    join_rooms: "**Entre no esquadrão**",
+    ready_title: "**Seu esquadrão está pronto!**",
+    available_for: "{0} disponível por {1}",
+    late_title: "**Seu esquadrão já está jogando!**",
+    late_description: "Você entrou em um esquadrão que já está jogando.",
+    promoted_title: "**Você saiu da lista de espera!**",
+    promoted_description: "Abriu uma vaga e você entrou no esquadrão.",
+    cancelled_title: "**Esquadrão cancelado**",
+    cancelled_description: "O dono cancelou um esquadrão em que você tinha entrado.",
+    member_reminder_title: "**Ainda está disponível?**",
+    member_reminder: "Sua vaga em {0} acaba {1}.",
+    squad_reminder_title: "**O tempo do seu esquadrão está acabando**",
+    squad_reminder: "Seu esquadrão {0} não completou e expira {1}.",
+    kick_prompt: "Escolha um membro para expulsar do esquadrão.",
+    kick_placeholder: "Membro a expulsar",
+    nobody_to_kick: "Não há ninguém para expulsar.",
+    member_kicked: "Membro expulso do esquadrão.",
+    squad_set: "Seu esquadrão está pronto, os membros foram avisados.",
+    nobody_joined: "Ninguém entrou neste esquadrão ainda, cancele-o em vez disso.",
+    squad_extended: "Seu esquadrão fica aberto por mais uma hora.",
+    not_cancellable: "Este esquadrão não pode mais ser cancelado.",
+    announce: "{0} começou um esquadrão em {1}: {2}",
+    info_title: "Informações do esquadrão",
+    info_playing: "🟢 Jogando, aberto a quem chegar depois, fecha {0}",
+    info_forming: "🟡 Se formando, fecha {0}",
+    info_filled: "🟢 Completo",
+    info_cancelled: "⚫ Cancelado pelo dono",
+    info_expired: "🔴 Expirado",
+    choice_forming: "formando",
+    choice_filled: "completo",
+    choice_cancelled: "cancelado",
+    choice_expired: "expirado",
+    info_status: "**Status:** {0}",
+    info_owner: "**Dono:** {0}",
+    info_start: "**Começa:** {0}",
+    info_minimum: "**Mínimo:** {0}",
+    info_members: "**Membros** ({0}/{1})",
+    info_waiting: "**Lista de espera:** {0} aguardando",
+    list_line: "**{0}/{1}** entraram · restam {2}{3}\n[Ir para a publicação]({4})",
+    list_title: "Esquadrões se formando",
+    list_empty: "Nenhum esquadrão está se formando neste servidor agora.",
+    list_page: "Página {0} de {1}",
+    previous_button: "Anterior",
+    next_button: "Próxima",
+    link_policy_open: "Qualquer pessoa pode publicar este esquadrão em outros canais e servidores.",
+    link_policy_approval: "Os outros precisam da sua aprovação para publicar este esquadrão em outros canais e servidores.",
+    link_policy_disabled: "Só você pode publicar este esquadrão em outros canais e servidores.",
+    links_summary: "**Links:** {0}\n\n**Publicações**\n",
+    unlink_placeholder: "Publicação a remover",
+    posting_unlinked: "Publicação removida do esquadrão.",
+    link_request_title: "**Pedido de link**",
+    link_request: "{0} quer publicar seu esquadrão {1} em {2}.",
+    link_requested: "O dono deste esquadrão aprova onde ele é publicado. Foi pedido que ele aprove publicá-lo aqui.",
+    link_approved_title: "**Link aprovado**",
+    link_approved: "O dono do esquadrão {0} aprovou publicá-lo em {1}: {2}",
+    link_approved_reply: "Link aprovado, o esquadrão foi publicado.",
+    link_denied_title: "**Link recusado**",
+    link_denied: "O dono do esquadrão {0} recusou publicá-lo em {1}.",
+    link_denied_reply: "Link recusado.",
+    room_name: "Esquadrão {0}",
+    event_description: "Esquadrão ({0}/{1}): {2}\n\nEntre ou saia do esquadrão aqui: {3}",
+    unknown_member: "Membro desconhecido",
+    settings_title: "Configurações de esquadrões",
+    settings_updated: "Configurações de esquadrões atualizadas",
+    settings_reset: "Configurações de esquadrões redefinidas",
+    settings_description: "**Tamanho padrão:** {0}\n\
+        **Faixa de tamanho:** {1}\n\
+        **Duração padrão:** {2}\n\
+        **Duração máxima:** {3}\n\
+        **Canal de anúncios:** {4}\n\
+        **Cor:** {5}\n\
+        **Cargos que criam esquadrões:** {6}\n\
+        **Mensagens diretas:** {7}\n\
+        **Categoria de canais de voz:** {8}\n\
+        **Tópicos:** {9}\n\
+        **Canais de voz vazios mantidos por:** {10}\n\
+        **Eventos:** {11}\n\
+        **Lembretes:** {12}\n\
+        **Opções de disponibilidade:** {13}\n\
+        **Idioma:** {14}",
+    size_range_value: "{0} a {1}",
+    setting_default: " (padrão)",
+    setting_none: "Nenhum",
+    setting_everyone: "Todos",
+    setting_on: "Ativado",
+    setting_off: "Desativado",
+    setting_language_default: "Inglês, respostas no idioma de cada membro",
+};
+
+/// Fill in the placeholders of a message, {0} with the first value and so on.
+pub fn fill(message: &str, values: &[&dyn Display]) -> String {
+    let mut filled = message.to_string();
+    for (index, value) in values.iter().enumerate() {
+        filled = filled.replace(&format!("{{{}}}", index), &value.to_string());
+    }
+    filled
+}
+
+/// Picks a message out of the catalogue of a language, e.g. |text| text.missing_id.
+pub type Key = fn(&Text) -> &'static str;
+
+/// A message of the catalogue along with the values it is filled in with, kept until
+/// the language of whoever reads it is known.
+#[derive(Debug)]
+pub struct Message {
+    key: Key,
+    values: Vec<String>,
+}
+
+impl Message {
+    pub fn new(key: Key, values: &[&dyn Display]) -> Message {
+        Message {
+            key,
+            values: values.iter().map(|value| value.to_string()).collect(),
+        }
+    }
+
+    /// The message in the given language.
+    pub fn render(&self, locale: Locale) -> String {
+        let values: Vec<&dyn Display> = self.values.iter().map(|v| v as &dyn Display).collect();
+        fill((self.key)(locale.text()), &values)
+    }
+}
+
+/// Language to reply to an interaction in: the language of the server it came from,
+/// falling back to the language of the user.
+pub async fn interaction_locale(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    user_locale: &str,
+) -> Locale {
+    let settings = match (guild_id, store::get_store(ctx).await) {
+        (Some(guild_id), Ok(store)) => store.get_guild_settings(guild_id).await.ok(),
+        _ => None,
+    };
+    settings
+        .and_then(|settings| settings.locale)
+        .or_else(|| Locale::parse(user_locale))
+        .unwrap_or_default()
+}
+
+/// Language of direct messages to a member of a squad: the language of the server
+/// they joined through, or of the first server the squad was posted in.
+pub async fn member_locale(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+    user_id: UserId,
+) -> StoreResult<Locale> {
+    let channel_id = match store.get_origins(squad_id).await?.get(&user_id) {
+        Some(channel_id) => Some(*channel_id),
+        None => store.get_channels(squad_id).await?.into_iter().next(),
+    };
+    let settings = match channel_id {
+        Some(channel_id) => store::get_channel_settings(ctx, store, channel_id).await?,
+        None => GuildSettings::default(),
+    };
+    Ok(settings.locale())
+}
+
+/// Language of the server a channel belongs to.
+pub async fn channel_locale(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    channel_id: ChannelId,
+) -> StoreResult<Locale> {
+    Ok(store::get_channel_settings(ctx, store, channel_id)
+        .await?
+        .locale())
+}
+
+/// Name, if it is translated, and description of a command or option in a language.
+pub type Translation = (Option<&'static str>, &'static str);
+
+/// Translations of the /squad command, its subcommands and their options, by path
+/// ("" for the command itself, e.g. "create" or "create.size"), in Spanish then in
+/// Portuguese. Option names are left as they are, as they are given in the
+/// documentation.
+pub const SQUAD_COMMAND_TRANSLATIONS: &[(&str, [Translation; 2])] = &[
+    (
+        "",
+        [
+            (None, "Crea y explora publicaciones de escuadras"),
+            (None, "Crie e explore publicações de esquadrões"),
+        ],
+    ),
+    (
+        "create",
+        [
+            (Some("crear"), "Crea una nueva publicación de escuadra"),
+            (Some("criar"), "Crie uma nova publicação de esquadrão"),
+        ],
+    ),
+    (
+        "create.size",
+        [
+            (None, "Número del 1 al 10"),
+            (None, "Número de 1 a 10"),
+        ],
+    ),
+    (
+        "create.role",
+        [
+            (None, "Menciona un rol, p. ej. @gamers, @valorant, etc."),
+            (None, "Mencione um cargo, ex. @gamers, @valorant, etc."),
+        ],
+    ),
+    (
+        "create.id",
+        [
+            (None, "ID de otra publicación para escuadras entre servidores."),
+            (None, "ID de outra publicação para esquadrões entre servidores."),
+        ],
+    ),
+    (
+        "create.slots",
+        [
+            (None, "Roles en lugar de un tamaño, p. ej. tank:1 healer:1 dps:3"),
+            (None, "Funções em vez de um tamanho, ex. tank:1 healer:1 dps:3"),
+        ],
+    ),
+    (
+        "create.duration",
+        [
+            (None, "Cuánto tiempo sigue abierta (desde el inicio si está programada), p. ej. 30m o 2h"),
+            (None, "Por quanto tempo fica aberto (após o início se agendado), ex. 30m ou 2h"),
+        ],
+    ),
+    (
+        "create.start",
+        [
+            (None, "Programar para más tarde, p. ej. 21:00, 9pm, tomorrow 8pm, in 2h (UTC salvo +02:00)"),
+            (None, "Agendar para depois, ex. 21:00, 9pm, tomorrow 8pm, in 2h (UTC salvo -03:00)"),
+        ],
+    ),
+    (
+        "create.links",
+        [
+            (None, "Quién puede publicar esta escuadra en otros canales y servidores (cualquiera si no)"),
+            (None, "Quem pode publicar este esquadrão em outros canais e servidores (qualquer um se não)"),
+        ],
+    ),
+    (
+        "create.min",
+        [
+            (None, "Avisar a todos cuando se unan tantos, sigue abierta hasta el tamaño"),
+            (None, "Avisar todos quando esse número entrar, segue aberto até o tamanho"),
+        ],
+    ),
+    (
+        "list",
+        [
+            (Some("listar"), "Explora las escuadras que se forman en este servidor"),
+            (Some("listar"), "Veja os esquadrões se formando neste servidor"),
+        ],
+    ),
+    (
+        "join",
+        [
+            (Some("unirse"), "Únete a una escuadra por su ID"),
+            (Some("entrar"), "Entre em um esquadrão pelo ID"),
+        ],
+    ),
+    (
+        "join.id",
+        [
+            (None, "ID de la escuadra, p. ej. squad:123456789"),
+            (None, "ID do esquadrão, ex. squad:123456789"),
+        ],
+    ),
+    (
+        "join.hours",
+        [
+            (None, "Horas que estás disponible; si se omite, mientras la escuadra esté abierta"),
+            (None, "Horas em que você está disponível; se omitido, enquanto o esquadrão estiver aberto"),
+        ],
+    ),
+    (
+        "join.slot",
+        [
+            (None, "Rol a ocupar en escuadras con roles, p. ej. tank"),
+            (None, "Função a ocupar em esquadrões com funções, ex. tank"),
+        ],
+    ),
+    (
+        "leave",
+        [
+            (Some("salir"), "Sal de una escuadra o de su lista de espera por su ID"),
+            (Some("sair"), "Saia de um esquadrão ou da lista de espera pelo ID"),
+        ],
+    ),
+    (
+        "leave.id",
+        [
+            (None, "ID de la escuadra, p. ej. squad:123456789"),
+            (None, "ID do esquadrão, ex. squad:123456789"),
+        ],
+    ),
+    (
+        "info",
+        [
+            (None, "Muestra el estado y los miembros de una escuadra por su ID"),
+            (None, "Mostra o status e os membros de um esquadrão pelo ID"),
+        ],
+    ),
+    (
+        "info.id",
+        [
+            (None, "ID de la escuadra, p. ej. squad:123456789"),
+            (None, "ID do esquadrão, ex. squad:123456789"),
+        ],
+    ),
+    (
+        "extend",
+        [
+            (Some("extender"), "Sigue disponible en una escuadra por más horas"),
+            (Some("estender"), "Fique disponível em um esquadrão por mais horas"),
+        ],
+    ),
+    (
+        "extend.id",
+        [
+            (None, "ID de la escuadra, p. ej. squad:123456789"),
+            (None, "ID do esquadrão, ex. squad:123456789"),
+        ],
+    ),
+    (
+        "extend.hours",
+        [
+            (None, "Horas a sumar a tu disponibilidad"),
+            (None, "Horas a adicionar à sua disponibilidade"),
+        ],
+    ),
+    (
+        "links",
+        [
+            (Some("enlaces"), "Lista y quita las publicaciones de una escuadra tuya"),
+            (None, "Liste e remova as publicações de um esquadrão seu"),
+        ],
+    ),
+    (
+        "links.id",
+        [
+            (None, "ID de la escuadra, p. ej. squad:123456789"),
+            (None, "ID do esquadrão, ex. squad:123456789"),
+        ],
+    ),
+    (
+        "links.links",
+        [
+            (None, "Cambia quién puede publicar la escuadra en otros canales y servidores"),
+            (None, "Altere quem pode publicar o esquadrão em outros canais e servidores"),
+        ],
+    ),
+];
+
+/// Translations of the /squadconfig command, its subcommands and their options, by
+/// path as for SQUAD_COMMAND_TRANSLATIONS.
+pub const CONFIG_COMMAND_TRANSLATIONS: &[(&str, [Translation; 2])] = &[
+    (
+        "",
+        [
+            (None, "Configura las escuadras de este servidor"),
+            (None, "Configure os esquadrões deste servidor"),
+        ],
+    ),
+    (
+        "view",
+        [
+            (
+                Some("ver"),
+                "Muestra los ajustes de escuadras de este servidor",
+            ),
+            (
+                Some("ver"),
+                "Mostre as configurações de esquadrões deste servidor",
+            ),
+        ],
+    ),
+    (
+        "set",
+        [
+            (
+                Some("cambiar"),
+                "Cambia los ajustes de escuadras de este servidor",
+            ),
+            (
+                Some("alterar"),
+                "Altere as configurações de esquadrões deste servidor",
+            ),
+        ],
+    ),
+    (
+        "set.default_size",
+        [
+            (None, "Tamaño de las escuadras creadas sin tamaño"),
+            (None, "Tamanho dos esquadrões criados sem tamanho"),
+        ],
+    ),
+    (
+        "set.min_size",
+        [
+            (None, "Tamaño mínimo que puede tener una escuadra"),
+            (None, "Menor tamanho que um esquadrão pode ter"),
+        ],
+    ),
+    (
+        "set.max_size",
+        [
+            (None, "Tamaño máximo que puede tener una escuadra"),
+            (None, "Maior tamanho que um esquadrão pode ter"),
+        ],
+    ),
+    (
+        "set.default_duration",
+        [
+            (
+                None,
+                "Cuánto tiempo siguen abiertas las escuadras por defecto, p. ej. 10h",
+            ),
+            (
+                None,
+                "Por quanto tempo os esquadrões ficam abertos por padrão, ex. 10h",
+            ),
+        ],
+    ),
+    (
+        "set.max_duration",
+        [
+            (
+                None,
+                "Tiempo máximo que una escuadra puede seguir abierta, p. ej. 24h",
+            ),
+            (
+                None,
+                "Tempo máximo que um esquadrão pode ficar aberto, ex. 24h",
+            ),
+        ],
+    ),
+    (
+        "set.announce_channel",
+        [
+            (None, "Canal en el que se anuncian las nuevas escuadras"),
+            (None, "Canal em que os novos esquadrões são anunciados"),
+        ],
+    ),
+    (
+        "set.colour",
+        [
+            (
+                None,
+                "Color de las publicaciones como código hexadecimal, p. ej. #3BA55D",
+            ),
+            (
+                None,
+                "Cor das publicações como código hexadecimal, ex. #3BA55D",
+            ),
+        ],
+    ),
+    (
+        "set.add_creator_role",
+        [
+            (None, "Permite que un rol cree escuadras"),
+            (None, "Permita que um cargo crie esquadrões"),
+        ],
+    ),
+    (
+        "set.remove_creator_role",
+        [
+            (None, "Deja de permitir que un rol cree escuadras"),
+            (None, "Deixe de permitir que um cargo crie esquadrões"),
+        ],
+    ),
+    (
+        "set.dms",
+        [
+            (
+                None,
+                "Si se envían mensajes directos a los miembros de las escuadras",
+            ),
+            (
+                None,
+                "Se os membros dos esquadrões recebem mensagens diretas",
+            ),
+        ],
+    ),
+    (
+        "set.voice_category",
+        [
+            (
+                None,
+                "Categoría en la que las escuadras completas reciben un canal de voz",
+            ),
+            (
+                None,
+                "Categoria em que os esquadrões completos ganham um canal de voz",
+            ),
+        ],
+    ),
+    (
+        "set.threads",
+        [
+            (
+                None,
+                "Si las escuadras completas reciben un hilo en su publicación",
+            ),
+            (
+                None,
+                "Se os esquadrões completos ganham um tópico na publicação",
+            ),
+        ],
+    ),
+    (
+        "set.voice_cleanup",
+        [
+            (
+                None,
+                "Cuánto se conserva un canal de voz vacío de una escuadra, p. ej. 15m",
+            ),
+            (
+                None,
+                "Por quanto tempo um canal de voz vazio de um esquadrão é mantido, ex. 15m",
+            ),
+        ],
+    ),
+    (
+        "set.events",
+        [
+            (
+                None,
+                "Si las escuadras reciben un evento de Discord al programarse o completarse",
+            ),
+            (
+                None,
+                "Se os esquadrões ganham um evento do Discord ao serem agendados ou completados",
+            ),
+        ],
+    ),
+    (
+        "set.reminders",
+        [
+            (
+                None,
+                "Si miembros y dueños reciben un MD antes de que se acabe su tiempo",
+            ),
+            (
+                None,
+                "Se membros e donos recebem uma DM antes do tempo acabar",
+            ),
+        ],
+    ),
+    (
+        "set.availability",
+        [
+            (
+                None,
+                "Tiempos entre los que los miembros eligen su disponibilidad, p. ej. 30m 1h 2h",
+            ),
+            (
+                None,
+                "Tempos entre os quais os membros escolhem a disponibilidade, ex. 30m 1h 2h",
+            ),
+        ],
+    ),
+    (
+        "set.locale",
+        [
+            (
+                None,
+                "Idioma de las publicaciones y de los mensajes a los miembros",
+            ),
+            (None, "Idioma das publicações e das mensagens aos membros"),
+        ],
+    ),
+    (
+        "reset",
+        [
+            (
+                Some("restablecer"),
+                "Restablece los ajustes de escuadras de este servidor",
+            ),
+            (
+                Some("redefinir"),
+                "Redefina as configurações de esquadrões deste servidor",
+            ),
+        ],
+    ),
+    (
+        "reset.setting",
+        [
+            (None, "Ajuste a restablecer, todos si se omite"),
+            (None, "Configuração a redefinir, todas se omitida"),
+        ],
+    ),
+];
+
+/// Discord locales that each translation of a command is sent for.
+const COMMAND_LOCALES: [&[&str]; 2] = [&["es-ES", "es-419"], &["pt-BR"]];
+
+/// Add the given Spanish and Portuguese names and descriptions of a command, its
+/// subcommands and their options to its registration.
+pub fn localize_command<'a>(
+    command: &'a mut CreateApplicationCommand,
+    translations: &[(&str, [Translation; 2])],
+) -> &'a mut CreateApplicationCommand {
+    let mut root = serenity::json::json!({ "options": command.0.remove("options") });
+    localize_options(&mut root, "", translations);
+    let fields = root.as_object_mut().into_iter().flatten();
+    for (key, value) in fields.filter(|(_, value)| !value.is_null()) {
+        match key.as_str() {
+            "options" => command.0.insert("options", value.take()),
+            "name_localizations" => command.0.insert("name_localizations", value.take()),
+            "description_localizations" => {
+                command.0.insert("description_localizations", value.take())
+            }
+            _ => None,
+        };
+    }
+    command
+}
+
+/// Add the translations of an option found at the given path, then of the options
+/// nested in it.
+fn localize_options(option: &mut Value, path: &str, translations: &[(&str, [Translation; 2])]) {
+    let object = match option.as_object_mut() {
+        Some(object) => object,
+        None => return,
+    };
+    if let Some((_, translation)) = translations.iter().find(|(key, _)| *key == path) {
+        let mut names = serenity::json::json!({});
+        let mut descriptions = serenity::json::json!({});
+        for ((name, description), locales) in translation.iter().zip(COMMAND_LOCALES) {
+            for locale in locales {
+                if let Some(name) = name {
+                    names[*locale] = Value::from(*name);
+                }
+                descriptions[*locale] = Value::from(*description);
+            }
+        }
+        if names.as_object().is_some_and(|names| !names.is_empty()) {
+            object.insert(String::from("name_localizations"), names);
+        }
+        object.insert(String::from("description_localizations"), descriptions);
+    }
+    let options = object
+        .get_mut("options")
+        .and_then(|options| options.as_array_mut());
+    for option in options.into_iter().flatten() {
+        let name = option["name"].as_str().unwrap_or_default().to_string();
+        let path = match path.is_empty() {
+            true => name,
+            false => format!("{}.{}", path, name),
+        };
+        localize_options(option, &path, translations);
+    }
+}
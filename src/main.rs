@@ -1,4 +1,5 @@
 use dotenv::dotenv;
+use error::SquadError;
 use serenity::async_trait;
 use serenity::framework::standard::macros::group;
 use serenity::framework::standard::StandardFramework;
@@ -7,15 +8,24 @@ use serenity::model::id::GuildId;
 use serenity::model::prelude::Interaction;
 use serenity::prelude::{Context, EventHandler, GatewayIntents};
 use serenity::Client;
+use std::collections::HashSet;
 use std::env;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::RwLock;
+mod config;
 mod embed;
+mod error;
+mod events;
+mod links;
+mod locale;
+mod memory;
 mod notify;
 mod redis_io;
+mod rooms;
+mod sqlite;
 mod squad;
+mod store;
 
 #[group]
 struct General;
@@ -25,52 +35,211 @@ struct Handler {
 }
 
 const UPDATE_POLL_SECONDS: u64 = 30;
+const RESYNC_POLL_SECONDS: u64 = 5 * 60;
+const CHANGE_DEBOUNCE_MILLIS: u64 = 500;
 
 #[async_trait]
 impl EventHandler for Handler {
-    /// Globally registers /squad command when application is launched.
+    /// Globally registers /squad and /squadconfig commands when application is launched.
     async fn ready(&self, ctx: Context, ready: Ready) {
         println!("{} is connected!", ready.user.name);
-        match squad::register_squad_command(ctx).await {
+        let registered = match squad::register_squad_command(ctx.clone()).await {
+            Ok(_) => config::register_config_command(ctx).await,
+            Err(error) => Err(error),
+        };
+        match registered {
             Ok(_) => println!("Registered global commands."),
             Err(error) => println!("Error registering global commands: {:?}", error),
         }
     }
 
-    /// SquadBot reacts to three interactions:
-    /// 1) A /squad command is given, indicating the creation of a new squad posting.
-    /// 2) A user clicks a numbered button, adding them to the squad.
+    /// SquadBot reacts to these interactions:
+    /// 1) A /squad create command is given, indicating the creation of a new squad posting,
+    ///    or a /squad list, join, leave, info, extend or links command is given to browse
+    ///    squads or manage them by ID.
+    /// 2) A user picks how long they are available from the menu of a posting, or
+    ///    gives an exact end time in the form opened from its custom option, adding
+    ///    them to the squad.
     /// 3) A user clicks on the "Leave Squad" button.
+    /// 4) A user clicks a button of a reminder DM to extend their availability, or to
+    ///    extend or close the squad they own.
+    /// 5) An owner approves or denies a request to post their squad elsewhere, or picks
+    ///    a posting of their squad to remove.
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
         match interaction {
-            Interaction::ApplicationCommand(command) => match command.data.name.as_str() {
-                "squad" => {
-                    if let Err(why) = squad::handle_squad_command(&ctx, &command).await {
-                        eprintln!("Error handling squad command: {}", why);
+            Interaction::ApplicationCommand(command) => {
+                let result = match command.data.name.as_str() {
+                    "squad" => squad::handle_squad_command(&ctx, &command).await,
+                    "squadconfig" => config::handle_config_command(&ctx, &command).await,
+                    _ => Err(SquadError::UnknownCommand),
+                };
+                if let Err(why) = result {
+                    if why.is_internal() {
+                        eprintln!("Error handling {} command: {}", command.data.name, why);
+                    }
+                    let locale =
+                        locale::interaction_locale(&ctx, command.guild_id, &command.locale).await;
+                    let reply = why.reply(locale);
+                    // Commands that posted before failing have used up their response
+                    if squad::respond_command(&ctx, &command, &reply)
+                        .await
+                        .is_err()
+                    {
+                        let followup = command
+                            .create_followup_message(&ctx.http, |m| {
+                                m.content(&reply).ephemeral(true)
+                            })
+                            .await;
+                        if let Err(why) = followup {
+                            eprintln!("{}", why);
+                        }
                     }
                 }
-                _ => {
-                    println!("Not implemented.");
+            }
+            Interaction::Autocomplete(autocomplete) if autocomplete.data.name == "squad" => {
+                if let Err(why) = squad::handle_autocomplete(&ctx, &autocomplete).await {
+                    eprintln!("Error handling autocomplete: {}", why);
                 }
-            },
+            }
             Interaction::MessageComponent(component_interaction) => {
-                match squad::parse_component_id(&component_interaction) {
-                    embed::ButtonChoice::Hours(expires) => {
-                        if let Err(why) =
-                            squad::handle_add_member(&ctx, &component_interaction, expires).await
+                let locale = locale::interaction_locale(
+                    &ctx,
+                    component_interaction.guild_id,
+                    &component_interaction.locale,
+                )
+                .await;
+                let text = locale.text();
+                let result = match squad::parse_component_id(&component_interaction) {
+                    Ok(embed::ButtonChoice::Availability(expires)) => {
+                        squad::handle_add_member(&ctx, &component_interaction, Some(expires))
+                            .await
+                            .map(|result| squad::join_result_message(&result, text))
+                    }
+                    Ok(embed::ButtonChoice::CustomAvailability) => {
+                        // Responds with the availability form itself
+                        match squad::handle_custom_availability(&ctx, &component_interaction, text)
+                            .await
                         {
-                            eprintln!("Error handling add member: {}", why);
-                        };
+                            Ok(()) => return,
+                            Err(why) => Err(why),
+                        }
+                    }
+                    Ok(embed::ButtonChoice::Commit) => {
+                        squad::handle_add_member(&ctx, &component_interaction, None)
+                            .await
+                            .map(|result| squad::join_result_message(&result, text))
                     }
-                    embed::ButtonChoice::Leave(_) => {
-                        if let Err(why) =
-                            squad::handle_delete_member(&ctx, &component_interaction).await
+                    Ok(embed::ButtonChoice::Slot(slot)) => {
+                        squad::handle_choose_slot(&ctx, &component_interaction, &slot, text)
+                            .await
+                            .map(Some)
+                    }
+                    Ok(embed::ButtonChoice::Close) => {
+                        squad::handle_close_squad(&ctx, &component_interaction, text).await
+                    }
+                    Ok(embed::ButtonChoice::Cancel) => {
+                        squad::handle_cancel_squad(&ctx, &component_interaction, text).await
+                    }
+                    Ok(embed::ButtonChoice::Kick) => {
+                        // Responds with the kick menu itself
+                        match squad::handle_kick_button(&ctx, &component_interaction, text).await {
+                            Ok(()) => return,
+                            Err(why) => Err(why),
+                        }
+                    }
+                    Ok(embed::ButtonChoice::Page(page)) => {
+                        // Responds with the new page of the list itself
+                        match squad::handle_list_page(&ctx, &component_interaction, page, text)
+                            .await
                         {
-                            eprintln!("Error handling delete member: {}", why);
-                        };
+                            Ok(()) => return,
+                            Err(why) => Err(why),
+                        }
                     }
+                    Ok(embed::ButtonChoice::Extend(squad_id)) => {
+                        squad::handle_extend_reminder(&ctx, &component_interaction, &squad_id, text)
+                            .await
+                    }
+                    Ok(embed::ButtonChoice::ExtendSquad(squad_id)) => {
+                        squad::handle_extend_squad_reminder(
+                            &ctx,
+                            &component_interaction,
+                            &squad_id,
+                            text,
+                        )
+                        .await
+                    }
+                    Ok(embed::ButtonChoice::CloseSquad(squad_id)) => {
+                        squad::handle_close_reminder(&ctx, &component_interaction, &squad_id, text)
+                            .await
+                    }
+                    Ok(embed::ButtonChoice::ApproveLink(request)) => {
+                        links::handle_approve_link(&ctx, &component_interaction, request, text)
+                            .await
+                    }
+                    Ok(embed::ButtonChoice::DenyLink(request)) => {
+                        links::handle_deny_link(&ctx, &component_interaction, request, text).await
+                    }
+                    Ok(embed::ButtonChoice::Unlink {
+                        squad_id,
+                        message_id,
+                    }) => links::handle_unlink(
+                        &ctx,
+                        &component_interaction,
+                        &squad_id,
+                        &message_id,
+                        text,
+                    )
+                    .await
+                    .map(Some),
+                    Ok(embed::ButtonChoice::KickMember {
+                        message_id,
+                        user_id,
+                    }) => squad::handle_kick_member(
+                        &ctx,
+                        &component_interaction,
+                        &message_id,
+                        &user_id,
+                        text,
+                    )
+                    .await
+                    .map(Some),
+                    Ok(embed::ButtonChoice::Leave) => {
+                        squad::handle_delete_member(&ctx, &component_interaction)
+                            .await
+                            .map(|_| None)
+                    }
+                    Err(why) => Err(why),
+                };
+                // Failed clicks are explained to the user, failures of SquadBot logged
+                let message = match result {
+                    Ok(message) => message.map(String::from),
+                    Err(why) => {
+                        if why.is_internal() {
+                            eprintln!("Error handling component: {}", why);
+                        }
+                        Some(why.reply(locale))
+                    }
+                };
+                if let Err(why) =
+                    squad::respond_component(&ctx, &component_interaction, message.as_deref()).await
+                {
+                    eprintln!("{}", why);
                 }
-                if let Err(why) = component_interaction.defer(&ctx.http).await {
+            }
+            Interaction::ModalSubmit(modal) => {
+                let locale = locale::interaction_locale(&ctx, modal.guild_id, &modal.locale).await;
+                let message =
+                    match squad::handle_availability_modal(&ctx, &modal, locale.text()).await {
+                        Ok(message) => message,
+                        Err(why) => {
+                            if why.is_internal() {
+                                eprintln!("Error handling form: {}", why);
+                            }
+                            why.reply(locale)
+                        }
+                    };
+                if let Err(why) = squad::respond_modal(&ctx, &modal, &message).await {
                     eprintln!("{}", why);
                 }
             }
@@ -78,40 +247,55 @@ impl EventHandler for Handler {
         }
     }
 
-    /// SquadBot runs the inner loop here every UPDATE_POLL_SECONDS.
-    /// This code updates existing postings with new status measurements such as how
-    /// long a squad member is available for or how long the squad posting will last.
-    /// Additionally, filled postings will result in direct messages being sent to
-    /// squad members.
+    /// SquadBot keeps postings up to date here. When the store reports changes as
+    /// they happen, the postings of each changed squad are refreshed right away and
+    /// every posting is only re-checked every RESYNC_POLL_SECONDS as a safety net.
+    /// Otherwise every posting is re-checked every UPDATE_POLL_SECONDS. Postings are
+    /// only edited when their content changed, and filled squads result in direct
+    /// messages being sent to squad members. Reminders that are due are sent along
+    /// with every re-check.
     async fn cache_ready(&self, ctx: Context, _guilds: Vec<GuildId>) {
         println!("Cache ready.");
         let ctx = Arc::new(ctx);
         if !self.is_loop_running.load(Ordering::Relaxed) {
+            let changes = match store::get_store(&ctx).await {
+                Ok(store) => store.subscribe().await.unwrap_or_else(|why| {
+                    eprintln!("Error subscribing to squad changes: {}", why);
+                    None
+                }),
+                Err(why) => {
+                    eprintln!("Error getting squad store: {}", why);
+                    None
+                }
+            };
+            let poll_seconds = match changes {
+                Some(_) => RESYNC_POLL_SECONDS,
+                None => UPDATE_POLL_SECONDS,
+            };
+            if let Some(mut changes) = changes {
+                let ctx1 = Arc::clone(&ctx);
+                tokio::spawn(async move {
+                    while let Some(squad_id) = changes.recv().await {
+                        // Let bursts of changes to the same squad settle
+                        tokio::time::sleep(Duration::from_millis(CHANGE_DEBOUNCE_MILLIS)).await;
+                        let mut squads = HashSet::from([squad_id]);
+                        while let Ok(squad_id) = changes.try_recv() {
+                            squads.insert(squad_id);
+                        }
+                        for squad_id in squads {
+                            if let Err(why) = refresh_squad(&ctx1, squad_id).await {
+                                eprintln!("Error refreshing squad: {}", why);
+                            }
+                        }
+                    }
+                });
+            }
             let ctx1 = Arc::clone(&ctx);
             tokio::spawn(async move {
                 loop {
-                    tokio::time::sleep(Duration::from_secs(UPDATE_POLL_SECONDS)).await;
-                    let ctx2 = Arc::clone(&ctx1);
-                    let mut con = match redis_io::get_redis_connection(&ctx2).await {
-                        Ok(c) => c,
-                        Err(why) => {
-                            eprintln!("Error getting Redis connection: {}", why);
-                            continue;
-                        }
-                    };
-                    let postings = redis_io::get_postings(&mut con).unwrap();
-                    for (key, value) in &postings {
-                        let result =
-                            embed::build_message(&ctx2, &value, &mut con, &key.to_string()).await;
-                        if let Err(why) = result {
-                            eprintln!("Error building message: {}", why);
-                            continue;
-                        };
-                    }
-                    let full_squads = redis_io::get_full_squads(&mut con).unwrap();
-                    if let Err(why) = notify::notify_squads(&ctx2, &mut con, full_squads).await {
-                        eprintln!("Error notifying squads: {}", why);
-                        continue;
+                    tokio::time::sleep(Duration::from_secs(poll_seconds)).await;
+                    if let Err(why) = refresh_all(&ctx1).await {
+                        eprintln!("Error refreshing postings: {}", why);
                     }
                 }
             });
@@ -120,6 +304,66 @@ impl EventHandler for Handler {
     }
 }
 
+/// Promote waitlisted users into free spots of a squad and notify its members if it
+/// just reached its quorum or filled, then update its postings.
+async fn refresh_squad(ctx: &Context, squad_id: String) -> Result<(), SquadError> {
+    let store = store::get_store(ctx).await?;
+    notify::promote_waitlist(ctx, store.as_ref(), &squad_id).await?;
+    let squads = HashSet::from([squad_id.clone()]);
+    notify::notify_quorum(ctx, store.as_ref(), &squads).await?;
+    notify::notify_squads(ctx, store.as_ref(), vec![squad_id.clone()]).await?;
+    embed::build_messages(ctx, store.as_ref(), &squad_id).await
+}
+
+/// Promote waitlisted users into spots freed by expired members, send the reminders
+/// that are due, notify the members of every squad that reached its quorum or filled,
+/// update every posting, then delete voice channels of filled squads that have sat
+/// empty for too long.
+async fn refresh_all(ctx: &Context) -> Result<(), SquadError> {
+    let store = store::get_store(ctx).await?;
+    let postings = store.get_postings().await?;
+    let mut squads = HashSet::new();
+    for message_id in postings.keys() {
+        if let Ok(squad_id) = store.get_squad_id(&message_id.to_string()).await {
+            squads.insert(squad_id);
+        }
+    }
+    // A squad that fails to refresh is logged and skipped, so that it doesn't hold
+    // back every other squad and posting
+    for squad_id in &squads {
+        if let Err(why) = notify::promote_waitlist(ctx, store.as_ref(), squad_id).await {
+            eprintln!("Error promoting waitlist of {}: {}", squad_id, why);
+        }
+    }
+    if let Err(why) = notify::send_reminders(ctx, store.as_ref(), &squads).await {
+        eprintln!("Error sending reminders: {}", why);
+    }
+    if let Err(why) = notify::notify_quorum(ctx, store.as_ref(), &squads).await {
+        eprintln!("Error notifying squads that reached their minimum: {}", why);
+    }
+    match store.get_full_squads().await {
+        Ok(full_squads) => {
+            if let Err(why) = notify::notify_squads(ctx, store.as_ref(), full_squads).await {
+                eprintln!("Error notifying filled squads: {}", why);
+            }
+        }
+        Err(why) => eprintln!("Error getting filled squads: {}", why),
+    }
+    for (key, value) in &postings {
+        let result = embed::build_message(ctx, value, store.as_ref(), &key.to_string()).await;
+        if let Err(why) = result {
+            eprintln!("Error building message: {}", why);
+        };
+    }
+    // Forget postings that have expired
+    let rendered = embed::get_rendered_postings(ctx).await;
+    rendered
+        .lock()
+        .await
+        .retain(|message_id, _| postings.contains_key(message_id));
+    rooms::clean_up_rooms(ctx, store.as_ref()).await
+}
+
 #[tokio::main]
 async fn main() {
     // Build framework
@@ -136,6 +380,7 @@ async fn main() {
         | GatewayIntents::DIRECT_MESSAGES
         | GatewayIntents::GUILDS
         | GatewayIntents::GUILD_MESSAGE_REACTIONS
+        | GatewayIntents::GUILD_VOICE_STATES
         | GatewayIntents::MESSAGE_CONTENT;
 
     // Build client
@@ -147,12 +392,26 @@ async fn main() {
         .await
         .expect("Client creation failed.");
 
-    // Add Redis connection
+    // Add squad store, Redis unless STORE=memory or STORE=sqlite is given
     {
-        let redis_url = env::var("REDIS_URL").expect("token");
+        let store: Arc<dyn store::SquadStore> = match env::var("STORE").as_deref() {
+            Ok("redis") | Err(env::VarError::NotPresent) => {
+                let redis_url =
+                    env::var("REDIS_URL").expect("REDIS_URL is required when STORE is redis");
+                let redis = redis_io::Redis::open(&redis_url).await;
+                Arc::new(redis.expect("Unable to connect to the Redis server"))
+            }
+            Ok("memory") => Arc::new(memory::Memory::default()),
+            Ok("sqlite") => {
+                let path = env::var("SQLITE_PATH").unwrap_or_else(|_| "squadbot.db".into());
+                let sqlite = sqlite::Sqlite::open(&path);
+                Arc::new(sqlite.expect("Unable to open the SQLite database"))
+            }
+            Ok(other) => panic!("Unknown STORE {:?}, use redis, sqlite or memory", other),
+            Err(why) => panic!("Unable to read STORE: {}", why),
+        };
         let mut data = client.data.write().await;
-        let redis = redis::Client::open(redis_url).unwrap();
-        data.insert::<redis_io::Redis>(Arc::new(RwLock::new(redis)));
+        data.insert::<store::Store>(store);
     }
 
     // Start
@@ -0,0 +1,794 @@
+use crate::error::SquadError;
+use crate::store::{
+    Composition, GuildSettings, JoinResult, LinkPolicy, Rooms, SquadStatus, SquadStore, StoreResult,
+};
+use chrono::Utc;
+use serenity::async_trait;
+use serenity::model::id::{GuildId, ScheduledEventId, UserId};
+use serenity::model::prelude::{ChannelId, MessageId, RoleId};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Squad store kept entirely in process memory. Mirrors the expiry semantics of the
+/// Redis keys so the bot can run without a Redis server. Data is lost on restart.
+#[derive(Default)]
+pub struct Memory {
+    state: Mutex<State>,
+}
+
+/// A stored value along with the instant at which it expires.
+struct Expiring<T> {
+    value: T,
+    expires: Instant,
+}
+
+impl<T> Expiring<T> {
+    fn new(value: T, ttl: u64) -> Expiring<T> {
+        Expiring {
+            value,
+            expires: Instant::now() + Duration::from_secs(ttl),
+        }
+    }
+
+    fn is_live(&self, now: Instant) -> bool {
+        self.expires > now
+    }
+
+    fn ttl(&self) -> u64 {
+        self.expires
+            .saturating_duration_since(Instant::now())
+            .as_secs()
+    }
+}
+
+struct Squad {
+    owner_id: UserId,
+    capacity: u8,
+    filled: bool,
+    cancelled: bool,
+    start: Option<i64>,
+    composition: Composition,
+    quorum: Option<u8>,
+    /// Whether the squad reached its quorum and its members were notified
+    ready: bool,
+    link_policy: LinkPolicy,
+}
+
+impl Squad {
+    /// Whether the squad is unscheduled or its start time has passed.
+    fn has_started(&self) -> bool {
+        self.start
+            .is_none_or(|start| start <= Utc::now().timestamp())
+    }
+}
+
+struct Posting {
+    squad_id: String,
+    channel_id: ChannelId,
+    guild_id: Option<GuildId>,
+    role_id: Option<RoleId>,
+    rooms: Rooms,
+    event_id: Option<ScheduledEventId>,
+}
+
+/// Equivalent of the squad:*, members:*, member:*, waitlist:*, slots:*, origins:*, posting:*,
+/// channels:*, guild:* and voice_channels keys.
+#[derive(Default)]
+struct State {
+    squads: HashMap<String, Expiring<Squad>>,
+    member_sets: HashMap<String, Expiring<HashSet<UserId>>>,
+    members: HashMap<(String, UserId), Expiring<()>>,
+    /// Waiting users in order along with the seconds they asked to be available for
+    waitlists: HashMap<String, VecDeque<(UserId, u32)>>,
+    /// Slot picked by each user of squads with role slots
+    slots: HashMap<(String, UserId), String>,
+    /// Channel of the posting through which each user joined a squad
+    origins: HashMap<(String, UserId), ChannelId>,
+    /// Settings of each guild, which never expire
+    guilds: HashMap<GuildId, GuildSettings>,
+    postings: HashMap<MessageId, Expiring<Posting>>,
+    channels: HashMap<String, Expiring<HashSet<ChannelId>>>,
+    /// Voice channels opened for filled squads, which never expire
+    voice_channels: HashSet<ChannelId>,
+}
+
+impl State {
+    /// Drop every entry that has expired, including expired members of each squad.
+    fn purge(&mut self) {
+        let now = Instant::now();
+        self.squads.retain(|_, squad| squad.is_live(now));
+        self.member_sets.retain(|_, set| set.is_live(now));
+        self.members.retain(|_, member| member.is_live(now));
+        self.postings.retain(|_, posting| posting.is_live(now));
+        self.channels.retain(|_, channels| channels.is_live(now));
+        let squads = &self.squads;
+        self.waitlists
+            .retain(|squad_id, _| squads.contains_key(squad_id));
+        self.slots
+            .retain(|(squad_id, _), _| squads.contains_key(squad_id));
+        self.origins
+            .retain(|(squad_id, _), _| squads.contains_key(squad_id));
+        let members = &self.members;
+        for (squad_id, set) in self.member_sets.iter_mut() {
+            set.value
+                .retain(|user_id| members.contains_key(&(squad_id.clone(), *user_id)));
+        }
+    }
+
+    /// Whether the given squad is forming, has started if scheduled, and has as many
+    /// members as its capacity.
+    fn is_full(&self, squad_id: &str) -> bool {
+        match self.squads.get(squad_id) {
+            Some(squad) => {
+                !squad.value.filled
+                    && !squad.value.cancelled
+                    && squad.value.has_started()
+                    && self.member_count(squad_id) >= usize::from(squad.value.capacity)
+            }
+            None => false,
+        }
+    }
+
+    fn squad(&self, squad_id: &str) -> StoreResult<&Expiring<Squad>> {
+        self.squads.get(squad_id).ok_or(SquadError::UnknownSquad)
+    }
+
+    fn squad_mut(&mut self, squad_id: &str) -> StoreResult<&mut Expiring<Squad>> {
+        self.squads
+            .get_mut(squad_id)
+            .ok_or(SquadError::UnknownSquad)
+    }
+
+    fn squad_status(&self, squad_id: &str) -> SquadStatus {
+        match self.squads.get(squad_id) {
+            None => SquadStatus::Expired,
+            Some(squad) if squad.value.cancelled => SquadStatus::Cancelled,
+            Some(squad) if squad.value.filled => SquadStatus::Filled,
+            Some(_) => SquadStatus::Forming,
+        }
+    }
+
+    fn member_count(&self, squad_id: &str) -> usize {
+        self.member_sets
+            .get(squad_id)
+            .map_or(0, |set| set.value.len())
+    }
+
+    fn slot(&self, squad_id: &str, user_id: UserId) -> Option<&str> {
+        self.slots
+            .get(&(squad_id.to_string(), user_id))
+            .map(String::as_str)
+    }
+
+    /// Number of members of a squad who picked the given slot.
+    fn slot_count(&self, squad_id: &str, slot: &str) -> usize {
+        self.member_sets.get(squad_id).map_or(0, |set| {
+            set.value
+                .iter()
+                .filter(|user_id| self.slot(squad_id, **user_id) == Some(slot))
+                .count()
+        })
+    }
+
+    /// Whether a squad has a free seat for a user who picked the given slot. Squads
+    /// without role slots only need a free seat overall.
+    fn has_room(&self, squad_id: &str, slot: Option<&str>) -> bool {
+        let squad = match self.squads.get(squad_id) {
+            Some(squad) => &squad.value,
+            None => return false,
+        };
+        if self.member_count(squad_id) >= usize::from(squad.capacity) {
+            return false;
+        }
+        squad.composition.is_empty() || slot.is_some_and(|slot| self.has_seat(squad_id, slot))
+    }
+
+    /// Whether the given slot of a squad has a free seat.
+    fn has_seat(&self, squad_id: &str, slot: &str) -> bool {
+        let seats = self.squads.get(squad_id).map_or(0, |squad| {
+            squad
+                .value
+                .composition
+                .iter()
+                .find(|(name, _)| name == slot)
+                .map_or(0, |(_, seats)| usize::from(*seats))
+        });
+        self.slot_count(squad_id, slot) < seats
+    }
+
+    fn is_member(&self, squad_id: &str, user_id: UserId) -> bool {
+        self.member_sets
+            .get(squad_id)
+            .is_some_and(|set| set.value.contains(&user_id))
+    }
+}
+
+impl Memory {
+    /// Lock the store state, dropping any expired entries first.
+    fn state(&self) -> StoreResult<MutexGuard<'_, State>> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| SquadError::store("Squad store lock poisoned."))?;
+        state.purge();
+        Ok(state)
+    }
+}
+
+/// Parse a Discord snowflake stored as a string.
+fn parse_id(id: &str) -> StoreResult<u64> {
+    Ok(id.parse::<u64>()?)
+}
+
+#[async_trait]
+impl SquadStore for Memory {
+    async fn build_squad(
+        &self,
+        squad_id: &str,
+        owner_id: &str,
+        capacity: u8,
+        start: Option<i64>,
+        composition: &Composition,
+        ttl: u64,
+    ) -> StoreResult<()> {
+        let owner_id = UserId(parse_id(owner_id)?);
+        let mut state = self.state()?;
+        let squad = Squad {
+            owner_id,
+            capacity,
+            filled: false,
+            cancelled: false,
+            start,
+            composition: composition.clone(),
+            quorum: None,
+            ready: false,
+            link_policy: LinkPolicy::default(),
+        };
+        state
+            .squads
+            .insert(squad_id.to_string(), Expiring::new(squad, ttl));
+        Ok(())
+    }
+
+    async fn build_posting(
+        &self,
+        channel_id: &str,
+        guild_id: Option<GuildId>,
+        message_id: &str,
+        role_id: Option<RoleId>,
+        squad_id: &str,
+        ttl: u64,
+    ) -> StoreResult<()> {
+        let channel_id = ChannelId(parse_id(channel_id)?);
+        let message_id = MessageId(parse_id(message_id)?);
+        let mut state = self.state()?;
+        let posting = Posting {
+            squad_id: squad_id.to_string(),
+            channel_id,
+            guild_id,
+            role_id,
+            rooms: Rooms::default(),
+            event_id: None,
+        };
+        state
+            .postings
+            .insert(message_id, Expiring::new(posting, ttl));
+        let mut channels = state
+            .channels
+            .remove(squad_id)
+            .map_or_else(HashSet::new, |channels| channels.value);
+        channels.insert(channel_id);
+        state
+            .channels
+            .insert(squad_id.to_string(), Expiring::new(channels, ttl));
+        Ok(())
+    }
+
+    async fn get_squad_id(&self, message_id: &str) -> StoreResult<String> {
+        let message_id = MessageId(parse_id(message_id)?);
+        let state = self.state()?;
+        match state.postings.get(&message_id) {
+            Some(posting) => Ok(posting.value.squad_id.clone()),
+            None => Err(SquadError::UnknownSquad),
+        }
+    }
+
+    async fn add_member(
+        &self,
+        squad_id: &str,
+        user_id: &str,
+        expires: u32,
+    ) -> StoreResult<JoinResult> {
+        let user_id = UserId(parse_id(user_id)?);
+        let mut state = self.state()?;
+        if !matches!(state.squad_status(squad_id), SquadStatus::Forming) {
+            return Ok(JoinResult::Closed);
+        }
+        let squad = state.squad(squad_id)?;
+        let squad_ttl = squad.ttl();
+        let slot = state.slot(squad_id, user_id);
+        if !squad.value.composition.is_empty() && slot.is_none() {
+            return Ok(JoinResult::NoSlot);
+        }
+        let result = if state.is_member(squad_id, user_id) {
+            JoinResult::AlreadyMember
+        } else if !state.has_room(squad_id, slot) {
+            let waitlist = state.waitlists.entry(squad_id.to_string()).or_default();
+            match waitlist.iter_mut().find(|(waiting, _)| *waiting == user_id) {
+                Some(entry) => entry.1 = expires,
+                None => waitlist.push_back((user_id, expires)),
+            }
+            return Ok(JoinResult::Waitlisted);
+        } else {
+            state
+                .member_sets
+                .entry(squad_id.to_string())
+                .or_insert_with(|| Expiring::new(HashSet::new(), squad_ttl))
+                .value
+                .insert(user_id);
+            if let Some(waitlist) = state.waitlists.get_mut(squad_id) {
+                waitlist.retain(|(waiting, _)| *waiting != user_id);
+            }
+            JoinResult::Joined
+        };
+        state.members.insert(
+            (squad_id.to_string(), user_id),
+            Expiring::new((), u64::from(expires)),
+        );
+        Ok(result)
+    }
+
+    async fn delete_member(&self, squad_id: &str, user_id: &str) -> StoreResult<()> {
+        let user_id = UserId(parse_id(user_id)?);
+        let mut state = self.state()?;
+        if let Some(set) = state.member_sets.get_mut(squad_id) {
+            set.value.remove(&user_id);
+        }
+        state.members.remove(&(squad_id.to_string(), user_id));
+        if let Some(waitlist) = state.waitlists.get_mut(squad_id) {
+            waitlist.retain(|(waiting, _)| *waiting != user_id);
+        }
+        Ok(())
+    }
+
+    async fn promote_members(&self, squad_id: &str) -> StoreResult<Vec<UserId>> {
+        let mut state = self.state()?;
+        if !matches!(state.squad_status(squad_id), SquadStatus::Forming) {
+            return Ok(Vec::new());
+        }
+        let squad_ttl = state.squad(squad_id)?.ttl();
+        let waitlist = state.waitlists.remove(squad_id).unwrap_or_default();
+        let mut still_waiting = VecDeque::new();
+        let mut promoted = Vec::new();
+        for (user_id, expires) in waitlist {
+            if !state.has_room(squad_id, state.slot(squad_id, user_id)) {
+                still_waiting.push_back((user_id, expires));
+                continue;
+            }
+            state
+                .member_sets
+                .entry(squad_id.to_string())
+                .or_insert_with(|| Expiring::new(HashSet::new(), squad_ttl))
+                .value
+                .insert(user_id);
+            state.members.insert(
+                (squad_id.to_string(), user_id),
+                Expiring::new((), u64::from(expires)),
+            );
+            promoted.push(user_id);
+        }
+        state.waitlists.insert(squad_id.to_string(), still_waiting);
+        Ok(promoted)
+    }
+
+    async fn choose_slot(&self, squad_id: &str, user_id: &str, slot: &str) -> StoreResult<bool> {
+        let user_id = UserId(parse_id(user_id)?);
+        let mut state = self.state()?;
+        if !matches!(state.squad_status(squad_id), SquadStatus::Forming) {
+            return Ok(false);
+        }
+        let squad = state.squad(squad_id)?;
+        if !squad.value.composition.iter().any(|(name, _)| name == slot) {
+            return Ok(false);
+        }
+        // Members moving to another slot need a free seat in it
+        let moving =
+            state.is_member(squad_id, user_id) && state.slot(squad_id, user_id) != Some(slot);
+        if moving && !state.has_seat(squad_id, slot) {
+            return Ok(false);
+        }
+        state
+            .slots
+            .insert((squad_id.to_string(), user_id), slot.to_string());
+        Ok(true)
+    }
+
+    async fn get_composition(&self, squad_id: &str) -> StoreResult<Composition> {
+        let state = self.state()?;
+        Ok(state
+            .squads
+            .get(squad_id)
+            .map_or_else(Vec::new, |squad| squad.value.composition.clone()))
+    }
+
+    async fn get_slots(&self, squad_id: &str) -> StoreResult<HashMap<UserId, String>> {
+        let state = self.state()?;
+        let slots = state
+            .slots
+            .iter()
+            .filter(|((id, _), _)| id == squad_id)
+            .map(|((_, user_id), slot)| (*user_id, slot.clone()))
+            .collect();
+        Ok(slots)
+    }
+
+    async fn set_origin(
+        &self,
+        squad_id: &str,
+        user_id: &str,
+        channel_id: ChannelId,
+    ) -> StoreResult<()> {
+        let user_id = UserId(parse_id(user_id)?);
+        let mut state = self.state()?;
+        if !state.squads.contains_key(squad_id) {
+            return Ok(());
+        }
+        state
+            .origins
+            .insert((squad_id.to_string(), user_id), channel_id);
+        Ok(())
+    }
+
+    async fn get_origins(&self, squad_id: &str) -> StoreResult<HashMap<UserId, ChannelId>> {
+        let state = self.state()?;
+        let origins = state
+            .origins
+            .iter()
+            .filter(|((id, _), _)| id == squad_id)
+            .map(|((_, user_id), channel_id)| (*user_id, *channel_id))
+            .collect();
+        Ok(origins)
+    }
+
+    async fn get_waitlist(&self, squad_id: &str) -> StoreResult<Vec<UserId>> {
+        let state = self.state()?;
+        let waitlist = state
+            .waitlists
+            .get(squad_id)
+            .map_or_else(Vec::new, |waitlist| {
+                waitlist.iter().map(|(user_id, _)| *user_id).collect()
+            });
+        Ok(waitlist)
+    }
+
+    async fn get_capacity(&self, squad_id: &str) -> StoreResult<u8> {
+        let state = self.state()?;
+        Ok(state.squad(squad_id)?.value.capacity)
+    }
+
+    async fn get_members(&self, squad_id: &str) -> StoreResult<HashMap<UserId, u64>> {
+        let state = self.state()?;
+        let set = match state.member_sets.get(squad_id) {
+            Some(set) => set,
+            None => return Ok(HashMap::new()),
+        };
+        let ttls = set
+            .value
+            .iter()
+            .filter_map(|user_id| {
+                let member = state.members.get(&(squad_id.to_string(), *user_id))?;
+                Some((*user_id, member.ttl()))
+            })
+            .collect();
+        Ok(ttls)
+    }
+
+    async fn get_squad_ttl(&self, squad_id: &str) -> StoreResult<u64> {
+        let state = self.state()?;
+        Ok(state.squad(squad_id)?.ttl())
+    }
+
+    async fn get_postings(&self) -> StoreResult<HashMap<MessageId, ChannelId>> {
+        let state = self.state()?;
+        let postings = state
+            .postings
+            .iter()
+            .map(|(message_id, posting)| (*message_id, posting.value.channel_id))
+            .collect();
+        Ok(postings)
+    }
+
+    async fn get_guild_squads(&self, guild_id: GuildId) -> StoreResult<Vec<String>> {
+        let state = self.state()?;
+        let mut squads: Vec<String> = state
+            .postings
+            .values()
+            .filter(|posting| posting.value.guild_id == Some(guild_id))
+            .map(|posting| posting.value.squad_id.clone())
+            .collect();
+        squads.sort();
+        squads.dedup();
+        Ok(squads)
+    }
+
+    async fn get_user_squads(&self, user_id: UserId) -> StoreResult<Vec<String>> {
+        let state = self.state()?;
+        let mut squads: Vec<String> = state
+            .member_sets
+            .iter()
+            .filter(|(_, set)| set.value.contains(&user_id))
+            .map(|(squad_id, _)| squad_id.clone())
+            .chain(
+                state
+                    .waitlists
+                    .iter()
+                    .filter(|(_, waitlist)| waitlist.iter().any(|(waiting, _)| *waiting == user_id))
+                    .map(|(squad_id, _)| squad_id.clone()),
+            )
+            .collect();
+        squads.sort();
+        squads.dedup();
+        Ok(squads)
+    }
+
+    async fn delete_posting(&self, message_id: &str) -> StoreResult<bool> {
+        let message_id = MessageId(parse_id(message_id)?);
+        let mut state = self.state()?;
+        let posting = match state.postings.remove(&message_id) {
+            Some(posting) => posting.value,
+            None => return Ok(false),
+        };
+        let shared = state.postings.values().any(|other| {
+            other.value.squad_id == posting.squad_id && other.value.channel_id == posting.channel_id
+        });
+        if !shared {
+            if let Some(channels) = state.channels.get_mut(&posting.squad_id) {
+                channels.value.remove(&posting.channel_id);
+            }
+        }
+        Ok(true)
+    }
+
+    async fn get_squad_postings(
+        &self,
+        squad_id: &str,
+    ) -> StoreResult<HashMap<MessageId, ChannelId>> {
+        let state = self.state()?;
+        let postings = state
+            .postings
+            .iter()
+            .filter(|(_, posting)| posting.value.squad_id == squad_id)
+            .map(|(message_id, posting)| (*message_id, posting.value.channel_id))
+            .collect();
+        Ok(postings)
+    }
+
+    async fn get_full_squads(&self) -> StoreResult<Vec<String>> {
+        let state = self.state()?;
+        let full_squads = state
+            .squads
+            .keys()
+            .filter(|squad_id| state.is_full(squad_id))
+            .cloned()
+            .collect();
+        Ok(full_squads)
+    }
+
+    async fn set_quorum(&self, squad_id: &str, quorum: u8) -> StoreResult<()> {
+        let mut state = self.state()?;
+        if let Some(squad) = state.squads.get_mut(squad_id) {
+            squad.value.quorum = Some(quorum);
+        }
+        Ok(())
+    }
+
+    async fn get_quorum(&self, squad_id: &str) -> StoreResult<Option<u8>> {
+        let state = self.state()?;
+        Ok(state
+            .squads
+            .get(squad_id)
+            .and_then(|squad| squad.value.quorum))
+    }
+
+    async fn set_link_policy(&self, squad_id: &str, policy: LinkPolicy) -> StoreResult<()> {
+        let mut state = self.state()?;
+        if let Some(squad) = state.squads.get_mut(squad_id) {
+            squad.value.link_policy = policy;
+        }
+        Ok(())
+    }
+
+    async fn get_link_policy(&self, squad_id: &str) -> StoreResult<LinkPolicy> {
+        let state = self.state()?;
+        Ok(state
+            .squads
+            .get(squad_id)
+            .map_or_else(LinkPolicy::default, |squad| squad.value.link_policy))
+    }
+
+    async fn ready_squad(&self, squad_id: &str) -> StoreResult<bool> {
+        let mut state = self.state()?;
+        if !matches!(state.squad_status(squad_id), SquadStatus::Forming) {
+            return Ok(false);
+        }
+        let members = state.member_count(squad_id);
+        let squad = &mut state.squad_mut(squad_id)?.value;
+        let quorum_met = squad
+            .quorum
+            .is_some_and(|quorum| members >= usize::from(quorum));
+        if squad.ready || !quorum_met || !squad.has_started() {
+            return Ok(false);
+        }
+        squad.ready = true;
+        Ok(true)
+    }
+
+    async fn is_ready(&self, squad_id: &str) -> StoreResult<bool> {
+        let state = self.state()?;
+        Ok(state
+            .squads
+            .get(squad_id)
+            .is_some_and(|squad| squad.value.ready))
+    }
+
+    async fn fill_squad(&self, squad_id: &str) -> StoreResult<bool> {
+        let mut state = self.state()?;
+        if !state.is_full(squad_id) {
+            return Ok(false);
+        }
+        state.squad_mut(squad_id)?.value.filled = true;
+        Ok(true)
+    }
+
+    async fn close_squad(&self, squad_id: &str) -> StoreResult<bool> {
+        let mut state = self.state()?;
+        let forming = matches!(state.squad_status(squad_id), SquadStatus::Forming);
+        if !forming || state.member_count(squad_id) == 0 {
+            return Ok(false);
+        }
+        state.squad_mut(squad_id)?.value.filled = true;
+        Ok(true)
+    }
+
+    async fn cancel_squad(&self, squad_id: &str) -> StoreResult<bool> {
+        let mut state = self.state()?;
+        if !matches!(state.squad_status(squad_id), SquadStatus::Forming) {
+            return Ok(false);
+        }
+        state.squad_mut(squad_id)?.value.cancelled = true;
+        Ok(true)
+    }
+
+    async fn extend_squad(&self, squad_id: &str, seconds: u64) -> StoreResult<bool> {
+        let mut state = self.state()?;
+        if !matches!(state.squad_status(squad_id), SquadStatus::Forming) {
+            return Ok(false);
+        }
+        let extension = Duration::from_secs(seconds);
+        state.squad_mut(squad_id)?.expires += extension;
+        if let Some(set) = state.member_sets.get_mut(squad_id) {
+            set.expires += extension;
+        }
+        if let Some(channels) = state.channels.get_mut(squad_id) {
+            channels.expires += extension;
+        }
+        for posting in state.postings.values_mut() {
+            if posting.value.squad_id == squad_id {
+                posting.expires += extension;
+            }
+        }
+        Ok(true)
+    }
+
+    async fn get_owner(&self, squad_id: &str) -> StoreResult<Option<UserId>> {
+        let state = self.state()?;
+        Ok(state.squads.get(squad_id).map(|squad| squad.value.owner_id))
+    }
+
+    async fn get_start(&self, squad_id: &str) -> StoreResult<Option<i64>> {
+        let state = self.state()?;
+        Ok(state
+            .squads
+            .get(squad_id)
+            .and_then(|squad| squad.value.start))
+    }
+
+    async fn get_squad_status(&self, squad_id: &str) -> StoreResult<SquadStatus> {
+        let state = self.state()?;
+        Ok(state.squad_status(squad_id))
+    }
+
+    async fn get_role_id(&self, message_id: &str) -> StoreResult<Option<RoleId>> {
+        let message_id = MessageId(parse_id(message_id)?);
+        let state = self.state()?;
+        Ok(state
+            .postings
+            .get(&message_id)
+            .and_then(|posting| posting.value.role_id))
+    }
+
+    async fn get_channels(&self, squad_id: &str) -> StoreResult<Vec<ChannelId>> {
+        let state = self.state()?;
+        let channels = state
+            .channels
+            .get(squad_id)
+            .map_or_else(Vec::new, |channels| {
+                channels.value.iter().copied().collect()
+            });
+        Ok(channels)
+    }
+
+    async fn set_rooms(&self, message_id: &str, rooms: &Rooms) -> StoreResult<()> {
+        let message_id = MessageId(parse_id(message_id)?);
+        let mut state = self.state()?;
+        let posting = state
+            .postings
+            .get_mut(&message_id)
+            .ok_or(SquadError::UnknownSquad)?;
+        posting.value.rooms = *rooms;
+        if let Some(voice) = rooms.voice {
+            state.voice_channels.insert(voice);
+        }
+        Ok(())
+    }
+
+    async fn get_rooms(&self, message_id: &str) -> StoreResult<Rooms> {
+        let message_id = MessageId(parse_id(message_id)?);
+        let state = self.state()?;
+        Ok(state
+            .postings
+            .get(&message_id)
+            .map_or_else(Rooms::default, |posting| posting.value.rooms))
+    }
+
+    async fn set_event(
+        &self,
+        message_id: &str,
+        event_id: Option<ScheduledEventId>,
+    ) -> StoreResult<()> {
+        let message_id = MessageId(parse_id(message_id)?);
+        let mut state = self.state()?;
+        let posting = state
+            .postings
+            .get_mut(&message_id)
+            .ok_or(SquadError::UnknownSquad)?;
+        posting.value.event_id = event_id;
+        Ok(())
+    }
+
+    async fn get_event(&self, message_id: &str) -> StoreResult<Option<ScheduledEventId>> {
+        let message_id = MessageId(parse_id(message_id)?);
+        let state = self.state()?;
+        Ok(state
+            .postings
+            .get(&message_id)
+            .and_then(|posting| posting.value.event_id))
+    }
+
+    async fn get_voice_channels(&self) -> StoreResult<Vec<ChannelId>> {
+        let state = self.state()?;
+        Ok(state.voice_channels.iter().copied().collect())
+    }
+
+    async fn delete_voice_channel(&self, channel_id: ChannelId) -> StoreResult<()> {
+        let mut state = self.state()?;
+        state.voice_channels.remove(&channel_id);
+        Ok(())
+    }
+
+    async fn get_guild_settings(&self, guild_id: GuildId) -> StoreResult<GuildSettings> {
+        let state = self.state()?;
+        Ok(state.guilds.get(&guild_id).cloned().unwrap_or_default())
+    }
+
+    async fn set_guild_settings(
+        &self,
+        guild_id: GuildId,
+        settings: &GuildSettings,
+    ) -> StoreResult<()> {
+        let mut state = self.state()?;
+        state.guilds.insert(guild_id, settings.clone());
+        Ok(())
+    }
+}
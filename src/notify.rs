@@ -1,50 +1,397 @@
 use crate::embed;
-use crate::redis_io;
+use crate::error::SquadError;
+use crate::locale;
+use crate::locale::Text;
+use crate::rooms;
+use crate::store;
+use crate::store::{SquadStatus, SquadStore};
+use chrono::Utc;
 use serenity::client::Context;
-use serenity::model::prelude::Mention;
-use std::error::Error;
+use serenity::model::id::UserId;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use typemap_rev::TypeMapKey;
+
+/// Seconds before a member's availability or an unfilled squad runs out at which a
+/// reminder is sent.
+const REMINDER_SECONDS: u64 = 10 * 60;
+
+pub struct SentReminders;
+
+/// Globally available TypeMapKey to store the reminders sent about each squad and its
+/// members, along with the expiry time they were sent for.
+impl TypeMapKey for SentReminders {
+    type Value = Arc<Mutex<HashMap<(String, Option<UserId>), i64>>>;
+}
 
 /// DMs a notification to each member of every given squad (presumably filled squads).
 pub async fn notify_squads(
     ctx: &Context,
-    con: &mut redis::Connection,
+    store: &dyn SquadStore,
     squads: Vec<String>,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<(), SquadError> {
+    for squad in squads {
+        // Mark the squad as filled, skipping it if it was filled elsewhere or someone
+        // left since it was found to be full
+        if !store.fill_squad(&squad).await? {
+            continue;
+        }
+        // Members of squads that reached their quorum were notified back then, and
+        // everyone who joined since was welcomed as a late joiner
+        if store.is_ready(&squad).await? {
+            continue;
+        }
+        notify_ready(ctx, store, &squad).await?;
+    }
+    Ok(())
+}
+
+/// DMs a notification to each member of every given squad that just reached its
+/// quorum. The squads stay open to late joiners until they fill.
+pub async fn notify_quorum(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squads: &HashSet<String>,
+) -> Result<(), SquadError> {
     for squad in squads {
-        // Get members of squad and the channel of the squad posting
-        let members = redis_io::get_members(con, &squad)?;
-        // Include roster of squad members in each message
-        let mut roster = String::from("**Members**\n");
+        // Flag the squad as ready, skipping it if it was flagged elsewhere or hasn't
+        // reached its quorum
+        if !store.ready_squad(squad).await? {
+            continue;
+        }
+        notify_ready(ctx, store, squad).await?;
+    }
+    Ok(())
+}
+
+/// Lets a user who just joined a squad that reached its quorum into its rooms and DMs
+/// them its roster and rooms, since its other members are already playing. Does
+/// nothing for squads that haven't reached their quorum.
+pub async fn notify_late_joiner(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+    user_id: UserId,
+) -> Result<(), SquadError> {
+    if !store.is_ready(squad_id).await? {
+        return Ok(());
+    }
+    let rooms = rooms::admit_member(ctx, store, squad_id, user_id).await?;
+    if !dms_allowed(ctx, store, squad_id).await? {
+        return Ok(());
+    }
+    let text = locale::member_locale(ctx, store, squad_id, user_id)
+        .await?
+        .text();
+    let servers = embed::member_servers(ctx, store, squad_id).await?;
+    let mut roster = format!("{}\n", text.members);
+    for member in store.get_members(squad_id).await?.keys() {
+        roster.push_str(&format!("{}\n", embed::member_mention(*member, &servers)));
+    }
+    let channels = channel_list(ctx, store, squad_id, user_id, text).await?;
+    let mut description = format!("{}\n\n{}\n{}", text.late_description, roster, channels);
+    if !rooms.is_empty() {
+        description.push_str(&format!("\n{}\n{}", text.join_rooms, rooms));
+    }
+    send_dm(ctx, user_id, text.late_title, &description).await;
+    Ok(())
+}
+
+/// Opens the voice channels and threads of a squad that was just filled, closed or
+/// reached its quorum, then DMs its roster and rooms to each of its members.
+pub async fn notify_ready(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+) -> Result<(), SquadError> {
+    let rooms = rooms::open_rooms(ctx, store, squad_id).await?;
+    if !dms_allowed(ctx, store, squad_id).await? {
+        return Ok(());
+    }
+    // Get members of squad and the channel of the squad posting
+    let members = store.get_members(squad_id).await?;
+    let scheduled = store.get_start(squad_id).await?.is_some();
+    let slots = store.get_slots(squad_id).await?;
+    let servers = embed::member_servers(ctx, store, squad_id).await?;
+    // Send message to each squad member in their language, with the roster of squad
+    // members and the channels as they can open them
+    for user_id in members.keys() {
+        let text = locale::member_locale(ctx, store, squad_id, *user_id)
+            .await?
+            .text();
+        let mut roster = format!("{}\n", text.members);
         for (key, value) in &members {
-            let mention = format!("{}", Mention::from(*key));
-            let ttl = embed::format_ttl(*value);
-            let line = &format!("{} available for {}\n", mention, ttl)[..];
-            roster.push_str(line);
-        }
-        // Build list of channels that the squad was posted in
-        let channel_ids = redis_io::get_channels(con, &squad)?;
-        let mut channels = String::from("**Channels**\n");
-        for channel in &channel_ids {
-            let mention = &format!("{}\n", Mention::from(*channel))[..];
-            channels.push_str(mention)
-        }
-        // Mark the squad as filled
-        redis_io::fill_squad(con, &squad)?;
-        // Send message to each squad member
-        for (user_id, _ttl) in &members {
-            if let Ok(dm_channel) = user_id.create_dm_channel(&ctx.http).await {
-                let _ = dm_channel
-                    .send_message(&ctx.http, |m| {
-                        m.embed(|e| {
-                            e.title("**Your squad is ready!**");
-                            e.description(format!("{}\n{}", roster, channels));
-                            e
-                        });
-                        m
-                    })
-                    .await;
+            // Label members of squads with role slots with their slot
+            let mention = embed::member_mention(*key, &servers);
+            let mention = match slots.get(key) {
+                Some(slot) => format!("{} ({})", mention, embed::slot_label(slot)),
+                None => mention,
+            };
+            // Members of scheduled squads committed to the start time itself
+            let line = if scheduled {
+                format!("{}\n", mention)
+            } else {
+                let ttl = embed::format_ttl(*value);
+                format!("{}\n", locale::fill(text.available_for, &[&mention, &ttl]))
+            };
+            roster.push_str(&line);
+        }
+        let channels = channel_list(ctx, store, squad_id, *user_id, text).await?;
+        let mut description = format!("{}\n{}", roster, channels);
+        if !rooms.is_empty() {
+            description.push_str(&format!("\n{}\n{}", text.join_rooms, rooms));
+        }
+        send_dm(ctx, *user_id, text.ready_title, &description).await;
+    }
+    Ok(())
+}
+
+/// Moves waitlisted users into the free spots of a squad and DMs each promoted user.
+pub async fn promote_waitlist(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+) -> Result<(), SquadError> {
+    let promoted: Vec<UserId> = store.promote_members(squad_id).await?;
+    if promoted.is_empty() {
+        return Ok(());
+    }
+    // Users promoted into a squad that's already playing are welcomed as late joiners
+    if store.is_ready(squad_id).await? {
+        for user_id in promoted {
+            notify_late_joiner(ctx, store, squad_id, user_id).await?;
+        }
+        return Ok(());
+    }
+    if !dms_allowed(ctx, store, squad_id).await? {
+        return Ok(());
+    }
+    // Point promoted users to the channels that the squad was posted in
+    for user_id in promoted {
+        let text = locale::member_locale(ctx, store, squad_id, user_id)
+            .await?
+            .text();
+        let channels = channel_list(ctx, store, squad_id, user_id, text).await?;
+        let description = format!("{}\n\n{}", text.promoted_description, channels);
+        send_dm(ctx, user_id, text.promoted_title, &description).await;
+    }
+    Ok(())
+}
+
+/// DMs the given members of a squad that its owner cancelled it.
+pub async fn notify_cancelled(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+    members: Vec<UserId>,
+) -> Result<(), SquadError> {
+    if !dms_allowed(ctx, store, squad_id).await? {
+        return Ok(());
+    }
+    for user_id in members {
+        let text = locale::member_locale(ctx, store, squad_id, user_id)
+            .await?
+            .text();
+        let channels = channel_list(ctx, store, squad_id, user_id, text).await?;
+        let description = format!("{}\n\n{}", text.cancelled_description, channels);
+        send_dm(ctx, user_id, text.cancelled_title, &description).await;
+    }
+    Ok(())
+}
+
+/// Sends the reminders that are due for the given squads: members of squads without
+/// a start time are reminded shortly before their availability runs out, owners
+/// shortly before their squad expires unfilled. Each reminder is sent once for every
+/// expiry time, so members and squads that are extended can be reminded again.
+pub async fn send_reminders(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squads: &HashSet<String>,
+) -> Result<(), SquadError> {
+    let sent = get_sent_reminders(ctx).await;
+    let mut sent = sent.lock().await;
+    let now = Utc::now().timestamp();
+    sent.retain(|_, expires| *expires > now);
+    for squad_id in squads {
+        if !matches!(
+            store.get_squad_status(squad_id).await?,
+            SquadStatus::Forming
+        ) || !reminders_allowed(ctx, store, squad_id).await?
+        {
+            continue;
+        }
+        let squad_ttl = store.get_squad_ttl(squad_id).await?;
+        // Members of scheduled squads stay until the squad itself expires
+        if store.get_start(squad_id).await?.is_none() {
+            for (user_id, ttl) in store.get_members(squad_id).await? {
+                if ttl > REMINDER_SECONDS || ttl >= squad_ttl {
+                    continue;
+                }
+                let expires = now + ttl as i64;
+                if !remind_once(&mut sent, squad_id, Some(user_id), expires) {
+                    continue;
+                }
+                let text = locale::member_locale(ctx, store, squad_id, user_id)
+                    .await?
+                    .text();
+                let channels = channel_list(ctx, store, squad_id, user_id, text).await?;
+                let reminder = locale::fill(
+                    text.member_reminder,
+                    &[squad_id, &embed::format_timestamp(ttl, 'R')],
+                );
+                let description = format!("{}\n\n{}", reminder, channels);
+                let buttons = vec![embed::ButtonChoice::Extend(squad_id.clone())];
+                let title = text.member_reminder_title;
+                send_reminder(ctx, user_id, title, &description, buttons, text).await;
             }
         }
+        if squad_ttl > REMINDER_SECONDS {
+            continue;
+        }
+        let owner = match store.get_owner(squad_id).await? {
+            Some(owner) => owner,
+            None => continue,
+        };
+        if !remind_once(&mut sent, squad_id, None, now + squad_ttl as i64) {
+            continue;
+        }
+        let text = locale::member_locale(ctx, store, squad_id, owner)
+            .await?
+            .text();
+        let channels = channel_list(ctx, store, squad_id, owner, text).await?;
+        let reminder = locale::fill(
+            text.squad_reminder,
+            &[squad_id, &embed::format_timestamp(squad_ttl, 'R')],
+        );
+        let description = format!("{}\n\n{}", reminder, channels);
+        let buttons = vec![
+            embed::ButtonChoice::ExtendSquad(squad_id.clone()),
+            embed::ButtonChoice::CloseSquad(squad_id.clone()),
+        ];
+        let title = text.squad_reminder_title;
+        send_reminder(ctx, owner, title, &description, buttons, text).await;
     }
     Ok(())
 }
+
+/// Record a reminder about a member of a squad, or about the squad itself when no
+/// member is given, for the given expiry time. Returns whether it wasn't sent yet.
+fn remind_once(
+    sent: &mut HashMap<(String, Option<UserId>), i64>,
+    squad_id: &str,
+    user_id: Option<UserId>,
+    expires: i64,
+) -> bool {
+    let key = (squad_id.to_string(), user_id);
+    // Expiry times read a second apart can differ by a second
+    if let Some(reminded) = sent.get(&key) {
+        if (expires - reminded).abs() <= 60 {
+            return false;
+        }
+    }
+    sent.insert(key, expires);
+    true
+}
+
+/// Retrieve the record of sent reminders from the global data context.
+async fn get_sent_reminders(ctx: &Context) -> Arc<Mutex<HashMap<(String, Option<UserId>), i64>>> {
+    let mut data = ctx.data.write().await;
+    let sent = data
+        .entry::<SentReminders>()
+        .or_insert_with(|| Arc::new(Mutex::new(HashMap::new())));
+    Arc::clone(sent)
+}
+
+/// Whether reminders may be sent about a squad, which takes every server the squad
+/// was posted in to allow both DMs and reminders.
+async fn reminders_allowed(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+) -> Result<bool, SquadError> {
+    for channel_id in store.get_channels(squad_id).await? {
+        let settings = store::get_channel_settings(ctx, store, channel_id).await?;
+        if !settings.dms() || !settings.reminders() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Whether members of a squad may be sent DMs, which takes every server the squad was
+/// posted in to allow them.
+async fn dms_allowed(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+) -> Result<bool, SquadError> {
+    for channel_id in store.get_channels(squad_id).await? {
+        if !store::get_channel_settings(ctx, store, channel_id)
+            .await?
+            .dms()
+        {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Build list of channels that the squad was posted in, as the given user can open
+/// them: channels of servers the user isn't in are named along with their server
+async fn channel_list(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+    user_id: UserId,
+    text: &Text,
+) -> Result<String, SquadError> {
+    let channel_ids = store.get_channels(squad_id).await?;
+    let mut channels = format!("{}\n", text.channels);
+    for channel in &channel_ids {
+        let reference = embed::channel_reference(ctx, *channel, user_id).await;
+        channels.push_str(&format!("{}\n", reference))
+    }
+    Ok(channels)
+}
+
+/// Send an embed with reminder buttons, labelled in the given language, to a user by
+/// DM. Users who don't accept DMs are skipped.
+async fn send_reminder(
+    ctx: &Context,
+    user_id: UserId,
+    title: &str,
+    description: &str,
+    buttons: Vec<embed::ButtonChoice>,
+    text: &Text,
+) {
+    if let Ok(dm_channel) = user_id.create_dm_channel(&ctx.http).await {
+        let _ = dm_channel
+            .send_message(&ctx.http, |m| {
+                m.embed(|e| {
+                    e.title(title);
+                    e.description(description);
+                    e
+                });
+                m.components(|c| c.add_action_row(embed::dm_row(buttons, text)));
+                m
+            })
+            .await;
+    }
+}
+
+/// Send an embed to a user by DM. Users who don't accept DMs are skipped.
+pub async fn send_dm(ctx: &Context, user_id: UserId, title: &str, description: &str) {
+    if let Ok(dm_channel) = user_id.create_dm_channel(&ctx.http).await {
+        let _ = dm_channel
+            .send_message(&ctx.http, |m| {
+                m.embed(|e| {
+                    e.title(title);
+                    e.description(description);
+                    e
+                });
+                m
+            })
+            .await;
+    }
+}
@@ -1,407 +1,1672 @@
-use redis;
-use serenity::model::id::UserId;
+use crate::error::SquadError;
+use crate::locale::Locale;
+use crate::store::{
+    format_availability, format_composition, format_roles, parse_availability, parse_composition,
+    parse_roles, Composition, GuildSettings, JoinResult, LinkPolicy, Rooms, SquadStatus,
+    SquadStore, StoreResult,
+};
+use chrono::Utc;
+use futures::StreamExt;
+use redis::aio::ConnectionManager;
+use serenity::async_trait;
+use serenity::model::id::{GuildId, ScheduledEventId, UserId};
 use serenity::model::prelude::{ChannelId, MessageId, RoleId};
-use serenity::prelude::Context;
 use std::collections::HashMap;
-use std::error::Error;
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use typemap_rev::TypeMapKey;
+use std::str::FromStr;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
-pub struct Redis;
-
-/// Globally available TypeMapKey to store client for Redis connection
-impl TypeMapKey for Redis {
-    type Value = Arc<RwLock<redis::Client>>;
-}
-
-pub enum SquadStatus {
-    Expired,
-    Forming,
-    Filled,
+/// Squad store backed by a live Redis server.
+/// All commands go through one multiplexed async connection which reconnects on its
+/// own, so interactions neither block the runtime nor pay for connection setup.
+pub struct Redis {
+    client: redis::Client,
+    manager: ConnectionManager,
+    /// Whether the server sends the keyspace notifications that subscribe relies on.
+    keyspace_events: bool,
 }
 
-/// Expiration time in seconds for squad postings
-const POSTING_TTL: u64 = 11 * 60 * 60;
-/// Expiration time in seconds for squad data.
-const SQUAD_TTL: u64 = 10 * 60 * 60;
-
-/// Retrieve redis connection from the global data context.
-pub async fn get_redis_connection(ctx: &Context) -> Result<redis::Connection, Box<dyn Error>> {
-    let data_read = ctx.data.read().await;
-    let redis_client_lock = match data_read.get::<Redis>() {
-        Some(lock) => lock.clone(),
-        None => {
-            return Err("Unable to get Redis client lock.".into());
-        }
-    };
-    let redis_client = redis_client_lock.read().await;
-    let con = redis_client.get_connection()?;
-    Ok(con)
-}
+impl Redis {
+    /// Connect to the Redis server at the given url and make sure the active-squad
+    /// indexes cover any data written before they existed.
+    pub async fn open(redis_url: &str) -> redis::RedisResult<Redis> {
+        let client = redis::Client::open(redis_url)?;
+        let mut manager = ConnectionManager::new(client.clone()).await?;
+        rebuild_indexes(&mut manager).await?;
+        let keyspace_events = enable_keyspace_events(&mut manager).await;
+        Ok(Redis {
+            client,
+            manager,
+            keyspace_events,
+        })
+    }
 
-/// Helper function to retrieve squad id from a given posting (message id)
-pub fn get_squad_id(
-    con: &mut redis::Connection,
-    message_id: &String,
-) -> redis::RedisResult<String> {
-    let posting_id = posting_id(&message_id);
-    let squad_id = redis::cmd("HGET")
-        .arg(&posting_id)
-        .arg("squad")
-        .query::<String>(con)?;
-    Ok(squad_id)
+    /// Handle to the shared connection. Cloning is cheap and every clone
+    /// multiplexes over the same underlying connection.
+    fn connection(&self) -> ConnectionManager {
+        self.manager.clone()
+    }
 }
 
 /// Helper function to create a members id for Redis.
 /// This is the key of the Set which contains ids of squad members.
-fn members_id(squad_id: &String) -> String {
+fn members_id(squad_id: &str) -> String {
     format!("members:{}", squad_id)
 }
 
 /// Helper function to create a member id for Redis.
 /// This is the Key of the key-value pair for a squad member.
-fn member_id(squad_id: &String, user_id: &String) -> String {
+fn member_id(squad_id: &str, user_id: &str) -> String {
     format!("member:{}:{}", squad_id, user_id)
 }
 
+/// Helper function to create a waitlist id for Redis.
+/// This is the key of the List which queues users waiting for a spot in a full squad.
+fn waitlist_id(squad_id: &str) -> String {
+    format!("waitlist:{}", squad_id)
+}
+
+/// Helper function to create a slots id for Redis.
+/// This is the key of the Hash which maps user ids to the slot they picked.
+fn slots_id(squad_id: &str) -> String {
+    format!("slots:{}", squad_id)
+}
+
+/// Helper function to create an origins id for Redis.
+/// This is the key of the Hash which maps user ids to the channel of the posting they
+/// joined through.
+fn origins_id(squad_id: &str) -> String {
+    format!("origins:{}", squad_id)
+}
+
+/// Helper function to create a guild id for Redis.
+/// This is the key of the Hash which holds the settings of a guild.
+fn guild_id(guild_id: GuildId) -> String {
+    format!("guild:{}", guild_id.as_u64())
+}
+
 /// Helper function to create a squad posting id.
 /// This is the Key of the key-value pair for a squad posting.
-pub fn posting_id(message_id: &String) -> String {
+fn posting_id(message_id: &str) -> String {
     format!("posting:{}", message_id)
 }
 
+/// Helper function to create a postings id for Redis.
+/// This is the key of the Set which contains the message ids of a squad's postings.
+fn postings_id(squad_id: &str) -> String {
+    format!("postings:{}", squad_id)
+}
+
+/// Helper function to create a start id for Redis.
+/// This is the key which expires when a scheduled squad starts.
+fn start_id(squad_id: &str) -> String {
+    format!("start:{}", squad_id)
+}
+
 /// Helper function to create a channels id for Redis.
 /// This is the key of the Set which contains all channels which a squad was posted in.
-pub fn channels_id(squad_id: &String) -> String {
+fn channels_id(squad_id: &str) -> String {
     format!("channels:{}", squad_id)
 }
 
+/// Key of the Set which contains the ids of voice channels opened for filled squads
+/// that haven't been cleaned up. Never expires.
+const VOICE_CHANNELS: &str = "voice_channels";
+
+/// Key of the Sorted Set indexing all live squad postings.
+/// Members are posting ids, scored by the unix timestamp at which they expire.
+const POSTINGS_INDEX: &str = "index:postings";
+
+/// Key of the Sorted Set indexing all live squads.
+/// Members are squad ids, scored by the unix timestamp at which they expire.
+const SQUADS_INDEX: &str = "index:squads";
+
+/// Key of the Sorted Set indexing the live squad postings of a guild.
+/// Members are posting ids, scored by the unix timestamp at which they expire.
+/// Expires along with its last posting.
+fn guild_postings_index(guild_id: GuildId) -> String {
+    format!("{}:{}", POSTINGS_INDEX, guild_id.as_u64())
+}
+
+/// Key of the Sorted Set indexing the squads a user is a member of or waiting to join.
+/// Members are squad ids, scored by the unix timestamp at which they expire.
+/// Expires along with its last squad.
+fn user_squads_index(user_id: &str) -> String {
+    format!("{}:{}", SQUADS_INDEX, user_id)
+}
+
+/// Unix timestamp in seconds at which something with the given ttl expires.
+fn expires_at(ttl: u64) -> i64 {
+    Utc::now().timestamp() + ttl as i64
+}
+
+/// Remove entries that have expired from an index and return the live ones.
+async fn live_index_members(
+    con: &mut ConnectionManager,
+    index: &str,
+) -> redis::RedisResult<Vec<String>> {
+    let now = Utc::now().timestamp();
+    redis::cmd("ZREMRANGEBYSCORE")
+        .arg(index)
+        .arg("-inf")
+        .arg(now)
+        .query_async::<_, ()>(con)
+        .await?;
+    redis::cmd("ZRANGE")
+        .arg(index)
+        .arg(0)
+        .arg(-1)
+        .query_async(con)
+        .await
+}
+
+/// Add every posting and squad key to its index, using SCAN rather than KEYS so the
+/// server isn't blocked. Only needed once for data created before the indexes.
+async fn rebuild_indexes(con: &mut ConnectionManager) -> redis::RedisResult<()> {
+    for (pattern, index) in [("posting:*", POSTINGS_INDEX), ("squad:*", SQUADS_INDEX)] {
+        let mut keys: Vec<String> = Vec::new();
+        let mut cursor: u64 = 0;
+        loop {
+            let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .cursor_arg(cursor)
+                .arg("MATCH")
+                .arg(pattern)
+                .query_async(con)
+                .await?;
+            keys.extend(batch);
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+        for key in keys {
+            let ttl = redis::cmd("TTL")
+                .arg(&key)
+                .query_async::<_, i64>(con)
+                .await?;
+            if ttl <= 0 {
+                continue;
+            }
+            redis::cmd("ZADD")
+                .arg(index)
+                .arg(expires_at(ttl as u64))
+                .arg(&key)
+                .query_async::<_, ()>(con)
+                .await?;
+            if index == POSTINGS_INDEX {
+                let (squad_id, message_id): (String, String) = redis::cmd("HMGET")
+                    .arg(&key)
+                    .arg("squad")
+                    .arg("message")
+                    .query_async(con)
+                    .await?;
+                let postings_id = postings_id(&squad_id);
+                redis::cmd("SADD")
+                    .arg(&postings_id)
+                    .arg(message_id)
+                    .query_async::<_, ()>(con)
+                    .await?;
+                redis::cmd("EXPIRE")
+                    .arg(&postings_id)
+                    .arg(ttl)
+                    .query_async::<_, ()>(con)
+                    .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Keyspace notification classes required by subscribe: K for keyspace events, g for
+/// generic commands such as DEL and EXPIRE, x for expiry, and $, h, s and z for writes
+/// to the strings, hashes, sets and sorted sets of squads.
+const KEYSPACE_EVENTS: &str = "Kgxhsz$";
+/// Classes that the A class of notify-keyspace-events stands for.
+const KEYSPACE_ALL: &str = "g$lshzxetd";
+
+/// Add the keyspace notification classes required by subscribe to those the server
+/// already sends, keeping the rest. Returns whether the server sends them all.
+/// Managed servers may refuse CONFIG, in which case notifications must be enabled
+/// in the server configuration instead.
+async fn enable_keyspace_events(con: &mut ConnectionManager) -> bool {
+    let result: redis::RedisResult<()> = async {
+        let (_, current): (String, String) = redis::cmd("CONFIG")
+            .arg("GET")
+            .arg("notify-keyspace-events")
+            .query_async(con)
+            .await?;
+        let missing: String = KEYSPACE_EVENTS
+            .chars()
+            .filter(|class| {
+                let all = current.contains('A') && KEYSPACE_ALL.contains(*class);
+                !all && !current.contains(*class)
+            })
+            .collect();
+        if !missing.is_empty() {
+            redis::cmd("CONFIG")
+                .arg("SET")
+                .arg("notify-keyspace-events")
+                .arg(format!("{}{}", current, missing))
+                .query_async::<_, ()>(con)
+                .await?;
+        }
+        Ok(())
+    }
+    .await;
+    match result {
+        Ok(()) => true,
+        Err(why) => {
+            eprintln!("Unable to enable Redis keyspace notifications: {}", why);
+            false
+        }
+    }
+}
+
+/// Map the key named in a keyspace notification to the id of the squad it belongs to.
+async fn changed_squad(con: &mut ConnectionManager, key: &str) -> Option<String> {
+    if let Some(member) = key.strip_prefix("member:") {
+        let (squad_id, _user_id) = member.rsplit_once(':')?;
+        Some(squad_id.to_string())
+    } else if key.starts_with("squad:") {
+        Some(key.to_string())
+    } else if let Some(squad_id) = key.strip_prefix("start:") {
+        Some(squad_id.to_string())
+    } else if let Some(message_id) = key.strip_prefix("posting:") {
+        // Expired postings can no longer be mapped back to their squad
+        get_squad_id(con, message_id).await.ok().flatten()
+    } else {
+        None
+    }
+}
+
+/// Forward the squad ids of keyspace notifications on member, squad, start and posting keys
+/// until the subscription drops or nobody is listening anymore.
+async fn forward_keyspace_events(
+    client: &redis::Client,
+    con: &mut ConnectionManager,
+    sender: &UnboundedSender<String>,
+) -> redis::RedisResult<()> {
+    let mut pubsub = client.get_async_connection().await?.into_pubsub();
+    for pattern in ["member:*", "squad:*", "start:*", "posting:*"] {
+        pubsub
+            .psubscribe(format!("__keyspace@*__:{}", pattern))
+            .await?;
+    }
+    let mut messages = pubsub.on_message();
+    while let Some(message) = messages.next().await {
+        let key = match message.get_channel_name().split_once("__:") {
+            Some((_, key)) => key,
+            None => continue,
+        };
+        if let Some(squad_id) = changed_squad(con, key).await {
+            if sender.send(squad_id).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Helper function to retrieve squad id from a given posting (message id), None if
+/// the posting doesn't exist
+async fn get_squad_id(
+    con: &mut ConnectionManager,
+    message_id: &str,
+) -> redis::RedisResult<Option<String>> {
+    let posting_id = posting_id(message_id);
+    let squad_id = redis::cmd("HGET")
+        .arg(&posting_id)
+        .arg("squad")
+        .query_async::<_, Option<String>>(con)
+        .await?;
+    Ok(squad_id)
+}
+
 /// Add new data for squad postings to the Redis data store
 /// HASH posting:msg_id
 ///     field channel: id of channel in which squad posting was made
+///     field guild: id of the guild of that channel (if not a DM)
 ///     field message: id of message containing squad posting
 ///     field role: role ID (if any) that was mentioned in the /squad command
-///     expires in POSTING_TTL seconds
-pub fn build_posting(
-    con: &mut redis::Connection,
-    channel_id: &String,
-    message_id: &String,
+///     field voice: id of the voice channel opened when the squad filled (if any)
+///     field thread: id of the thread opened when the squad filled (if any)
+///     field event: id of the Discord scheduled event created for the squad (if any)
+///     expires in ttl seconds
+/// The posting is also added to the postings index, and that of its guild.
+async fn build_posting(
+    con: &mut ConnectionManager,
+    channel_id: &str,
+    guild_id: Option<GuildId>,
+    message_id: &str,
     role_id: Option<RoleId>,
-    squad_id: &String,
+    squad_id: &str,
+    ttl: u64,
 ) -> redis::RedisResult<()> {
-    let posting_id = posting_id(&message_id);
-    let channels_id = channels_id(&squad_id);
+    let posting_id = posting_id(message_id);
+    let channels_id = channels_id(squad_id);
     redis::cmd("HSET")
         .arg(&posting_id)
         .arg("squad")
-        .arg(&squad_id)
-        .query(con)?;
+        .arg(squad_id)
+        .query_async::<_, ()>(con)
+        .await?;
     redis::cmd("HSET")
         .arg(&posting_id)
         .arg("channel")
-        .arg(&channel_id)
-        .query(con)?;
+        .arg(channel_id)
+        .query_async::<_, ()>(con)
+        .await?;
     redis::cmd("HSET")
         .arg(&posting_id)
         .arg("message")
-        .arg(&message_id)
-        .query(con)?;
+        .arg(message_id)
+        .query_async::<_, ()>(con)
+        .await?;
     redis::cmd("SADD")
         .arg(&channels_id)
-        .arg(&channel_id)
-        .query(con)?;
+        .arg(channel_id)
+        .query_async::<_, ()>(con)
+        .await?;
     redis::cmd("EXPIRE")
         .arg(&channels_id)
-        .arg(POSTING_TTL)
-        .query(con)?;
-    match role_id {
-        Some(id) => {
-            redis::cmd("HSET")
-                .arg(&posting_id)
-                .arg("role")
-                .arg(id.as_u64().to_string())
-                .query(con)?;
-        }
-        None => {}
+        .arg(ttl)
+        .query_async::<_, ()>(con)
+        .await?;
+    if let Some(id) = role_id {
+        redis::cmd("HSET")
+            .arg(&posting_id)
+            .arg("role")
+            .arg(id.as_u64().to_string())
+            .query_async::<_, ()>(con)
+            .await?;
     }
     redis::cmd("EXPIRE")
         .arg(&posting_id)
-        .arg(POSTING_TTL)
-        .query(con)?;
+        .arg(ttl)
+        .query_async::<_, ()>(con)
+        .await?;
+    redis::cmd("ZADD")
+        .arg(POSTINGS_INDEX)
+        .arg(expires_at(ttl))
+        .arg(&posting_id)
+        .query_async::<_, ()>(con)
+        .await?;
+    if let Some(guild_id) = guild_id {
+        redis::cmd("HSET")
+            .arg(&posting_id)
+            .arg("guild")
+            .arg(guild_id.as_u64().to_string())
+            .query_async::<_, ()>(con)
+            .await?;
+        let index = guild_postings_index(guild_id);
+        redis::cmd("ZADD")
+            .arg(&index)
+            .arg(expires_at(ttl))
+            .arg(&posting_id)
+            .query_async::<_, ()>(con)
+            .await?;
+        let index_ttl = redis::cmd("TTL")
+            .arg(&index)
+            .query_async::<_, i64>(con)
+            .await?;
+        if index_ttl < ttl as i64 {
+            redis::cmd("EXPIRE")
+                .arg(&index)
+                .arg(ttl)
+                .query_async::<_, ()>(con)
+                .await?;
+        }
+    }
+    let postings_id = postings_id(squad_id);
+    redis::cmd("SADD")
+        .arg(&postings_id)
+        .arg(message_id)
+        .query_async::<_, ()>(con)
+        .await?;
+    redis::cmd("EXPIRE")
+        .arg(&postings_id)
+        .arg(ttl)
+        .query_async::<_, ()>(con)
+        .await?;
     Ok(())
 }
 
 /// Add new squad data to the Redis data store:
 /// HASH squad:msg_id
 ///     field members: key of Set which contains member ids
+///     field owner: Discord user id of the squad's creator
 ///     field capacity: full size of squad
-///     field filled: 0 or 1, whether or not the squad has been filled and notified
-///     expires in SQUAD_TTL seconds
-pub fn build_squad(
-    con: &mut redis::Connection,
-    squad_id: &String,
+///     field filled: 0 while forming, 1 once filled (or closed) and notified, or 2 if
+///         cancelled by the owner
+///     field start: unix timestamp at which a scheduled squad starts (if scheduled)
+///     field composition: role slots such as tank:1 healer:1 dps:3 (if any)
+///     field quorum: members at which the squad is ready while staying open (if any)
+///     field ready: 1 once the squad reached its quorum and its members were notified
+///     field links: who may link postings to the squad, open, approval or disabled
+///         (open if unset)
+///     expires in ttl seconds
+/// The squad is also added to the squads index. Scheduled squads get a
+/// KEY start:squad_id
+///     expires at the start time, so its keyspace notification signals the start
+async fn build_squad(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+    owner_id: &str,
     capacity: u8,
+    start: Option<i64>,
+    composition: &Composition,
+    ttl: u64,
 ) -> redis::RedisResult<()> {
-    let members_id = members_id(&squad_id);
+    let members_id = members_id(squad_id);
     redis::cmd("HSET")
-        .arg(&squad_id)
+        .arg(squad_id)
         .arg("members")
         .arg(members_id)
-        .query(con)?;
+        .query_async::<_, ()>(con)
+        .await?;
     redis::cmd("HSET")
-        .arg(&squad_id)
+        .arg(squad_id)
+        .arg("owner")
+        .arg(owner_id)
+        .query_async::<_, ()>(con)
+        .await?;
+    redis::cmd("HSET")
+        .arg(squad_id)
         .arg("capacity")
         .arg(capacity)
-        .query(con)?;
+        .query_async::<_, ()>(con)
+        .await?;
     redis::cmd("HSET")
-        .arg(&squad_id)
+        .arg(squad_id)
         .arg("filled")
         .arg(0)
-        .query(con)?;
+        .query_async::<_, ()>(con)
+        .await?;
+    if !composition.is_empty() {
+        redis::cmd("HSET")
+            .arg(squad_id)
+            .arg("composition")
+            .arg(format_composition(composition))
+            .query_async::<_, ()>(con)
+            .await?;
+    }
+    if let Some(start) = start {
+        redis::cmd("HSET")
+            .arg(squad_id)
+            .arg("start")
+            .arg(start)
+            .query_async::<_, ()>(con)
+            .await?;
+        let until_start = start - Utc::now().timestamp();
+        if until_start > 0 {
+            redis::cmd("SET")
+                .arg(start_id(squad_id))
+                .arg(start)
+                .arg("EX")
+                .arg(until_start)
+                .query_async::<_, ()>(con)
+                .await?;
+        }
+    }
     redis::cmd("EXPIRE")
-        .arg(&squad_id)
-        .arg(SQUAD_TTL)
-        .query(con)?;
+        .arg(squad_id)
+        .arg(ttl)
+        .query_async::<_, ()>(con)
+        .await?;
+    redis::cmd("ZADD")
+        .arg(SQUADS_INDEX)
+        .arg(expires_at(ttl))
+        .arg(squad_id)
+        .query_async::<_, ()>(con)
+        .await?;
     Ok(())
 }
 
-/// Adds a new member to the corresponding squad in Redis.
+/// Lua prelude shared by the scripts below. Scripts may only touch keys passed in
+/// KEYS, so keys named after the contents of a set or list, such as member keys and
+/// postings, are read ahead and passed after the keys listed for each script. If any
+/// of them wasn't passed the squad changed since they were read, and the script
+/// replies with a STALE error before writing anything so that it can be retried.
+const DECLARED_KEYS: &str = r"
+local declared = {}
+for _, key in ipairs(KEYS) do
+    declared[key] = true
+end
+local function undeclared(keys)
+    for _, key in ipairs(keys) do
+        if not declared[key] then
+            return true
+        end
+    end
+    return false
+end
+local function stale()
+    return redis.error_reply('STALE keys changed since they were read')
+end
+";
+
+/// Lua prelude shared by the scripts below. Removes members whose member key has
+/// expired from a members set so that SCARD reflects the live squad. Returns false
+/// without removing anything if a member key wasn't passed in KEYS.
+const PRUNE_MEMBERS: &str = r"
+local function prune_members(members)
+    local member_keys = redis.call('SMEMBERS', members)
+    if undeclared(member_keys) then
+        return false
+    end
+    for _, member in ipairs(member_keys) do
+        if redis.call('EXISTS', member) == 0 then
+            redis.call('SREM', members, member)
+        end
+    end
+    return true
+end
+";
+
+/// Lua prelude shared by the scripts below. Finds the 0-based position of a user in
+/// a waitlist, whose entries have the form user_id:seconds.
+const WAITLIST_INDEX: &str = r"
+local function waitlist_index(key, user)
+    for index, entry in ipairs(redis.call('LRANGE', key, 0, -1)) do
+        if string.match(entry, '^(%d+):') == user then
+            return index - 1
+        end
+    end
+    return nil
+end
+";
+
+/// Lua prelude shared by the scripts below. Reads the seats of each role slot from
+/// the composition of a squad, and counts the members of a members set in each slot
+/// according to a slots hash.
+const SLOT_SEATS: &str = r"
+local function slot_seats(squad)
+    local seats = {}
+    local composition = redis.call('HGET', squad, 'composition')
+    if composition then
+        for name, count in string.gmatch(composition, '([^%s:]+):(%d+)') do
+            seats[name] = tonumber(count)
+        end
+    end
+    return seats, composition ~= false
+end
+
+local function slot_counts(members, slots)
+    local counts = {}
+    for _, member in ipairs(redis.call('SMEMBERS', members)) do
+        local slot = redis.call('HGET', slots, string.match(member, '(%d+)$'))
+        if slot then
+            counts[slot] = (counts[slot] or 0) + 1
+        end
+    end
+    return counts
+end
+
+local function has_room(squad, members, slots, user)
+    local capacity = tonumber(redis.call('HGET', squad, 'capacity'))
+    if redis.call('SCARD', members) >= capacity then
+        return false
+    end
+    local seats, has_slots = slot_seats(squad)
+    if not has_slots then
+        return true
+    end
+    local slot = redis.call('HGET', slots, user)
+    return slot and (slot_counts(members, slots)[slot] or 0) < (seats[slot] or 0)
+end
+";
+
+/// KEYS[1] squad, KEYS[2] members set, KEYS[3] member key, KEYS[4] waitlist,
+/// KEYS[5] slots hash, KEYS[6] squads index of the user, followed by the member keys
+/// ARGV[1] user id, ARGV[2] seconds until the member expires, ARGV[3] current unix
+/// timestamp
+const JOIN_SCRIPT: &str = r"
+if not prune_members(KEYS[2]) then
+    return stale()
+end
+local function index_squad()
+    local ttl = redis.call('TTL', KEYS[1])
+    redis.call('ZADD', KEYS[6], tonumber(ARGV[3]) + ttl, KEYS[1])
+    if redis.call('TTL', KEYS[6]) < ttl then
+        redis.call('EXPIRE', KEYS[6], ttl)
+    end
+end
+if redis.call('EXISTS', KEYS[1]) == 0 or redis.call('HGET', KEYS[1], 'filled') ~= '0' then
+    return 'closed'
+end
+local _, has_slots = slot_seats(KEYS[1])
+if has_slots and not redis.call('HGET', KEYS[5], ARGV[1]) then
+    return 'no_slot'
+end
+index_squad()
+if redis.call('SISMEMBER', KEYS[2], KEYS[3]) == 1 then
+    redis.call('SET', KEYS[3], ARGV[1], 'EX', ARGV[2])
+    return 'already_member'
+end
+local entry = ARGV[1] .. ':' .. ARGV[2]
+local index = waitlist_index(KEYS[4], ARGV[1])
+if not has_room(KEYS[1], KEYS[2], KEYS[5], ARGV[1]) then
+    if index then
+        redis.call('LSET', KEYS[4], index, entry)
+    else
+        redis.call('RPUSH', KEYS[4], entry)
+        redis.call('EXPIRE', KEYS[4], redis.call('TTL', KEYS[1]))
+    end
+    return 'waitlisted'
+end
+if index then
+    redis.call('LREM', KEYS[4], 1, redis.call('LINDEX', KEYS[4], index))
+end
+redis.call('SADD', KEYS[2], KEYS[3])
+if redis.call('TTL', KEYS[2]) < 0 then
+    redis.call('EXPIRE', KEYS[2], redis.call('TTL', KEYS[1]))
+end
+redis.call('SET', KEYS[3], ARGV[1], 'EX', ARGV[2])
+return 'joined'
+";
+
+/// KEYS[1] members set, KEYS[2] member key, KEYS[3] waitlist, KEYS[4] squads index of
+/// the user
+/// ARGV[1] user id, ARGV[2] squad id
+const LEAVE_SCRIPT: &str = r"
+redis.call('SREM', KEYS[1], KEYS[2])
+redis.call('DEL', KEYS[2])
+redis.call('ZREM', KEYS[4], ARGV[2])
+local index = waitlist_index(KEYS[3], ARGV[1])
+if index then
+    redis.call('LREM', KEYS[3], 1, redis.call('LINDEX', KEYS[3], index))
+end
+";
+
+/// KEYS[1] squad, KEYS[2] members set, KEYS[3] waitlist, KEYS[4] slots hash,
+/// followed by the member keys and the member key each waiting user would get
+const PROMOTE_SCRIPT: &str = r"
+local waiting = {}
+local waiting_keys = {}
+for _, entry in ipairs(redis.call('LRANGE', KEYS[3], 0, -1)) do
+    local user, seconds = string.match(entry, '^(%d+):(%d+)$')
+    local member = 'member:' .. KEYS[1] .. ':' .. user
+    table.insert(waiting, {entry, user, seconds, member})
+    table.insert(waiting_keys, member)
+end
+if undeclared(waiting_keys) or not prune_members(KEYS[2]) then
+    return stale()
+end
+if redis.call('EXISTS', KEYS[1]) == 0 or redis.call('HGET', KEYS[1], 'filled') ~= '0' then
+    return {}
+end
+local promoted = {}
+for _, waiting_user in ipairs(waiting) do
+    local entry, user, seconds, member = unpack(waiting_user)
+    if has_room(KEYS[1], KEYS[2], KEYS[4], user) then
+        redis.call('LREM', KEYS[3], 1, entry)
+        redis.call('SADD', KEYS[2], member)
+        if redis.call('TTL', KEYS[2]) < 0 then
+            redis.call('EXPIRE', KEYS[2], redis.call('TTL', KEYS[1]))
+        end
+        redis.call('SET', member, user, 'EX', seconds)
+        table.insert(promoted, user)
+    end
+end
+return promoted
+";
+
+/// KEYS[1] squad, KEYS[2] members set, KEYS[3] member key, KEYS[4] slots hash
+/// ARGV[1] user id, ARGV[2] slot
+const CHOOSE_SLOT_SCRIPT: &str = r"
+if redis.call('EXISTS', KEYS[1]) == 0 or redis.call('HGET', KEYS[1], 'filled') ~= '0' then
+    return 0
+end
+local seats = slot_seats(KEYS[1])
+if not seats[ARGV[2]] then
+    return 0
+end
+local moving = redis.call('SISMEMBER', KEYS[2], KEYS[3]) == 1
+    and redis.call('HGET', KEYS[4], ARGV[1]) ~= ARGV[2]
+if moving and (slot_counts(KEYS[2], KEYS[4])[ARGV[2]] or 0) >= seats[ARGV[2]] then
+    return 0
+end
+redis.call('HSET', KEYS[4], ARGV[1], ARGV[2])
+if redis.call('TTL', KEYS[4]) < 0 then
+    redis.call('EXPIRE', KEYS[4], redis.call('TTL', KEYS[1]))
+end
+return 1
+";
+
+/// KEYS[1] squad, KEYS[2] members set, followed by the member keys
+/// ARGV[1] current unix timestamp
+const FILL_SCRIPT: &str = r"
+if not prune_members(KEYS[2]) then
+    return stale()
+end
+if redis.call('EXISTS', KEYS[1]) == 0 or redis.call('HGET', KEYS[1], 'filled') ~= '0' then
+    return 0
+end
+local start = redis.call('HGET', KEYS[1], 'start')
+if start and tonumber(start) > tonumber(ARGV[1]) then
+    return 0
+end
+local capacity = tonumber(redis.call('HGET', KEYS[1], 'capacity'))
+if redis.call('SCARD', KEYS[2]) < capacity then
+    return 0
+end
+redis.call('HSET', KEYS[1], 'filled', 1)
+return 1
+";
+
+/// KEYS[1] squad, KEYS[2] members set, followed by the member keys
+/// ARGV[1] current unix timestamp
+const READY_SCRIPT: &str = r"
+if not prune_members(KEYS[2]) then
+    return stale()
+end
+if redis.call('EXISTS', KEYS[1]) == 0 or redis.call('HGET', KEYS[1], 'filled') ~= '0' then
+    return 0
+end
+local quorum = redis.call('HGET', KEYS[1], 'quorum')
+if not quorum or redis.call('HGET', KEYS[1], 'ready') == '1' then
+    return 0
+end
+local start = redis.call('HGET', KEYS[1], 'start')
+if start and tonumber(start) > tonumber(ARGV[1]) then
+    return 0
+end
+if redis.call('SCARD', KEYS[2]) < tonumber(quorum) then
+    return 0
+end
+redis.call('HSET', KEYS[1], 'ready', 1)
+return 1
+";
+
+/// KEYS[1] squad, KEYS[2] members set, followed by the member keys
+const CLOSE_SCRIPT: &str = r"
+if not prune_members(KEYS[2]) then
+    return stale()
+end
+if redis.call('EXISTS', KEYS[1]) == 0 or redis.call('HGET', KEYS[1], 'filled') ~= '0' then
+    return 0
+end
+if redis.call('SCARD', KEYS[2]) == 0 then
+    return 0
+end
+redis.call('HSET', KEYS[1], 'filled', 1)
+return 1
+";
+
+/// KEYS[1] squad
+const CANCEL_SCRIPT: &str = r"
+if redis.call('EXISTS', KEYS[1]) == 0 or redis.call('HGET', KEYS[1], 'filled') ~= '0' then
+    return 0
+end
+redis.call('HSET', KEYS[1], 'filled', 2)
+return 1
+";
+
+/// KEYS[1] squad, KEYS[2] members set, KEYS[3] waitlist, KEYS[4] slots hash,
+/// KEYS[5] postings set, KEYS[6] channels set, KEYS[7] squads index, KEYS[8] postings index,
+/// KEYS[9] origins hash
+/// followed by the squads index of each member and waiting user, each posting and the
+/// postings index of the guild of each posting
+/// ARGV[1] seconds to extend by, ARGV[2] current unix timestamp
+/// The postings index of each guild and the squads index of each member and waiting
+/// user are keyed after KEYS[8] and KEYS[7].
+const EXTEND_SCRIPT: &str = r"
+if redis.call('EXISTS', KEYS[1]) == 0 or redis.call('HGET', KEYS[1], 'filled') ~= '0' then
+    return 0
+end
+local user_indexes = {}
+for _, member in ipairs(redis.call('SMEMBERS', KEYS[2])) do
+    table.insert(user_indexes, KEYS[7] .. ':' .. string.match(member, '(%d+)$'))
+end
+for _, entry in ipairs(redis.call('LRANGE', KEYS[3], 0, -1)) do
+    table.insert(user_indexes, KEYS[7] .. ':' .. string.match(entry, '^(%d+):'))
+end
+local postings = {}
+for _, message in ipairs(redis.call('SMEMBERS', KEYS[5])) do
+    table.insert(postings, 'posting:' .. message)
+end
+if undeclared(user_indexes) or undeclared(postings) then
+    return stale()
+end
+local guild_indexes = {}
+for _, posting in ipairs(postings) do
+    local guild = redis.call('HGET', posting, 'guild')
+    if guild then
+        guild_indexes[posting] = KEYS[8] .. ':' .. guild
+        if undeclared({guild_indexes[posting]}) then
+            return stale()
+        end
+    end
+end
+local seconds = tonumber(ARGV[1])
+local now = tonumber(ARGV[2])
+for _, index in ipairs({1, 2, 3, 4, 5, 6, 9}) do
+    local ttl = redis.call('TTL', KEYS[index])
+    if ttl > 0 then
+        redis.call('EXPIRE', KEYS[index], ttl + seconds)
+    end
+end
+local function extend_index(index, expires, member)
+    redis.call('ZADD', index, 'XX', expires, member)
+    if redis.call('TTL', index) < expires - now then
+        redis.call('EXPIRE', index, expires - now)
+    end
+end
+local expires = now + redis.call('TTL', KEYS[1])
+redis.call('ZADD', KEYS[7], expires, KEYS[1])
+for _, user_index in ipairs(user_indexes) do
+    extend_index(user_index, expires, KEYS[1])
+end
+for _, posting in ipairs(postings) do
+    local ttl = redis.call('TTL', posting)
+    if ttl > 0 then
+        redis.call('EXPIRE', posting, ttl + seconds)
+        redis.call('ZADD', KEYS[8], now + ttl + seconds, posting)
+        if guild_indexes[posting] then
+            extend_index(guild_indexes[posting], now + ttl + seconds, posting)
+        end
+    end
+end
+return 1
+";
+
+/// KEYS[1] squad, KEYS[2] origins hash
+/// ARGV[1] user id, ARGV[2] channel id of the posting the user joined through
+const ORIGIN_SCRIPT: &str = r"
+local ttl = redis.call('TTL', KEYS[1])
+if ttl <= 0 then
+    return 0
+end
+redis.call('HSET', KEYS[2], ARGV[1], ARGV[2])
+redis.call('EXPIRE', KEYS[2], ttl)
+return 1
+";
+
+/// KEYS[1] squad
+/// ARGV[1] field, ARGV[2] value
+const FIELD_SCRIPT: &str = r"
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    return 0
+end
+redis.call('HSET', KEYS[1], ARGV[1], ARGV[2])
+return 1
+";
+
+/// KEYS[1] posting, KEYS[2] postings set, KEYS[3] channels set, KEYS[4] postings index,
+/// followed by each posting of the squad and the postings index of the guild of the
+/// posting
+/// ARGV[1] message id of the posting
+const UNLINK_SCRIPT: &str = r"
+local channel = redis.call('HGET', KEYS[1], 'channel')
+if not channel then
+    return 0
+end
+local postings = {}
+for _, message in ipairs(redis.call('SMEMBERS', KEYS[2])) do
+    if message ~= ARGV[1] then
+        table.insert(postings, 'posting:' .. message)
+    end
+end
+local guild = redis.call('HGET', KEYS[1], 'guild')
+local guild_index = guild and KEYS[4] .. ':' .. guild
+if undeclared(postings) or (guild_index and undeclared({guild_index})) then
+    return stale()
+end
+if guild_index then
+    redis.call('ZREM', guild_index, KEYS[1])
+end
+redis.call('DEL', KEYS[1])
+redis.call('SREM', KEYS[2], ARGV[1])
+redis.call('ZREM', KEYS[4], KEYS[1])
+for _, posting in ipairs(postings) do
+    if redis.call('HGET', posting, 'channel') == channel then
+        return 1
+    end
+end
+redis.call('SREM', KEYS[3], channel)
+return 1
+";
+
+/// Squad scripts with their preludes, hashed once and loaded into Redis on first use.
+static JOIN: LazyLock<redis::Script> = LazyLock::new(|| {
+    redis::Script::new(
+        &[
+            DECLARED_KEYS,
+            PRUNE_MEMBERS,
+            WAITLIST_INDEX,
+            SLOT_SEATS,
+            JOIN_SCRIPT,
+        ]
+        .concat(),
+    )
+});
+static LEAVE: LazyLock<redis::Script> =
+    LazyLock::new(|| redis::Script::new(&[WAITLIST_INDEX, LEAVE_SCRIPT].concat()));
+static PROMOTE: LazyLock<redis::Script> = LazyLock::new(|| {
+    redis::Script::new(&[DECLARED_KEYS, PRUNE_MEMBERS, SLOT_SEATS, PROMOTE_SCRIPT].concat())
+});
+static CHOOSE_SLOT: LazyLock<redis::Script> =
+    LazyLock::new(|| redis::Script::new(&[SLOT_SEATS, CHOOSE_SLOT_SCRIPT].concat()));
+static FILL: LazyLock<redis::Script> =
+    LazyLock::new(|| redis::Script::new(&[DECLARED_KEYS, PRUNE_MEMBERS, FILL_SCRIPT].concat()));
+static READY: LazyLock<redis::Script> =
+    LazyLock::new(|| redis::Script::new(&[DECLARED_KEYS, PRUNE_MEMBERS, READY_SCRIPT].concat()));
+static CLOSE: LazyLock<redis::Script> =
+    LazyLock::new(|| redis::Script::new(&[DECLARED_KEYS, PRUNE_MEMBERS, CLOSE_SCRIPT].concat()));
+static CANCEL: LazyLock<redis::Script> = LazyLock::new(|| redis::Script::new(CANCEL_SCRIPT));
+static EXTEND: LazyLock<redis::Script> =
+    LazyLock::new(|| redis::Script::new(&[DECLARED_KEYS, EXTEND_SCRIPT].concat()));
+static ORIGIN: LazyLock<redis::Script> = LazyLock::new(|| redis::Script::new(ORIGIN_SCRIPT));
+static FIELD: LazyLock<redis::Script> = LazyLock::new(|| redis::Script::new(FIELD_SCRIPT));
+static UNLINK: LazyLock<redis::Script> =
+    LazyLock::new(|| redis::Script::new(&[DECLARED_KEYS, UNLINK_SCRIPT].concat()));
+
+/// Number of times a script is run before giving up when the keys it touches keep
+/// changing between reading them and running it.
+const SCRIPT_ATTEMPTS: u8 = 5;
+
+/// Keys named after the contents of a set or list that a script touches, which are
+/// read ahead of running it and passed after its own keys.
+#[derive(Clone, Copy)]
+enum ScriptKeys<'a> {
+    /// The member keys of a squad, see member_keys
+    Members(&'a str),
+    /// The member keys of a squad and of its waiting users, see waiting_member_keys
+    Waiting(&'a str),
+    /// The keys extending a squad touches, see extend_keys
+    Extend(&'a str),
+    /// The keys removing a posting of a squad touches, see unlink_keys
+    Unlink(&'a str, &'a str),
+}
+
+/// Invoke a script that touches keys named after the contents of a set or list. The
+/// keys are read and passed after the keys of the invocation, and read again to retry
+/// the script if it replies that they changed in the meantime.
+async fn invoke_with_keys<T: redis::FromRedisValue>(
+    con: &mut ConnectionManager,
+    script_keys: ScriptKeys<'_>,
+    invocation: impl Fn() -> redis::ScriptInvocation<'static>,
+) -> redis::RedisResult<T> {
+    let mut attempt = 1;
+    loop {
+        let keys = match script_keys {
+            ScriptKeys::Members(squad_id) => member_keys(con, squad_id).await?,
+            ScriptKeys::Waiting(squad_id) => waiting_member_keys(con, squad_id).await?,
+            ScriptKeys::Extend(squad_id) => extend_keys(con, squad_id).await?,
+            ScriptKeys::Unlink(squad_id, message_id) => {
+                unlink_keys(con, squad_id, message_id).await?
+            }
+        };
+        let result = invocation().key(keys).invoke_async(con).await;
+        match result {
+            Err(why) if why.code() == Some("STALE") && attempt < SCRIPT_ATTEMPTS => attempt += 1,
+            result => return result,
+        }
+    }
+}
+
+/// Get the member keys in the members set of a given squad id
+async fn member_keys(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+) -> redis::RedisResult<Vec<String>> {
+    redis::cmd("SMEMBERS")
+        .arg(members_id(squad_id))
+        .query_async(con)
+        .await
+}
+
+/// Get the member keys of a given squad id along with the member keys its waiting
+/// users would get when promoted
+async fn waiting_member_keys(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+) -> redis::RedisResult<Vec<String>> {
+    let mut keys = member_keys(con, squad_id).await?;
+    for user_id in get_waitlist(con, squad_id).await? {
+        keys.push(member_id(squad_id, &user_id.as_u64().to_string()));
+    }
+    Ok(keys)
+}
+
+/// Get the keys named after the members, waiting users and postings of a given squad
+/// id that extending it touches: the squads index of each user, each posting and the
+/// postings index of the guild of each posting
+async fn extend_keys(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+) -> redis::RedisResult<Vec<String>> {
+    let mut keys = Vec::new();
+    for member in member_keys(con, squad_id).await? {
+        if let Some(user_id) = member.rsplit(':').next() {
+            keys.push(user_squads_index(user_id));
+        }
+    }
+    for user_id in get_waitlist(con, squad_id).await? {
+        keys.push(user_squads_index(&user_id.as_u64().to_string()));
+    }
+    let message_ids: Vec<String> = redis::cmd("SMEMBERS")
+        .arg(postings_id(squad_id))
+        .query_async(con)
+        .await?;
+    for message_id in message_ids {
+        let posting = posting_id(&message_id);
+        let guild: Option<u64> = redis::cmd("HGET")
+            .arg(&posting)
+            .arg("guild")
+            .query_async(con)
+            .await?;
+        keys.push(posting);
+        keys.extend(guild.map(|guild| guild_postings_index(GuildId(guild))));
+    }
+    Ok(keys)
+}
+
+/// Get the keys named after the postings of a given squad id that removing one of its
+/// postings touches: the other postings and the postings index of the guild of the
+/// removed posting
+async fn unlink_keys(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+    message_id: &str,
+) -> redis::RedisResult<Vec<String>> {
+    let message_ids: Vec<String> = redis::cmd("SMEMBERS")
+        .arg(postings_id(squad_id))
+        .query_async(con)
+        .await?;
+    let mut keys: Vec<String> = message_ids
+        .iter()
+        .filter(|id| *id != message_id)
+        .map(|id| posting_id(id))
+        .collect();
+    let guild: Option<u64> = redis::cmd("HGET")
+        .arg(posting_id(message_id))
+        .arg("guild")
+        .query_async(con)
+        .await?;
+    keys.extend(guild.map(|guild| guild_postings_index(GuildId(guild))));
+    Ok(keys)
+}
+
+/// Adds a new member to the corresponding squad in Redis as a single atomic script.
 /// Creates or appends to ->
 /// SET members:msg_id
 ///     contains member ids of the squad in the form member:msg_id:user_id
-///     expires in POSTING_TTL seconds
+///     expires along with the squad
 /// Creates ->
 /// KEY member:msg_id:user_id
 ///     contains Discord user id of squad member
 ///     expires in <hours * 60 * 60> seconds where hours is chosen from the posting
-pub fn add_member(
-    con: &mut redis::Connection,
-    squad_id: &String,
-    user_id: &String,
+/// If the squad is full, the user is queued instead ->
+/// LIST waitlist:msg_id
+///     contains entries of the form user_id:seconds, first in line first
+///     expires along with the squad
+/// Squads with role slots only have room for a user if the slot they picked does.
+/// Members and waiting users have the squad added to their squads index.
+/// Nothing is written if the squad is no longer forming or the user hasn't picked a
+/// slot of a squad with role slots.
+async fn add_member(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+    user_id: &str,
     expires: u32,
+) -> redis::RedisResult<JoinResult> {
+    let result: String = invoke_with_keys(con, ScriptKeys::Members(squad_id), || {
+        let mut invocation = JOIN.key(squad_id);
+        invocation
+            .key(members_id(squad_id))
+            .key(member_id(squad_id, user_id))
+            .key(waitlist_id(squad_id))
+            .key(slots_id(squad_id))
+            .key(user_squads_index(user_id))
+            .arg(user_id)
+            .arg(expires)
+            .arg(Utc::now().timestamp());
+        invocation
+    })
+    .await?;
+    match result.as_str() {
+        "joined" => Ok(JoinResult::Joined),
+        "already_member" => Ok(JoinResult::AlreadyMember),
+        "waitlisted" => Ok(JoinResult::Waitlisted),
+        "no_slot" => Ok(JoinResult::NoSlot),
+        _ => Ok(JoinResult::Closed),
+    }
+}
+
+/// Deletes a give user from the squad data by removing them from the members Set, the
+/// waitlist and their squads index and deleting the member:msg_id:user_id key-value
+/// pair in a single atomic script.
+async fn delete_member(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+    user_id: &str,
 ) -> redis::RedisResult<()> {
-    let members_id = members_id(&squad_id);
-    let member_id = member_id(&squad_id, &user_id);
-    let squad_status = get_squad_status(con, &squad_id).unwrap();
-    match squad_status {
-        SquadStatus::Forming => {
-            let member_count: u8 = redis::cmd("SCARD").arg(&members_id).query(con).unwrap();
-            if member_count == 0 {
-                let ttl: u32 = redis::cmd("TTL").arg(&squad_id).query(con).unwrap();
-                redis::cmd("SADD")
-                    .arg(&members_id)
-                    .arg(&member_id)
-                    .query(con)?;
-                redis::cmd("EXPIRE").arg(&members_id).arg(ttl).query(con)?;
-            } else {
-                let capacity: u8 = get_capacity(con, &squad_id).unwrap();
-                if member_count < capacity {
-                    redis::cmd("SADD")
-                        .arg(&members_id)
-                        .arg(&member_id)
-                        .query(con)?;
-                }
-            }
-            redis::cmd("SET")
-                .arg(member_id)
-                .arg(user_id)
-                .arg("EX")
-                .arg(expires)
-                .query(con)?;
-        }
-        _ => {}
+    LEAVE
+        .key(members_id(squad_id))
+        .key(member_id(squad_id, user_id))
+        .key(waitlist_id(squad_id))
+        .key(user_squads_index(user_id))
+        .arg(user_id)
+        .arg(squad_id)
+        .invoke_async::<_, ()>(con)
+        .await
+}
+
+/// Moves users from the front of the waitlist into the free spots of a forming squad
+/// in a single atomic script, after removing expired members. Users waiting for a slot
+/// without a free seat keep their place in line. Each promoted user gets a
+/// member:msg_id:user_id key expiring in the seconds they chose when they queued.
+async fn promote_members(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+) -> redis::RedisResult<Vec<UserId>> {
+    let promoted: Vec<u64> = invoke_with_keys(con, ScriptKeys::Waiting(squad_id), || {
+        let mut invocation = PROMOTE.key(squad_id);
+        invocation
+            .key(members_id(squad_id))
+            .key(waitlist_id(squad_id))
+            .key(slots_id(squad_id));
+        invocation
+    })
+    .await?;
+    Ok(promoted.into_iter().map(UserId::from).collect())
+}
+
+/// Records the slot a user picked in a single atomic script ->
+/// HASH slots:msg_id
+///     field user_id: name of the slot the user picked
+///     expires along with the squad
+/// Members can only move to a slot with a free seat.
+async fn choose_slot(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+    user_id: &str,
+    slot: &str,
+) -> redis::RedisResult<bool> {
+    CHOOSE_SLOT
+        .key(squad_id)
+        .key(members_id(squad_id))
+        .key(member_id(squad_id, user_id))
+        .key(slots_id(squad_id))
+        .arg(user_id)
+        .arg(slot)
+        .invoke_async(con)
+        .await
+}
+
+/// Get the role slots of a given squad id, empty if it has none
+async fn get_composition(con: &mut ConnectionManager, squad_id: &str) -> StoreResult<Composition> {
+    let composition: Option<String> = redis::cmd("HGET")
+        .arg(squad_id)
+        .arg("composition")
+        .query_async(con)
+        .await?;
+    match composition {
+        Some(composition) => parse_composition(&composition),
+        None => Ok(Vec::new()),
     }
+}
 
-    Ok(())
+/// Get the slot picked by each user of a given squad id
+async fn get_slots(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+) -> redis::RedisResult<HashMap<UserId, String>> {
+    let slots: HashMap<u64, String> = redis::cmd("HGETALL")
+        .arg(slots_id(squad_id))
+        .query_async(con)
+        .await?;
+    Ok(slots
+        .into_iter()
+        .map(|(user_id, slot)| (UserId(user_id), slot))
+        .collect())
 }
 
-/// Deletes a give user from the squad data by removing them from the members Set and
-/// deleting the member:msg_id:user_id key-value pair.
-pub fn delete_member(
-    con: &mut redis::Connection,
-    squad_id: &String,
-    user_id: &String,
+/// Record the channel of the posting through which a user joined a squad, in a Hash
+/// that expires along with the squad, as a single atomic script
+/// HASH origins:squad_id
+///     field user id: id of the channel of the posting the user joined through
+async fn set_origin(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+    user_id: &str,
+    channel_id: ChannelId,
 ) -> redis::RedisResult<()> {
-    let members_id = redis::cmd("HGET")
-        .arg(&squad_id)
-        .arg("members")
-        .query::<String>(con)?;
-    let member_id = member_id(&squad_id, &user_id);
-    redis::cmd("SREM")
-        .arg(&members_id)
-        .arg(&member_id)
-        .query(con)?;
-    redis::cmd("DEL").arg(&member_id).query(con)?;
+    ORIGIN
+        .key(squad_id)
+        .key(origins_id(squad_id))
+        .arg(user_id)
+        .arg(channel_id.as_u64())
+        .invoke_async::<_, ()>(con)
+        .await
+}
 
-    Ok(())
+/// Set a field of a given squad id, leaving squads that are gone unset rather than
+/// leaving behind a hash that never expires
+async fn set_squad_field(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+    field: &str,
+    value: &str,
+) -> redis::RedisResult<()> {
+    FIELD
+        .key(squad_id)
+        .arg(field)
+        .arg(value)
+        .invoke_async::<_, ()>(con)
+        .await
 }
 
-/// Get the capacity from a given squad id
-pub fn get_capacity(con: &mut redis::Connection, squad_id: &String) -> redis::RedisResult<u8> {
-    redis::cmd("HGET").arg(&squad_id).arg("capacity").query(con)
+/// Get the channel of the posting through which each user joined a given squad id
+async fn get_origins(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+) -> redis::RedisResult<HashMap<UserId, ChannelId>> {
+    let origins: HashMap<u64, u64> = redis::cmd("HGETALL")
+        .arg(origins_id(squad_id))
+        .query_async(con)
+        .await?;
+    Ok(origins
+        .into_iter()
+        .map(|(user_id, channel_id)| (UserId(user_id), ChannelId(channel_id)))
+        .collect())
+}
+
+/// Get the users on the waitlist of a given squad id, first in line first
+async fn get_waitlist(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+) -> redis::RedisResult<Vec<UserId>> {
+    let entries: Vec<String> = redis::cmd("LRANGE")
+        .arg(waitlist_id(squad_id))
+        .arg(0)
+        .arg(-1)
+        .query_async(con)
+        .await?;
+    let waitlist = entries
+        .iter()
+        .filter_map(|entry| entry.split(':').next()?.parse::<u64>().ok())
+        .map(UserId::from)
+        .collect();
+    Ok(waitlist)
+}
+
+/// Get the capacity from a given squad id, None if the squad doesn't exist
+async fn get_capacity(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+) -> redis::RedisResult<Option<u8>> {
+    redis::cmd("HGET")
+        .arg(squad_id)
+        .arg("capacity")
+        .query_async(con)
+        .await
 }
 
 /// Get the members and corresponding expiry times in seconds of a given squad id
 /// Also realizes any expired members and deletes them from the members set
-pub fn get_members(
-    con: &mut redis::Connection,
-    squad_id: &String,
+async fn get_members(
+    con: &mut ConnectionManager,
+    squad_id: &str,
 ) -> redis::RedisResult<HashMap<UserId, u64>> {
     // Check if reference to members id set exists within squad data
     let members_id_field_exists = redis::cmd("HEXISTS")
-        .arg(&squad_id)
+        .arg(squad_id)
         .arg("members")
-        .query::<u8>(con)?;
+        .query_async::<_, u8>(con)
+        .await?;
     // If it does, get the set key, else early return an empty hashmap
     let members_id = match members_id_field_exists {
-        1 => redis::cmd("HGET")
-            .arg(&squad_id)
-            .arg("members")
-            .query::<String>(con)?,
+        1 => {
+            redis::cmd("HGET")
+                .arg(squad_id)
+                .arg("members")
+                .query_async::<_, String>(con)
+                .await?
+        }
         _ => return Ok(HashMap::new()),
     };
     // Check if the members set is populated
-    let members_id_populated = redis::cmd("SCARD").arg(&members_id).query::<u8>(con)?;
+    let members_id_populated = redis::cmd("SCARD")
+        .arg(&members_id)
+        .query_async::<_, u8>(con)
+        .await?;
     // If it doesn't, early return an empty hashmap, else collect member ids
     let redis_members: Vec<String> = match members_id_populated {
         0 => return Ok(HashMap::new()),
-        _ => redis::cmd("SMEMBERS")
-            .arg(&members_id)
-            .clone()
-            .iter::<String>(con)?
-            .collect(),
+        _ => {
+            redis::cmd("SMEMBERS")
+                .arg(&members_id)
+                .query_async(con)
+                .await?
+        }
     };
     // Create hashmap of user ids and corresponding ttls
     let mut members = HashMap::new();
     for member in redis_members {
-        let exists = redis::cmd("EXISTS").arg(&member).query::<u8>(con)?;
-        if exists == 1 {
-            let user_id: UserId = redis::cmd("GET").arg(&member).query::<u64>(con)?.into();
-            let ttl: u64 = redis::cmd("TTL").arg(&member).query::<u64>(con)?;
-            members.insert(user_id, ttl);
-        } else {
-            redis::cmd("SREM")
-                .arg(&members_id)
-                .arg(&member)
-                .query(con)?;
+        let user_id: Option<u64> = redis::cmd("GET").arg(&member).query_async(con).await?;
+        // The member key can expire between the two reads, which TTL reports as -2
+        let ttl: i64 = redis::cmd("TTL").arg(&member).query_async(con).await?;
+        match (user_id, u64::try_from(ttl)) {
+            (Some(user_id), Ok(ttl)) => {
+                members.insert(UserId(user_id), ttl);
+            }
+            _ => {
+                redis::cmd("SREM")
+                    .arg(&members_id)
+                    .arg(&member)
+                    .query_async::<_, ()>(con)
+                    .await?;
+            }
         }
     }
     Ok(members)
 }
 
-/// Get the time-to-live in seconds of a given key
-pub fn get_ttl(con: &mut redis::Connection, key: &String) -> redis::RedisResult<u64> {
-    redis::cmd("TTL").arg(&key).query::<u64>(con)
+/// Get the time-to-live in seconds of a given key, or None if it doesn't exist or
+/// has no expiry
+async fn get_ttl(con: &mut ConnectionManager, key: &str) -> redis::RedisResult<Option<u64>> {
+    let ttl: i64 = redis::cmd("TTL").arg(key).query_async(con).await?;
+    Ok(u64::try_from(ttl).ok())
 }
 
 /// Get the channel and message ids of all current squad postings
-pub fn get_postings(
-    con: &mut redis::Connection,
+async fn get_postings(
+    con: &mut ConnectionManager,
 ) -> redis::RedisResult<HashMap<MessageId, ChannelId>> {
-    let posting_ids: Vec<String> = redis::cmd("KEYS")
-        .arg("posting:*")
-        .clone()
-        .iter::<String>(con)?
-        .collect();
+    let posting_ids = live_index_members(con, POSTINGS_INDEX).await?;
     let mut postings = HashMap::new();
     for id in posting_ids {
-        let message_id: MessageId = redis::cmd("HGET")
+        let (message_id, channel_id): (Option<u64>, Option<u64>) = redis::cmd("HMGET")
             .arg(&id)
             .arg("message")
-            .query::<u64>(con)?
-            .into();
-        let channel_id: ChannelId = redis::cmd("HGET")
-            .arg(&id)
             .arg("channel")
-            .query::<u64>(con)?
-            .into();
-        postings.insert(message_id, channel_id);
+            .query_async(con)
+            .await?;
+        match (message_id, channel_id) {
+            (Some(message_id), Some(channel_id)) => {
+                postings.insert(MessageId(message_id), ChannelId(channel_id));
+            }
+            // The posting expired since the index was pruned
+            _ => {
+                redis::cmd("ZREM")
+                    .arg(POSTINGS_INDEX)
+                    .arg(&id)
+                    .query_async::<_, ()>(con)
+                    .await?;
+            }
+        }
+    }
+    Ok(postings)
+}
+
+/// Get the ids of the squads with a live posting in a given guild
+async fn get_guild_squads(
+    con: &mut ConnectionManager,
+    guild_id: GuildId,
+) -> redis::RedisResult<Vec<String>> {
+    let index = guild_postings_index(guild_id);
+    let mut squads = Vec::new();
+    for posting_id in live_index_members(con, &index).await? {
+        let squad_id: Option<String> = redis::cmd("HGET")
+            .arg(&posting_id)
+            .arg("squad")
+            .query_async(con)
+            .await?;
+        match squad_id {
+            Some(squad_id) if !squads.contains(&squad_id) => squads.push(squad_id),
+            Some(_) => {}
+            // The posting was removed since the index was pruned
+            None => {
+                redis::cmd("ZREM")
+                    .arg(&index)
+                    .arg(&posting_id)
+                    .query_async::<_, ()>(con)
+                    .await?;
+            }
+        }
+    }
+    Ok(squads)
+}
+
+/// Get the channel and message ids of all current postings of a given squad
+async fn get_squad_postings(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+) -> redis::RedisResult<HashMap<MessageId, ChannelId>> {
+    let postings_id = postings_id(squad_id);
+    let message_ids: Vec<String> = redis::cmd("SMEMBERS")
+        .arg(&postings_id)
+        .query_async(con)
+        .await?;
+    let mut postings = HashMap::new();
+    for message_id in message_ids {
+        let channel_id: Option<u64> = redis::cmd("HGET")
+            .arg(posting_id(&message_id))
+            .arg("channel")
+            .query_async(con)
+            .await?;
+        match (message_id.parse::<u64>(), channel_id) {
+            (Ok(message), Some(channel)) => {
+                postings.insert(MessageId(message), ChannelId(channel));
+            }
+            // The posting has expired
+            _ => {
+                redis::cmd("SREM")
+                    .arg(&postings_id)
+                    .arg(&message_id)
+                    .query_async::<_, ()>(con)
+                    .await?;
+            }
+        }
     }
     Ok(postings)
 }
 
 /// Get a list of squad ids of all squads that are currently at capacity and haven't
 /// been flagged as filled and notified.
-pub fn get_full_squads(con: &mut redis::Connection) -> redis::RedisResult<Vec<String>> {
-    let squads: Vec<String> = redis::cmd("KEYS")
-        .arg("squad:*")
-        .clone()
-        .iter::<String>(con)?
-        .collect();
+async fn get_full_squads(con: &mut ConnectionManager) -> redis::RedisResult<Vec<String>> {
+    let squads = live_index_members(con, SQUADS_INDEX).await?;
     let mut full_squads = Vec::new();
     for squad in squads {
-        let members_id = redis::cmd("HGET")
+        let (members_id, capacity, filled, start): (
+            Option<String>,
+            Option<u8>,
+            Option<u8>,
+            Option<i64>,
+        ) = redis::cmd("HMGET")
             .arg(&squad)
             .arg("members")
-            .query::<String>(con)?;
-        let squad_size = redis::cmd("SCARD").arg(&members_id).query::<u8>(con)?;
-        let capacity = redis::cmd("HGET")
-            .arg(&squad)
             .arg("capacity")
-            .query::<u8>(con)?;
-        let filled = redis::cmd("HGET")
-            .arg(&squad)
             .arg("filled")
-            .query::<u8>(con)?;
-        if (squad_size >= capacity) && (filled == 0) {
-            full_squads.push(squad.clone());
+            .arg("start")
+            .query_async(con)
+            .await?;
+        let (capacity, filled) = match (members_id, capacity, filled) {
+            (Some(_), Some(capacity), Some(filled)) => (capacity, filled),
+            // The squad expired since the index was pruned
+            _ => {
+                redis::cmd("ZREM")
+                    .arg(SQUADS_INDEX)
+                    .arg(&squad)
+                    .query_async::<_, ()>(con)
+                    .await?;
+                continue;
+            }
+        };
+        let started = start.is_none_or(|start| start <= Utc::now().timestamp());
+        if filled != 0 || !started {
+            continue;
+        }
+        // Count through get_members so members whose keys expired aren't counted
+        let squad_size = get_members(con, &squad).await?.len();
+        if squad_size >= usize::from(capacity) {
+            full_squads.push(squad);
         }
     }
     Ok(full_squads)
 }
 
-/// Flag a squad as filled
-pub fn fill_squad(con: &mut redis::Connection, squad_id: &String) -> redis::RedisResult<()> {
-    redis::cmd("HSET")
-        .arg(&squad_id)
-        .arg("filled")
-        .arg(1)
-        .query(con)?;
-    Ok(())
+/// Flag a squad as filled if it is still forming, at capacity and has started (if
+/// scheduled), as a single atomic script. Returns whether the squad was flagged by
+/// this call.
+async fn fill_squad(con: &mut ConnectionManager, squad_id: &str) -> redis::RedisResult<bool> {
+    invoke_with_keys(con, ScriptKeys::Members(squad_id), || {
+        let mut invocation = FILL.key(squad_id);
+        invocation
+            .key(members_id(squad_id))
+            .arg(Utc::now().timestamp());
+        invocation
+    })
+    .await
+}
+
+/// Flag a forming squad as ready once it has as many members as its quorum, as a
+/// single atomic script. Returns whether the squad was flagged by this call.
+async fn ready_squad(con: &mut ConnectionManager, squad_id: &str) -> redis::RedisResult<bool> {
+    invoke_with_keys(con, ScriptKeys::Members(squad_id), || {
+        let mut invocation = READY.key(squad_id);
+        invocation
+            .key(members_id(squad_id))
+            .arg(Utc::now().timestamp());
+        invocation
+    })
+    .await
+}
+
+/// Flag a forming squad with at least one member as filled, whether or not it is at
+/// capacity, as a single atomic script. Returns whether the squad was flagged by this
+/// call.
+async fn close_squad(con: &mut ConnectionManager, squad_id: &str) -> redis::RedisResult<bool> {
+    invoke_with_keys(con, ScriptKeys::Members(squad_id), || {
+        let mut invocation = CLOSE.key(squad_id);
+        invocation.key(members_id(squad_id));
+        invocation
+    })
+    .await
+}
+
+/// Flag a forming squad as cancelled as a single atomic script. Returns whether the
+/// squad was flagged by this call.
+async fn cancel_squad(con: &mut ConnectionManager, squad_id: &str) -> redis::RedisResult<bool> {
+    CANCEL.key(squad_id).invoke_async::<_, bool>(con).await
+}
+
+/// Push back the expiry of a forming squad, its postings and the keys that expire
+/// along with it as a single atomic script. Returns whether the squad was extended.
+async fn extend_squad(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+    seconds: u64,
+) -> redis::RedisResult<bool> {
+    invoke_with_keys(con, ScriptKeys::Extend(squad_id), || {
+        let mut invocation = EXTEND.key(squad_id);
+        invocation
+            .key(members_id(squad_id))
+            .key(waitlist_id(squad_id))
+            .key(slots_id(squad_id))
+            .key(postings_id(squad_id))
+            .key(channels_id(squad_id))
+            .key(SQUADS_INDEX)
+            .key(POSTINGS_INDEX)
+            .key(origins_id(squad_id))
+            .arg(seconds)
+            .arg(Utc::now().timestamp());
+        invocation
+    })
+    .await
+}
+
+/// Delete a squad posting and remove it from the postings of its squad, as a single
+/// atomic script. Its channel is removed from the channels the squad was posted in
+/// unless another posting of the squad is in it. Returns whether the posting existed.
+async fn delete_posting(con: &mut ConnectionManager, message_id: &str) -> redis::RedisResult<bool> {
+    let squad_id = match get_squad_id(con, message_id).await? {
+        Some(squad_id) => squad_id,
+        None => return Ok(false),
+    };
+    invoke_with_keys(con, ScriptKeys::Unlink(&squad_id, message_id), || {
+        let mut invocation = UNLINK.key(posting_id(message_id));
+        invocation
+            .key(postings_id(&squad_id))
+            .key(channels_id(&squad_id))
+            .key(POSTINGS_INDEX)
+            .arg(message_id);
+        invocation
+    })
+    .await
+}
+
+/// Get the owner of a given squad id, if it was recorded
+async fn get_owner(
+    con: &mut ConnectionManager,
+    squad_id: &str,
+) -> redis::RedisResult<Option<UserId>> {
+    let owner: Option<u64> = redis::cmd("HGET")
+        .arg(squad_id)
+        .arg("owner")
+        .query_async(con)
+        .await?;
+    Ok(owner.map(UserId))
 }
 
 /// Read the flag indicating whether or not a squad has been filled and notified
-pub fn get_filled(con: &mut redis::Connection, squad_id: &String) -> redis::RedisResult<u8> {
+async fn get_filled(con: &mut ConnectionManager, squad_id: &str) -> redis::RedisResult<u8> {
     redis::cmd("HGET")
-        .arg(&squad_id)
+        .arg(squad_id)
         .arg("filled")
-        .query::<u8>(con)
+        .query_async::<_, u8>(con)
+        .await
 }
 
-/// Get the squad status of a given squad id: Expired, Forming, or Filled
-pub fn get_squad_status(
-    con: &mut redis::Connection,
-    squad_id: &String,
+/// Get the squad status of a given squad id: Expired, Forming, Filled or Cancelled
+async fn get_squad_status(
+    con: &mut ConnectionManager,
+    squad_id: &str,
 ) -> redis::RedisResult<SquadStatus> {
-    let exists = redis::cmd("EXISTS").arg(&squad_id).query::<u8>(con)?;
+    let exists = redis::cmd("EXISTS")
+        .arg(squad_id)
+        .query_async::<_, u8>(con)
+        .await?;
     if exists == 0 {
-        return Ok(SquadStatus::Expired);
+        Ok(SquadStatus::Expired)
     } else {
-        let filled = get_filled(con, &squad_id).unwrap();
-        if filled == 0 {
-            return Ok(SquadStatus::Forming);
-        } else {
-            return Ok(SquadStatus::Filled);
+        match get_filled(con, squad_id).await? {
+            0 => Ok(SquadStatus::Forming),
+            2 => Ok(SquadStatus::Cancelled),
+            _ => Ok(SquadStatus::Filled),
         }
     }
 }
 
+/// Get the start time of a given squad id, if it is a scheduled squad
+async fn get_start(con: &mut ConnectionManager, squad_id: &str) -> redis::RedisResult<Option<i64>> {
+    redis::cmd("HGET")
+        .arg(squad_id)
+        .arg("start")
+        .query_async(con)
+        .await
+}
+
 /// Get the role id that was posted with the given squad
-pub fn get_role_id(
-    con: &mut redis::Connection,
-    posting_id: &String,
+async fn get_role_id(
+    con: &mut ConnectionManager,
+    posting_id: &str,
 ) -> redis::RedisResult<Option<RoleId>> {
     let role_id_field_exists = redis::cmd("HEXISTS")
-        .arg(&posting_id)
+        .arg(posting_id)
         .arg("role")
-        .query::<u8>(con)?;
+        .query_async::<_, u8>(con)
+        .await?;
     match role_id_field_exists {
         1 => {
             let role_id: RoleId = redis::cmd("HGET")
-                .arg(&posting_id)
+                .arg(posting_id)
                 .arg("role")
-                .query::<u64>(con)?
+                .query_async::<_, u64>(con)
+                .await?
                 .into();
             Ok(Some(role_id))
         }
@@ -410,16 +1675,515 @@ pub fn get_role_id(
 }
 
 /// Get the channels in which a squad is posted
-pub fn get_channels(
-    con: &mut redis::Connection,
-    squad_id: &String,
+async fn get_channels(
+    con: &mut ConnectionManager,
+    squad_id: &str,
 ) -> redis::RedisResult<Vec<ChannelId>> {
-    let channels_id = channels_id(&squad_id);
+    let channels_id = channels_id(squad_id);
     let channels: Vec<ChannelId> = redis::cmd("SMEMBERS")
         .arg(&channels_id)
-        .clone()
-        .iter::<u64>(con)?
-        .map(|i| ChannelId::from(i))
+        .query_async::<_, Vec<u64>>(con)
+        .await?
+        .into_iter()
+        .map(ChannelId::from)
         .collect();
     Ok(channels)
 }
+
+/// Record the voice channel and thread opened for a posting when its squad filled,
+/// keeping the voice channel in the voice_channels Set until it is cleaned up
+async fn set_rooms(
+    con: &mut ConnectionManager,
+    message_id: &str,
+    rooms: &Rooms,
+) -> StoreResult<()> {
+    let posting_id = posting_id(message_id);
+    let exists = redis::cmd("EXISTS")
+        .arg(&posting_id)
+        .query_async::<_, u8>(con)
+        .await?;
+    if exists == 0 {
+        return Err(SquadError::UnknownSquad);
+    }
+    for (field, channel) in [("voice", rooms.voice), ("thread", rooms.thread)] {
+        if let Some(channel) = channel {
+            redis::cmd("HSET")
+                .arg(&posting_id)
+                .arg(field)
+                .arg(channel.as_u64().to_string())
+                .query_async::<_, ()>(con)
+                .await?;
+        }
+    }
+    if let Some(voice) = rooms.voice {
+        redis::cmd("SADD")
+            .arg(VOICE_CHANNELS)
+            .arg(voice.as_u64().to_string())
+            .query_async::<_, ()>(con)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Get the voice channel and thread opened for a posting, unset if none were
+async fn get_rooms(con: &mut ConnectionManager, message_id: &str) -> redis::RedisResult<Rooms> {
+    let (voice, thread): (Option<u64>, Option<u64>) = redis::cmd("HMGET")
+        .arg(posting_id(message_id))
+        .arg("voice")
+        .arg("thread")
+        .query_async(con)
+        .await?;
+    Ok(Rooms {
+        voice: voice.map(ChannelId),
+        thread: thread.map(ChannelId),
+    })
+}
+
+/// Record the Discord scheduled event created for a posting, or remove it
+async fn set_event(
+    con: &mut ConnectionManager,
+    message_id: &str,
+    event_id: Option<ScheduledEventId>,
+) -> StoreResult<()> {
+    let posting_id = posting_id(message_id);
+    let exists = redis::cmd("EXISTS")
+        .arg(&posting_id)
+        .query_async::<_, u8>(con)
+        .await?;
+    if exists == 0 {
+        return Err(SquadError::UnknownSquad);
+    }
+    match event_id {
+        Some(event_id) => {
+            redis::cmd("HSET")
+                .arg(&posting_id)
+                .arg("event")
+                .arg(event_id.as_u64().to_string())
+                .query_async::<_, ()>(con)
+                .await?;
+        }
+        None => {
+            redis::cmd("HDEL")
+                .arg(&posting_id)
+                .arg("event")
+                .query_async::<_, ()>(con)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Parse a field of a hash, None if it is missing or invalid
+fn parse_field<T: FromStr>(fields: &HashMap<String, String>, field: &str) -> Option<T> {
+    fields.get(field).and_then(|value| value.parse().ok())
+}
+
+/// Get the settings of a given guild, all unset if none were saved
+async fn get_guild_settings(
+    con: &mut ConnectionManager,
+    guild: GuildId,
+) -> redis::RedisResult<GuildSettings> {
+    let fields: HashMap<String, String> = redis::cmd("HGETALL")
+        .arg(guild_id(guild))
+        .query_async(con)
+        .await?;
+    Ok(GuildSettings {
+        default_size: parse_field(&fields, "default_size"),
+        min_size: parse_field(&fields, "min_size"),
+        max_size: parse_field(&fields, "max_size"),
+        default_duration: parse_field(&fields, "default_duration"),
+        max_duration: parse_field(&fields, "max_duration"),
+        announce_channel: parse_field(&fields, "announce_channel").map(ChannelId),
+        colour: parse_field(&fields, "colour"),
+        creator_roles: fields
+            .get("creator_roles")
+            .map_or_else(Vec::new, |roles| parse_roles(roles)),
+        dms: parse_field(&fields, "dms").map(|dms: u8| dms != 0),
+        voice_category: parse_field(&fields, "voice_category").map(ChannelId),
+        threads: parse_field(&fields, "threads").map(|threads: u8| threads != 0),
+        voice_cleanup: parse_field(&fields, "voice_cleanup"),
+        events: parse_field(&fields, "events").map(|events: u8| events != 0),
+        reminders: parse_field(&fields, "reminders").map(|reminders: u8| reminders != 0),
+        availability: fields
+            .get("availability")
+            .map_or_else(Vec::new, |presets| parse_availability(presets)),
+        locale: fields
+            .get("locale")
+            .and_then(|locale| Locale::parse(locale)),
+    })
+}
+
+/// Save the settings of a given guild, replacing any saved before:
+/// HASH guild:guild_id
+///     field default_size, min_size, max_size: squad sizes
+///     field default_duration, max_duration: squad lifetimes in seconds
+///     field announce_channel: id of the channel new squads are announced in
+///     field colour: colour of squad postings as a 0xRRGGBB number
+///     field creator_roles: ids of the roles allowed to create squads, space separated
+///     field dms: 0 or 1, whether squad members are sent direct messages
+///     field voice_category: id of the category voice channels of filled squads go in
+///     field threads: 0 or 1, whether threads are opened off filled squad postings
+///     field voice_cleanup: seconds an empty voice channel of a filled squad is kept
+///     field events: 0 or 1, whether Discord scheduled events are created for squads
+///     field reminders: 0 or 1, whether reminders are sent before squads or members expire
+///     field availability: seconds members can pick their availability from, space separated
+///     field locale: language code of the guild, e.g. es
+///     never expires, unset settings have no field
+async fn set_guild_settings(
+    con: &mut ConnectionManager,
+    guild: GuildId,
+    settings: &GuildSettings,
+) -> redis::RedisResult<()> {
+    let key = guild_id(guild);
+    let roles = Some(format_roles(&settings.creator_roles)).filter(|roles| !roles.is_empty());
+    let availability =
+        Some(format_availability(&settings.availability)).filter(|presets| !presets.is_empty());
+    let fields = [
+        (
+            "default_size",
+            settings.default_size.map(|size| size.to_string()),
+        ),
+        ("min_size", settings.min_size.map(|size| size.to_string())),
+        ("max_size", settings.max_size.map(|size| size.to_string())),
+        (
+            "default_duration",
+            settings.default_duration.map(|ttl| ttl.to_string()),
+        ),
+        (
+            "max_duration",
+            settings.max_duration.map(|ttl| ttl.to_string()),
+        ),
+        (
+            "announce_channel",
+            settings.announce_channel.map(|id| id.as_u64().to_string()),
+        ),
+        ("colour", settings.colour.map(|colour| colour.to_string())),
+        ("creator_roles", roles),
+        ("dms", settings.dms.map(|dms| u8::from(dms).to_string())),
+        (
+            "voice_category",
+            settings.voice_category.map(|id| id.as_u64().to_string()),
+        ),
+        (
+            "threads",
+            settings
+                .threads
+                .map(|threads| u8::from(threads).to_string()),
+        ),
+        (
+            "voice_cleanup",
+            settings.voice_cleanup.map(|ttl| ttl.to_string()),
+        ),
+        (
+            "events",
+            settings.events.map(|events| u8::from(events).to_string()),
+        ),
+        (
+            "reminders",
+            settings
+                .reminders
+                .map(|reminders| u8::from(reminders).to_string()),
+        ),
+        ("availability", availability),
+        (
+            "locale",
+            settings.locale.map(|locale| locale.as_str().to_string()),
+        ),
+    ];
+    let fields: Vec<(&str, String)> = fields
+        .into_iter()
+        .filter_map(|(field, value)| Some((field, value?)))
+        .collect();
+    // Replace the settings in one transaction so readers never see them half written
+    let mut pipe = redis::pipe();
+    pipe.atomic().cmd("DEL").arg(&key).ignore();
+    if !fields.is_empty() {
+        pipe.cmd("HSET").arg(&key).arg(&fields).ignore();
+    }
+    pipe.query_async::<_, ()>(con).await
+}
+
+#[async_trait]
+impl SquadStore for Redis {
+    async fn build_squad(
+        &self,
+        squad_id: &str,
+        owner_id: &str,
+        capacity: u8,
+        start: Option<i64>,
+        composition: &Composition,
+        ttl: u64,
+    ) -> StoreResult<()> {
+        let mut con = self.connection();
+        Ok(build_squad(
+            &mut con,
+            squad_id,
+            owner_id,
+            capacity,
+            start,
+            composition,
+            ttl,
+        )
+        .await?)
+    }
+
+    async fn build_posting(
+        &self,
+        channel_id: &str,
+        guild_id: Option<GuildId>,
+        message_id: &str,
+        role_id: Option<RoleId>,
+        squad_id: &str,
+        ttl: u64,
+    ) -> StoreResult<()> {
+        let mut con = self.connection();
+        Ok(build_posting(
+            &mut con, channel_id, guild_id, message_id, role_id, squad_id, ttl,
+        )
+        .await?)
+    }
+
+    async fn get_squad_id(&self, message_id: &str) -> StoreResult<String> {
+        get_squad_id(&mut self.connection(), message_id)
+            .await?
+            .ok_or(SquadError::UnknownSquad)
+    }
+
+    async fn add_member(
+        &self,
+        squad_id: &str,
+        user_id: &str,
+        expires: u32,
+    ) -> StoreResult<JoinResult> {
+        Ok(add_member(&mut self.connection(), squad_id, user_id, expires).await?)
+    }
+
+    async fn delete_member(&self, squad_id: &str, user_id: &str) -> StoreResult<()> {
+        Ok(delete_member(&mut self.connection(), squad_id, user_id).await?)
+    }
+
+    async fn promote_members(&self, squad_id: &str) -> StoreResult<Vec<UserId>> {
+        Ok(promote_members(&mut self.connection(), squad_id).await?)
+    }
+
+    async fn choose_slot(&self, squad_id: &str, user_id: &str, slot: &str) -> StoreResult<bool> {
+        Ok(choose_slot(&mut self.connection(), squad_id, user_id, slot).await?)
+    }
+
+    async fn get_composition(&self, squad_id: &str) -> StoreResult<Composition> {
+        get_composition(&mut self.connection(), squad_id).await
+    }
+
+    async fn get_slots(&self, squad_id: &str) -> StoreResult<HashMap<UserId, String>> {
+        Ok(get_slots(&mut self.connection(), squad_id).await?)
+    }
+
+    async fn set_origin(
+        &self,
+        squad_id: &str,
+        user_id: &str,
+        channel_id: ChannelId,
+    ) -> StoreResult<()> {
+        Ok(set_origin(&mut self.connection(), squad_id, user_id, channel_id).await?)
+    }
+
+    async fn get_origins(&self, squad_id: &str) -> StoreResult<HashMap<UserId, ChannelId>> {
+        Ok(get_origins(&mut self.connection(), squad_id).await?)
+    }
+
+    async fn get_waitlist(&self, squad_id: &str) -> StoreResult<Vec<UserId>> {
+        Ok(get_waitlist(&mut self.connection(), squad_id).await?)
+    }
+
+    async fn get_capacity(&self, squad_id: &str) -> StoreResult<u8> {
+        get_capacity(&mut self.connection(), squad_id)
+            .await?
+            .ok_or(SquadError::UnknownSquad)
+    }
+
+    async fn get_members(&self, squad_id: &str) -> StoreResult<HashMap<UserId, u64>> {
+        Ok(get_members(&mut self.connection(), squad_id).await?)
+    }
+
+    async fn get_squad_ttl(&self, squad_id: &str) -> StoreResult<u64> {
+        get_ttl(&mut self.connection(), squad_id)
+            .await?
+            .ok_or(SquadError::UnknownSquad)
+    }
+
+    async fn get_postings(&self) -> StoreResult<HashMap<MessageId, ChannelId>> {
+        Ok(get_postings(&mut self.connection()).await?)
+    }
+
+    async fn get_squad_postings(
+        &self,
+        squad_id: &str,
+    ) -> StoreResult<HashMap<MessageId, ChannelId>> {
+        Ok(get_squad_postings(&mut self.connection(), squad_id).await?)
+    }
+
+    async fn get_guild_squads(&self, guild_id: GuildId) -> StoreResult<Vec<String>> {
+        Ok(get_guild_squads(&mut self.connection(), guild_id).await?)
+    }
+
+    async fn get_user_squads(&self, user_id: UserId) -> StoreResult<Vec<String>> {
+        let index = user_squads_index(&user_id.as_u64().to_string());
+        Ok(live_index_members(&mut self.connection(), &index).await?)
+    }
+
+    async fn delete_posting(&self, message_id: &str) -> StoreResult<bool> {
+        Ok(delete_posting(&mut self.connection(), message_id).await?)
+    }
+
+    async fn get_full_squads(&self) -> StoreResult<Vec<String>> {
+        Ok(get_full_squads(&mut self.connection()).await?)
+    }
+
+    async fn set_link_policy(&self, squad_id: &str, policy: LinkPolicy) -> StoreResult<()> {
+        Ok(set_squad_field(&mut self.connection(), squad_id, "links", policy.as_str()).await?)
+    }
+
+    async fn get_link_policy(&self, squad_id: &str) -> StoreResult<LinkPolicy> {
+        let policy: Option<String> = redis::cmd("HGET")
+            .arg(squad_id)
+            .arg("links")
+            .query_async(&mut self.connection())
+            .await?;
+        Ok(policy.map_or_else(LinkPolicy::default, |policy| LinkPolicy::parse(&policy)))
+    }
+
+    async fn set_quorum(&self, squad_id: &str, quorum: u8) -> StoreResult<()> {
+        let quorum = quorum.to_string();
+        Ok(set_squad_field(&mut self.connection(), squad_id, "quorum", &quorum).await?)
+    }
+
+    async fn get_quorum(&self, squad_id: &str) -> StoreResult<Option<u8>> {
+        Ok(redis::cmd("HGET")
+            .arg(squad_id)
+            .arg("quorum")
+            .query_async(&mut self.connection())
+            .await?)
+    }
+
+    async fn ready_squad(&self, squad_id: &str) -> StoreResult<bool> {
+        Ok(ready_squad(&mut self.connection(), squad_id).await?)
+    }
+
+    async fn is_ready(&self, squad_id: &str) -> StoreResult<bool> {
+        let ready: Option<u8> = redis::cmd("HGET")
+            .arg(squad_id)
+            .arg("ready")
+            .query_async(&mut self.connection())
+            .await?;
+        Ok(ready == Some(1))
+    }
+
+    async fn fill_squad(&self, squad_id: &str) -> StoreResult<bool> {
+        Ok(fill_squad(&mut self.connection(), squad_id).await?)
+    }
+
+    async fn close_squad(&self, squad_id: &str) -> StoreResult<bool> {
+        Ok(close_squad(&mut self.connection(), squad_id).await?)
+    }
+
+    async fn cancel_squad(&self, squad_id: &str) -> StoreResult<bool> {
+        Ok(cancel_squad(&mut self.connection(), squad_id).await?)
+    }
+
+    async fn extend_squad(&self, squad_id: &str, seconds: u64) -> StoreResult<bool> {
+        Ok(extend_squad(&mut self.connection(), squad_id, seconds).await?)
+    }
+
+    async fn get_owner(&self, squad_id: &str) -> StoreResult<Option<UserId>> {
+        Ok(get_owner(&mut self.connection(), squad_id).await?)
+    }
+
+    async fn get_start(&self, squad_id: &str) -> StoreResult<Option<i64>> {
+        Ok(get_start(&mut self.connection(), squad_id).await?)
+    }
+
+    async fn get_squad_status(&self, squad_id: &str) -> StoreResult<SquadStatus> {
+        Ok(get_squad_status(&mut self.connection(), squad_id).await?)
+    }
+
+    async fn get_role_id(&self, message_id: &str) -> StoreResult<Option<RoleId>> {
+        Ok(get_role_id(&mut self.connection(), &posting_id(message_id)).await?)
+    }
+
+    async fn get_channels(&self, squad_id: &str) -> StoreResult<Vec<ChannelId>> {
+        Ok(get_channels(&mut self.connection(), squad_id).await?)
+    }
+
+    async fn set_rooms(&self, message_id: &str, rooms: &Rooms) -> StoreResult<()> {
+        set_rooms(&mut self.connection(), message_id, rooms).await
+    }
+
+    async fn get_rooms(&self, message_id: &str) -> StoreResult<Rooms> {
+        Ok(get_rooms(&mut self.connection(), message_id).await?)
+    }
+
+    async fn set_event(
+        &self,
+        message_id: &str,
+        event_id: Option<ScheduledEventId>,
+    ) -> StoreResult<()> {
+        set_event(&mut self.connection(), message_id, event_id).await
+    }
+
+    async fn get_event(&self, message_id: &str) -> StoreResult<Option<ScheduledEventId>> {
+        let event: Option<u64> = redis::cmd("HGET")
+            .arg(posting_id(message_id))
+            .arg("event")
+            .query_async(&mut self.connection())
+            .await?;
+        Ok(event.map(ScheduledEventId))
+    }
+
+    async fn get_voice_channels(&self) -> StoreResult<Vec<ChannelId>> {
+        let channels: Vec<u64> = redis::cmd("SMEMBERS")
+            .arg(VOICE_CHANNELS)
+            .query_async(&mut self.connection())
+            .await?;
+        Ok(channels.into_iter().map(ChannelId).collect())
+    }
+
+    async fn delete_voice_channel(&self, channel_id: ChannelId) -> StoreResult<()> {
+        redis::cmd("SREM")
+            .arg(VOICE_CHANNELS)
+            .arg(channel_id.as_u64().to_string())
+            .query_async::<_, ()>(&mut self.connection())
+            .await?;
+        Ok(())
+    }
+
+    async fn get_guild_settings(&self, guild_id: GuildId) -> StoreResult<GuildSettings> {
+        Ok(get_guild_settings(&mut self.connection(), guild_id).await?)
+    }
+
+    async fn set_guild_settings(
+        &self,
+        guild_id: GuildId,
+        settings: &GuildSettings,
+    ) -> StoreResult<()> {
+        Ok(set_guild_settings(&mut self.connection(), guild_id, settings).await?)
+    }
+
+    async fn subscribe(&self) -> StoreResult<Option<UnboundedReceiver<String>>> {
+        // Without notifications, postings are kept up to date by polling alone
+        if !self.keyspace_events {
+            return Ok(None);
+        }
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let client = self.client.clone();
+        let mut con = self.connection();
+        tokio::spawn(async move {
+            while !sender.is_closed() {
+                if let Err(why) = forward_keyspace_events(&client, &mut con, &sender).await {
+                    eprintln!("Error receiving Redis keyspace notifications: {}", why);
+                }
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        });
+        Ok(Some(receiver))
+    }
+}
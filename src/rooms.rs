@@ -0,0 +1,249 @@
+use crate::error::SquadError;
+use crate::locale;
+use crate::locale::Text;
+use crate::store;
+use crate::store::{Rooms, SquadStore};
+use serenity::client::Context;
+use serenity::model::channel::{ChannelType, PermissionOverwrite, PermissionOverwriteType};
+use serenity::model::id::{ChannelId, GuildId, MessageId, RoleId, UserId};
+use serenity::model::mention::Mention;
+use serenity::model::Permissions;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use typemap_rev::TypeMapKey;
+
+/// Minutes of inactivity after which Discord archives the thread of a filled squad.
+const THREAD_ARCHIVE_MINUTES: u16 = 60;
+
+pub struct EmptyVoiceChannels;
+
+/// Globally available TypeMapKey to store since when each voice channel opened for a
+/// filled squad has been empty, so that it is deleted once it has been for too long.
+impl TypeMapKey for EmptyVoiceChannels {
+    type Value = Arc<Mutex<HashMap<ChannelId, Instant>>>;
+}
+
+/// Open a voice channel and a thread for each posting of a squad that just filled,
+/// was closed or reached its quorum, as far as the guild of the posting opted into
+/// them. Voice channels only let squad members connect, and rooms are named in the
+/// language of their guild. Rooms that fail to open are logged and skipped. Returns
+/// the rooms as lines to include in the DM to squad members, empty if none were
+/// opened.
+pub async fn open_rooms(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+) -> Result<String, SquadError> {
+    let postings = store.get_squad_postings(squad_id).await?;
+    let members: Vec<UserId> = store.get_members(squad_id).await?.into_keys().collect();
+    let mut links = String::new();
+    for (message_id, channel_id) in postings {
+        let guild_id = match ctx.cache.guild_channel(channel_id) {
+            Some(channel) => channel.guild_id,
+            None => continue,
+        };
+        let settings = store.get_guild_settings(guild_id).await?;
+        let name = room_name(squad_id, settings.locale().text());
+        let mut rooms = Rooms::default();
+        if let Some(category_id) = settings.voice_category {
+            match open_voice_channel(ctx, guild_id, category_id, &name, &members).await {
+                Ok(voice) => rooms.voice = Some(voice),
+                Err(why) => eprintln!("Error opening voice channel: {}", why),
+            }
+        }
+        if settings.threads() {
+            match open_thread(ctx, channel_id, message_id, &name, &members).await {
+                Ok(thread) => rooms.thread = Some(thread),
+                Err(why) => eprintln!("Error opening thread: {}", why),
+            }
+        }
+        if rooms.voice.is_none() && rooms.thread.is_none() {
+            continue;
+        }
+        store.set_rooms(&message_id.to_string(), &rooms).await?;
+        for room in [rooms.voice, rooms.thread].into_iter().flatten() {
+            links.push_str(&format!("{}\n", Mention::from(room)));
+        }
+    }
+    Ok(links)
+}
+
+/// Let a user who joined a squad after its rooms were opened into them: allow them to
+/// connect to its voice channels, as far as they are members of the guild, and add
+/// them to its threads. Rooms that fail to admit the user are logged and skipped.
+/// Returns the rooms as lines to include in the DM to the user.
+pub async fn admit_member(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+    user_id: UserId,
+) -> Result<String, SquadError> {
+    let mut links = String::new();
+    for (message_id, channel_id) in store.get_squad_postings(squad_id).await? {
+        let rooms = store.get_rooms(&message_id.to_string()).await?;
+        if let Some(voice) = rooms.voice {
+            let guild_id = match ctx.cache.guild_channel(channel_id) {
+                Some(channel) => channel.guild_id,
+                None => continue,
+            };
+            if guild_id.member(ctx, user_id).await.is_ok() {
+                let overwrite = PermissionOverwrite {
+                    allow: Permissions::VIEW_CHANNEL | Permissions::CONNECT | Permissions::SPEAK,
+                    deny: Permissions::empty(),
+                    kind: PermissionOverwriteType::Member(user_id),
+                };
+                if let Err(why) = voice.create_permission(&ctx.http, &overwrite).await {
+                    eprintln!("Error admitting member to voice channel: {}", why);
+                }
+            }
+        }
+        if let Some(thread) = rooms.thread {
+            let _ = thread.add_thread_member(&ctx.http, user_id).await;
+        }
+        for room in [rooms.voice, rooms.thread].into_iter().flatten() {
+            links.push_str(&format!("{}\n", Mention::from(room)));
+        }
+    }
+    Ok(links)
+}
+
+/// Take back the permission of a user who left or was kicked from a squad to connect
+/// to its voice channels, so that they stay restricted to squad members. Voice
+/// channels that fail to update are logged and skipped.
+pub async fn dismiss_member(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+    user_id: UserId,
+) -> Result<(), SquadError> {
+    for message_id in store.get_squad_postings(squad_id).await?.keys() {
+        let rooms = store.get_rooms(&message_id.to_string()).await?;
+        if let Some(voice) = rooms.voice {
+            let overwrite = PermissionOverwriteType::Member(user_id);
+            if let Err(why) = voice.delete_permission(&ctx.http, overwrite).await {
+                eprintln!("Error dismissing member from voice channel: {}", why);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Name of the voice channel and thread of a squad in the given language, e.g.
+/// "Squad 6789" for the squad with ID squad:123456789.
+pub fn room_name(squad_id: &str, text: &Text) -> String {
+    let id = squad_id.trim_start_matches("squad:");
+    locale::fill(text.room_name, &[&&id[id.len().saturating_sub(4)..]])
+}
+
+/// Create a voice channel in the given category that only the given users, as far as
+/// they are members of the guild, can connect to.
+async fn open_voice_channel(
+    ctx: &Context,
+    guild_id: GuildId,
+    category_id: ChannelId,
+    name: &str,
+    members: &[UserId],
+) -> Result<ChannelId, SquadError> {
+    let mut overwrites = vec![PermissionOverwrite {
+        allow: Permissions::empty(),
+        deny: Permissions::CONNECT,
+        kind: PermissionOverwriteType::Role(RoleId(guild_id.0)),
+    }];
+    for user_id in members {
+        // Members of squads posted in several servers may not be in this one
+        if guild_id.member(ctx, *user_id).await.is_ok() {
+            overwrites.push(PermissionOverwrite {
+                allow: Permissions::VIEW_CHANNEL | Permissions::CONNECT | Permissions::SPEAK,
+                deny: Permissions::empty(),
+                kind: PermissionOverwriteType::Member(*user_id),
+            });
+        }
+    }
+    let channel = guild_id
+        .create_channel(&ctx.http, |c| {
+            c.name(name)
+                .kind(ChannelType::Voice)
+                .category(category_id)
+                .permissions(overwrites)
+        })
+        .await?;
+    Ok(channel.id)
+}
+
+/// Create a public thread off a squad posting and add the squad members to it.
+/// Discord archives the thread on its own once it goes quiet.
+async fn open_thread(
+    ctx: &Context,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    name: &str,
+    members: &[UserId],
+) -> Result<ChannelId, SquadError> {
+    let thread = channel_id
+        .create_public_thread(&ctx.http, message_id, |t| {
+            t.name(name)
+                .kind(ChannelType::PublicThread)
+                .auto_archive_duration(THREAD_ARCHIVE_MINUTES)
+        })
+        .await?;
+    for user_id in members {
+        // Members of squads posted in several servers may not be in this one
+        let _ = thread.id.add_thread_member(&ctx.http, *user_id).await;
+    }
+    Ok(thread.id)
+}
+
+/// Delete voice channels of filled squads that have been empty for longer than their
+/// guild allows. Voice channels that no longer exist are forgotten.
+pub async fn clean_up_rooms(ctx: &Context, store: &dyn SquadStore) -> Result<(), SquadError> {
+    let empty_since = get_empty_voice_channels(ctx).await;
+    let mut empty_since = empty_since.lock().await;
+    let now = Instant::now();
+    for channel_id in store.get_voice_channels().await? {
+        let channel = match ctx.cache.guild_channel(channel_id) {
+            Some(channel) => channel,
+            None => {
+                empty_since.remove(&channel_id);
+                store.delete_voice_channel(channel_id).await?;
+                continue;
+            }
+        };
+        let occupied = ctx
+            .cache
+            .guild_field(channel.guild_id, |guild| {
+                guild
+                    .voice_states
+                    .values()
+                    .any(|state| state.channel_id == Some(channel_id))
+            })
+            .unwrap_or(false);
+        if occupied {
+            empty_since.remove(&channel_id);
+            continue;
+        }
+        let since = *empty_since.entry(channel_id).or_insert(now);
+        let cleanup = store::get_channel_settings(ctx, store, channel_id)
+            .await?
+            .voice_cleanup();
+        if now.duration_since(since) < Duration::from_secs(cleanup) {
+            continue;
+        }
+        if let Err(why) = channel_id.delete(&ctx.http).await {
+            eprintln!("Error deleting voice channel: {}", why);
+        }
+        empty_since.remove(&channel_id);
+        store.delete_voice_channel(channel_id).await?;
+    }
+    Ok(())
+}
+
+/// Retrieve the record of empty voice channels from the global data context.
+async fn get_empty_voice_channels(ctx: &Context) -> Arc<Mutex<HashMap<ChannelId, Instant>>> {
+    let mut data = ctx.data.write().await;
+    let empty = data
+        .entry::<EmptyVoiceChannels>()
+        .or_insert_with(|| Arc::new(Mutex::new(HashMap::new())));
+    Arc::clone(empty)
+}
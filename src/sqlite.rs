@@ -0,0 +1,1252 @@
+use crate::error::SquadError;
+use crate::locale::Locale;
+use crate::store::{
+    format_availability, format_composition, format_roles, parse_availability, parse_composition,
+    parse_roles, Composition, GuildSettings, JoinResult, LinkPolicy, Rooms, SquadStatus,
+    SquadStore, StoreResult,
+};
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serenity::async_trait;
+use serenity::model::id::{GuildId, ScheduledEventId, UserId};
+use serenity::model::prelude::{ChannelId, MessageId, RoleId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::task;
+
+/// Squad store backed by a single SQLite database file.
+/// Live squad data expires with the same semantics as the Redis keys, while the
+/// squad_history and squad_history_members tables keep a permanent record of every
+/// squad: its capacity, who joined, and when it filled or expired.
+pub struct Sqlite {
+    con: Arc<Mutex<Connection>>,
+}
+
+/// Schema migrations, applied in order and tracked through PRAGMA user_version.
+const MIGRATIONS: &[&str] = &[
+    // Live data, equivalent of the squad:*, members:*, member:*, posting:* and
+    // channels:* keys in Redis. All times are unix timestamps in seconds.
+    "CREATE TABLE squads (
+        id TEXT PRIMARY KEY,
+        capacity INTEGER NOT NULL,
+        filled INTEGER NOT NULL DEFAULT 0,
+        expires INTEGER NOT NULL,
+        history INTEGER NOT NULL
+    );
+    CREATE TABLE members (
+        squad TEXT NOT NULL,
+        user TEXT NOT NULL,
+        in_squad INTEGER NOT NULL,
+        expires INTEGER NOT NULL,
+        PRIMARY KEY (squad, user)
+    );
+    CREATE TABLE postings (
+        message TEXT PRIMARY KEY,
+        channel TEXT NOT NULL,
+        squad TEXT NOT NULL,
+        role TEXT,
+        expires INTEGER NOT NULL
+    );
+    CREATE TABLE channels (
+        squad TEXT NOT NULL,
+        channel TEXT NOT NULL,
+        expires INTEGER NOT NULL,
+        PRIMARY KEY (squad, channel)
+    );
+    CREATE TABLE squad_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        squad TEXT NOT NULL,
+        capacity INTEGER NOT NULL,
+        created INTEGER NOT NULL,
+        filled INTEGER,
+        expired INTEGER
+    );
+    CREATE TABLE squad_history_members (
+        history INTEGER NOT NULL REFERENCES squad_history (id),
+        user TEXT NOT NULL,
+        joined INTEGER NOT NULL,
+        PRIMARY KEY (history, user)
+    );",
+    // Start time of scheduled squads
+    "ALTER TABLE squads ADD COLUMN start INTEGER;
+    ALTER TABLE squad_history ADD COLUMN start INTEGER;",
+    // Waitlist of full squads, equivalent of the waitlist:* lists in Redis. Users are
+    // promoted in id order and stay for the given number of seconds once promoted.
+    "CREATE TABLE waitlist (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        squad TEXT NOT NULL,
+        user TEXT NOT NULL,
+        seconds INTEGER NOT NULL,
+        UNIQUE (squad, user)
+    );",
+    // Role slots, equivalent of the composition field and slots:* hashes in Redis
+    "ALTER TABLE squads ADD COLUMN composition TEXT;
+    CREATE TABLE slots (
+        squad TEXT NOT NULL,
+        user TEXT NOT NULL,
+        slot TEXT NOT NULL,
+        PRIMARY KEY (squad, user)
+    );",
+    // Squad owners, and cancellation. squads.filled is 2 for cancelled squads.
+    "ALTER TABLE squads ADD COLUMN owner TEXT;
+    ALTER TABLE squad_history ADD COLUMN owner TEXT;
+    ALTER TABLE squad_history ADD COLUMN cancelled INTEGER;",
+    // Guild settings, equivalent of the guild:* hashes in Redis. Durations are in
+    // seconds, NULL when unset.
+    "CREATE TABLE guild_settings (
+        guild TEXT PRIMARY KEY,
+        default_duration INTEGER,
+        max_duration INTEGER
+    );",
+    // The rest of the guild settings. Creator roles are role ids separated by spaces.
+    "ALTER TABLE guild_settings ADD COLUMN default_size INTEGER;
+    ALTER TABLE guild_settings ADD COLUMN min_size INTEGER;
+    ALTER TABLE guild_settings ADD COLUMN max_size INTEGER;
+    ALTER TABLE guild_settings ADD COLUMN announce_channel TEXT;
+    ALTER TABLE guild_settings ADD COLUMN colour INTEGER;
+    ALTER TABLE guild_settings ADD COLUMN creator_roles TEXT;
+    ALTER TABLE guild_settings ADD COLUMN dms INTEGER;",
+    // Voice channels and threads opened for filled squads. Voice channels are kept
+    // until they are cleaned up, regardless of the expiry of their posting.
+    "ALTER TABLE guild_settings ADD COLUMN voice_category TEXT;
+    ALTER TABLE guild_settings ADD COLUMN threads INTEGER;
+    ALTER TABLE guild_settings ADD COLUMN voice_cleanup INTEGER;
+    ALTER TABLE postings ADD COLUMN voice TEXT;
+    ALTER TABLE postings ADD COLUMN thread TEXT;
+    CREATE TABLE voice_channels (
+        channel TEXT PRIMARY KEY
+    );",
+    // Discord scheduled events created for squads
+    "ALTER TABLE guild_settings ADD COLUMN events INTEGER;
+    ALTER TABLE postings ADD COLUMN event TEXT;",
+    "ALTER TABLE guild_settings ADD COLUMN reminders INTEGER;",
+    // Squads with a quorum are ready to play once it is met, while staying open
+    "ALTER TABLE squads ADD COLUMN quorum INTEGER;
+    ALTER TABLE squads ADD COLUMN ready INTEGER NOT NULL DEFAULT 0;",
+    // Who may link postings to a squad, NULL for squads from before link policies
+    "ALTER TABLE squads ADD COLUMN link_policy TEXT;",
+    // Channel of the posting through which each user joined a squad, equivalent of
+    // the origins:* hashes in Redis
+    "CREATE TABLE origins (
+        squad TEXT NOT NULL,
+        user TEXT NOT NULL,
+        channel TEXT NOT NULL,
+        PRIMARY KEY (squad, user)
+    );",
+    // Availability presets are times in seconds separated by spaces
+    "ALTER TABLE guild_settings ADD COLUMN availability TEXT;",
+    // Language code of the guild, e.g. es
+    "ALTER TABLE guild_settings ADD COLUMN locale TEXT;",
+    // Guild of each posting, NULL for postings in DMs or from before guilds were kept,
+    // and indexes to find the squads of a guild or of a user
+    "ALTER TABLE postings ADD COLUMN guild TEXT;
+    CREATE INDEX postings_guild ON postings (guild);
+    CREATE INDEX members_user ON members (user);
+    CREATE INDEX waitlist_user ON waitlist (user);",
+];
+
+/// Current unix timestamp in seconds.
+fn now() -> i64 {
+    Utc::now().timestamp()
+}
+
+/// Seconds remaining until the given unix timestamp.
+fn ttl(expires: i64) -> u64 {
+    u64::try_from(expires - now()).unwrap_or(0)
+}
+
+impl Sqlite {
+    /// Open (or create) the database at the given path and bring its schema up to date.
+    pub fn open(path: &str) -> rusqlite::Result<Sqlite> {
+        let mut con = Connection::open(path)?;
+        let version: usize = con.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = con.transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", index + 1)?;
+            tx.commit()?;
+        }
+        Ok(Sqlite {
+            con: Arc::new(Mutex::new(con)),
+        })
+    }
+
+    /// Run queries on the database connection, expiring any live data that has run out
+    /// first. SQLite calls block, so they run on a blocking thread rather than holding
+    /// up the async runtime.
+    async fn run<T, F>(&self, queries: F) -> StoreResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> StoreResult<T> + Send + 'static,
+    {
+        let con = Arc::clone(&self.con);
+        task::spawn_blocking(move || {
+            let mut con = con
+                .lock()
+                .map_err(|_| SquadError::store("Squad store lock poisoned."))?;
+            purge(&con)?;
+            queries(&mut con)
+        })
+        .await
+        .map_err(SquadError::store)?
+    }
+}
+
+/// Delete expired live data. Squads that expire are stamped in the history table.
+fn purge(con: &Connection) -> rusqlite::Result<()> {
+    let now = now();
+    con.execute(
+        "UPDATE squad_history SET expired =
+            (SELECT expires FROM squads WHERE squads.history = squad_history.id)
+        WHERE id IN (SELECT history FROM squads WHERE expires <= ?1)",
+        params![now],
+    )?;
+    con.execute("DELETE FROM squads WHERE expires <= ?1", params![now])?;
+    con.execute(
+        "DELETE FROM members WHERE expires <= ?1
+        OR squad NOT IN (SELECT id FROM squads)",
+        params![now],
+    )?;
+    con.execute(
+        "DELETE FROM waitlist WHERE squad NOT IN (SELECT id FROM squads)",
+        [],
+    )?;
+    con.execute(
+        "DELETE FROM slots WHERE squad NOT IN (SELECT id FROM squads)",
+        [],
+    )?;
+    con.execute(
+        "DELETE FROM origins WHERE squad NOT IN (SELECT id FROM squads)",
+        [],
+    )?;
+    con.execute("DELETE FROM postings WHERE expires <= ?1", params![now])?;
+    con.execute("DELETE FROM channels WHERE expires <= ?1", params![now])?;
+    Ok(())
+}
+
+/// Get the squad status of a given squad id: Expired, Forming, or Filled
+fn squad_status(con: &Connection, squad_id: &str) -> rusqlite::Result<SquadStatus> {
+    let filled: Option<u8> = con
+        .query_row(
+            "SELECT filled FROM squads WHERE id = ?1",
+            params![squad_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(match filled {
+        None => SquadStatus::Expired,
+        Some(0) => SquadStatus::Forming,
+        Some(2) => SquadStatus::Cancelled,
+        Some(_) => SquadStatus::Filled,
+    })
+}
+
+/// Get the role slots of a given squad id, empty if it has none
+fn composition(con: &Connection, squad_id: &str) -> StoreResult<Composition> {
+    let composition: Option<String> = con
+        .query_row(
+            "SELECT composition FROM squads WHERE id = ?1",
+            params![squad_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    match composition {
+        Some(composition) => parse_composition(&composition),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Get the slot a user picked in a given squad id
+fn slot(con: &Connection, squad_id: &str, user_id: &str) -> rusqlite::Result<Option<String>> {
+    con.query_row(
+        "SELECT slot FROM slots WHERE squad = ?1 AND user = ?2",
+        params![squad_id, user_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Count the members of a given squad id, or only those who picked the given slot
+fn member_count(con: &Connection, squad_id: &str, slot: Option<&str>) -> rusqlite::Result<usize> {
+    con.query_row(
+        "SELECT COUNT(*) FROM members LEFT JOIN slots
+            ON slots.squad = members.squad AND slots.user = members.user
+        WHERE members.squad = ?1 AND in_squad AND (?2 IS NULL OR slot = ?2)",
+        params![squad_id, slot],
+        |row| row.get(0),
+    )
+}
+
+/// Whether the given slot of a squad has a free seat
+fn has_seat(con: &Connection, squad_id: &str, slot: &str) -> StoreResult<bool> {
+    let seats = composition(con, squad_id)?
+        .iter()
+        .find(|(name, _)| name == slot)
+        .map_or(0, |(_, seats)| usize::from(*seats));
+    Ok(member_count(con, squad_id, Some(slot))? < seats)
+}
+
+/// Whether a squad has a free seat for a user who picked the given slot. Squads
+/// without role slots only need a free seat overall.
+fn has_room(con: &Connection, squad_id: &str, slot: Option<&str>) -> StoreResult<bool> {
+    let capacity: usize = con.query_row(
+        "SELECT capacity FROM squads WHERE id = ?1",
+        params![squad_id],
+        |row| row.get(0),
+    )?;
+    if member_count(con, squad_id, None)? >= capacity {
+        return Ok(false);
+    }
+    if composition(con, squad_id)?.is_empty() {
+        return Ok(true);
+    }
+    match slot {
+        Some(slot) => has_seat(con, squad_id, slot),
+        None => Ok(false),
+    }
+}
+
+/// Parse a Discord snowflake stored as text.
+fn parse_id(id: String) -> StoreResult<u64> {
+    Ok(id.parse::<u64>()?)
+}
+
+#[async_trait]
+impl SquadStore for Sqlite {
+    async fn build_squad(
+        &self,
+        squad_id: &str,
+        owner_id: &str,
+        capacity: u8,
+        start: Option<i64>,
+        composition: &Composition,
+        ttl: u64,
+    ) -> StoreResult<()> {
+        let squad_id = squad_id.to_owned();
+        let owner_id = owner_id.to_owned();
+        let composition = match composition.is_empty() {
+            true => None,
+            false => Some(format_composition(composition)),
+        };
+        self.run(move |con| {
+            let now = now();
+            let tx = con.transaction()?;
+            tx.execute(
+                "INSERT INTO squad_history (squad, owner, capacity, created, start)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![squad_id, owner_id, capacity, now, start],
+            )?;
+            let history = tx.last_insert_rowid();
+            tx.execute(
+                "INSERT OR REPLACE INTO squads
+                    (id, owner, capacity, filled, expires, history, start, composition)
+                VALUES (?1, ?2, ?3, 0, ?4, ?5, ?6, ?7)",
+                params![
+                    squad_id,
+                    owner_id,
+                    capacity,
+                    now + ttl as i64,
+                    history,
+                    start,
+                    composition
+                ],
+            )?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn build_posting(
+        &self,
+        channel_id: &str,
+        guild_id: Option<GuildId>,
+        message_id: &str,
+        role_id: Option<RoleId>,
+        squad_id: &str,
+        ttl: u64,
+    ) -> StoreResult<()> {
+        let channel_id = channel_id.to_owned();
+        let message_id = message_id.to_owned();
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let expires = now() + ttl as i64;
+            let guild = guild_id.map(|id| id.as_u64().to_string());
+            let role = role_id.map(|id| id.as_u64().to_string());
+            let tx = con.transaction()?;
+            tx.execute(
+                "INSERT OR REPLACE INTO postings (message, channel, guild, squad, role, expires)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![message_id, channel_id, guild, squad_id, role, expires],
+            )?;
+            tx.execute(
+                "INSERT OR IGNORE INTO channels (squad, channel, expires) VALUES (?1, ?2, ?3)",
+                params![squad_id, channel_id, expires],
+            )?;
+            // Like EXPIRE on the channels set, adding a channel refreshes the whole set
+            tx.execute(
+                "UPDATE channels SET expires = ?2 WHERE squad = ?1",
+                params![squad_id, expires],
+            )?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_squad_id(&self, message_id: &str) -> StoreResult<String> {
+        let message_id = message_id.to_owned();
+        self.run(move |con| {
+            let squad_id = con.query_row(
+                "SELECT squad FROM postings WHERE message = ?1",
+                params![message_id],
+                |row| row.get(0),
+            )?;
+            Ok(squad_id)
+        })
+        .await
+    }
+
+    async fn add_member(
+        &self,
+        squad_id: &str,
+        user_id: &str,
+        expires: u32,
+    ) -> StoreResult<JoinResult> {
+        let squad_id = squad_id.to_owned();
+        let user_id = user_id.to_owned();
+        self.run(move |con| {
+            let now = now();
+            let tx = con.transaction()?;
+            if !matches!(squad_status(&tx, &squad_id)?, SquadStatus::Forming) {
+                return Ok(JoinResult::Closed);
+            }
+            let history: i64 = tx.query_row(
+                "SELECT history FROM squads WHERE id = ?1",
+                params![squad_id],
+                |row| row.get(0),
+            )?;
+            let slot = slot(&tx, &squad_id, &user_id)?;
+            if slot.is_none() && !composition(&tx, &squad_id)?.is_empty() {
+                return Ok(JoinResult::NoSlot);
+            }
+            let already_member: bool = tx.query_row(
+                "SELECT EXISTS (SELECT 1 FROM members WHERE squad = ?1 AND user = ?2 AND in_squad)",
+                params![squad_id, user_id],
+                |row| row.get(0),
+            )?;
+            if !already_member && !has_room(&tx, &squad_id, slot.as_deref())? {
+                // Keep the place in line of users who were already waiting
+                tx.execute(
+                    "INSERT INTO waitlist (squad, user, seconds) VALUES (?1, ?2, ?3)
+                    ON CONFLICT (squad, user) DO UPDATE SET seconds = excluded.seconds",
+                    params![squad_id, user_id, expires],
+                )?;
+                tx.commit()?;
+                return Ok(JoinResult::Waitlisted);
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO members (squad, user, in_squad, expires)
+                VALUES (?1, ?2, 1, ?3)",
+                params![squad_id, user_id, now + i64::from(expires)],
+            )?;
+            tx.execute(
+                "DELETE FROM waitlist WHERE squad = ?1 AND user = ?2",
+                params![squad_id, user_id],
+            )?;
+            tx.execute(
+                "INSERT OR IGNORE INTO squad_history_members (history, user, joined)
+                VALUES (?1, ?2, ?3)",
+                params![history, user_id, now],
+            )?;
+            tx.commit()?;
+            match already_member {
+                true => Ok(JoinResult::AlreadyMember),
+                false => Ok(JoinResult::Joined),
+            }
+        })
+        .await
+    }
+
+    async fn delete_member(&self, squad_id: &str, user_id: &str) -> StoreResult<()> {
+        let squad_id = squad_id.to_owned();
+        let user_id = user_id.to_owned();
+        self.run(move |con| {
+            let tx = con.transaction()?;
+            tx.execute(
+                "DELETE FROM members WHERE squad = ?1 AND user = ?2",
+                params![squad_id, user_id],
+            )?;
+            tx.execute(
+                "DELETE FROM waitlist WHERE squad = ?1 AND user = ?2",
+                params![squad_id, user_id],
+            )?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn promote_members(&self, squad_id: &str) -> StoreResult<Vec<UserId>> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let now = now();
+            let tx = con.transaction()?;
+            if !matches!(squad_status(&tx, &squad_id)?, SquadStatus::Forming) {
+                return Ok(Vec::new());
+            }
+            let waiting = {
+                let mut statement = tx.prepare(
+                    "SELECT id, user, seconds FROM waitlist WHERE squad = ?1 ORDER BY id",
+                )?;
+                let rows = statement.query_map(params![squad_id], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, i64>(2)?,
+                    ))
+                })?;
+                rows.collect::<rusqlite::Result<Vec<_>>>()?
+            };
+            let mut promoted = Vec::new();
+            for (id, user_id, seconds) in waiting {
+                let slot = slot(&tx, &squad_id, &user_id)?;
+                if !has_room(&tx, &squad_id, slot.as_deref())? {
+                    continue;
+                }
+                tx.execute(
+                    "INSERT OR REPLACE INTO members (squad, user, in_squad, expires)
+                    VALUES (?1, ?2, 1, ?3)",
+                    params![squad_id, user_id, now + seconds],
+                )?;
+                tx.execute(
+                    "INSERT OR IGNORE INTO squad_history_members (history, user, joined)
+                    VALUES ((SELECT history FROM squads WHERE id = ?1), ?2, ?3)",
+                    params![squad_id, user_id, now],
+                )?;
+                tx.execute("DELETE FROM waitlist WHERE id = ?1", params![id])?;
+                promoted.push(UserId(parse_id(user_id)?));
+            }
+            tx.commit()?;
+            Ok(promoted)
+        })
+        .await
+    }
+
+    async fn choose_slot(&self, squad_id: &str, user_id: &str, slot: &str) -> StoreResult<bool> {
+        let squad_id = squad_id.to_owned();
+        let user_id = user_id.to_owned();
+        let slot = slot.to_owned();
+        self.run(move |con| {
+            let tx = con.transaction()?;
+            if !matches!(squad_status(&tx, &squad_id)?, SquadStatus::Forming) {
+                return Ok(false);
+            }
+            if !composition(&tx, &squad_id)?
+                .iter()
+                .any(|(name, _)| *name == slot)
+            {
+                return Ok(false);
+            }
+            // Members moving to another slot need a free seat in it
+            let is_member: bool = tx.query_row(
+                "SELECT EXISTS (SELECT 1 FROM members WHERE squad = ?1 AND user = ?2 AND in_squad)",
+                params![squad_id, user_id],
+                |row| row.get(0),
+            )?;
+            let moving =
+                is_member && self::slot(&tx, &squad_id, &user_id)?.as_deref() != Some(&slot);
+            if moving && !has_seat(&tx, &squad_id, &slot)? {
+                return Ok(false);
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO slots (squad, user, slot) VALUES (?1, ?2, ?3)",
+                params![squad_id, user_id, slot],
+            )?;
+            tx.commit()?;
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn get_composition(&self, squad_id: &str) -> StoreResult<Composition> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| composition(con, &squad_id)).await
+    }
+
+    async fn get_slots(&self, squad_id: &str) -> StoreResult<HashMap<UserId, String>> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let mut statement = con.prepare("SELECT user, slot FROM slots WHERE squad = ?1")?;
+            let rows = statement.query_map(params![squad_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            let mut slots = HashMap::new();
+            for row in rows {
+                let (user_id, slot) = row?;
+                slots.insert(UserId(parse_id(user_id)?), slot);
+            }
+            Ok(slots)
+        })
+        .await
+    }
+
+    async fn set_origin(
+        &self,
+        squad_id: &str,
+        user_id: &str,
+        channel_id: ChannelId,
+    ) -> StoreResult<()> {
+        let squad_id = squad_id.to_owned();
+        let user_id = user_id.to_owned();
+        self.run(move |con| {
+            con.execute(
+                "INSERT OR REPLACE INTO origins (squad, user, channel)
+                SELECT ?1, ?2, ?3 WHERE EXISTS (SELECT 1 FROM squads WHERE id = ?1)",
+                params![squad_id, user_id, channel_id.as_u64().to_string()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_origins(&self, squad_id: &str) -> StoreResult<HashMap<UserId, ChannelId>> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let mut statement =
+                con.prepare("SELECT user, channel FROM origins WHERE squad = ?1")?;
+            let rows = statement.query_map(params![squad_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            let mut origins = HashMap::new();
+            for row in rows {
+                let (user_id, channel_id) = row?;
+                origins.insert(UserId(parse_id(user_id)?), ChannelId(parse_id(channel_id)?));
+            }
+            Ok(origins)
+        })
+        .await
+    }
+
+    async fn get_waitlist(&self, squad_id: &str) -> StoreResult<Vec<UserId>> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let mut statement =
+                con.prepare("SELECT user FROM waitlist WHERE squad = ?1 ORDER BY id")?;
+            let rows = statement.query_map(params![squad_id], |row| row.get::<_, String>(0))?;
+            let mut waitlist = Vec::new();
+            for row in rows {
+                waitlist.push(UserId(parse_id(row?)?));
+            }
+            Ok(waitlist)
+        })
+        .await
+    }
+
+    async fn get_capacity(&self, squad_id: &str) -> StoreResult<u8> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let capacity = con.query_row(
+                "SELECT capacity FROM squads WHERE id = ?1",
+                params![squad_id],
+                |row| row.get(0),
+            )?;
+            Ok(capacity)
+        })
+        .await
+    }
+
+    async fn get_members(&self, squad_id: &str) -> StoreResult<HashMap<UserId, u64>> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let mut statement =
+                con.prepare("SELECT user, expires FROM members WHERE squad = ?1 AND in_squad")?;
+            let rows = statement.query_map(params![squad_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?;
+            let mut members = HashMap::new();
+            for row in rows {
+                let (user_id, expires) = row?;
+                members.insert(UserId(parse_id(user_id)?), ttl(expires));
+            }
+            Ok(members)
+        })
+        .await
+    }
+
+    async fn get_squad_ttl(&self, squad_id: &str) -> StoreResult<u64> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let expires: i64 = con.query_row(
+                "SELECT expires FROM squads WHERE id = ?1",
+                params![squad_id],
+                |row| row.get(0),
+            )?;
+            Ok(ttl(expires))
+        })
+        .await
+    }
+
+    async fn get_postings(&self) -> StoreResult<HashMap<MessageId, ChannelId>> {
+        self.run(move |con| {
+            let mut statement = con.prepare("SELECT message, channel FROM postings")?;
+            let rows = statement.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            let mut postings = HashMap::new();
+            for row in rows {
+                let (message_id, channel_id) = row?;
+                postings.insert(
+                    MessageId(parse_id(message_id)?),
+                    ChannelId(parse_id(channel_id)?),
+                );
+            }
+            Ok(postings)
+        })
+        .await
+    }
+
+    async fn get_guild_squads(&self, guild_id: GuildId) -> StoreResult<Vec<String>> {
+        self.run(move |con| {
+            let mut statement =
+                con.prepare("SELECT DISTINCT squad FROM postings WHERE guild = ?1")?;
+            let squads = statement
+                .query_map(params![guild_id.as_u64().to_string()], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            Ok(squads)
+        })
+        .await
+    }
+
+    async fn get_user_squads(&self, user_id: UserId) -> StoreResult<Vec<String>> {
+        self.run(move |con| {
+            let mut statement = con.prepare(
+                "SELECT squad FROM members WHERE user = ?1 AND in_squad
+                UNION SELECT squad FROM waitlist WHERE user = ?1",
+            )?;
+            let squads = statement
+                .query_map(params![user_id.as_u64().to_string()], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            Ok(squads)
+        })
+        .await
+    }
+
+    async fn delete_posting(&self, message_id: &str) -> StoreResult<bool> {
+        let message_id = message_id.to_owned();
+        self.run(move |con| {
+            let tx = con.transaction()?;
+            let posting: Option<(String, String)> = tx
+                .query_row(
+                    "SELECT squad, channel FROM postings WHERE message = ?1",
+                    params![message_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            let (squad_id, channel_id) = match posting {
+                Some(posting) => posting,
+                None => return Ok(false),
+            };
+            tx.execute(
+                "DELETE FROM postings WHERE message = ?1",
+                params![message_id],
+            )?;
+            tx.execute(
+                "DELETE FROM channels WHERE squad = ?1 AND channel = ?2 AND NOT EXISTS
+                (SELECT 1 FROM postings WHERE squad = ?1 AND channel = ?2)",
+                params![squad_id, channel_id],
+            )?;
+            tx.commit()?;
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn get_squad_postings(
+        &self,
+        squad_id: &str,
+    ) -> StoreResult<HashMap<MessageId, ChannelId>> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let mut statement =
+                con.prepare("SELECT message, channel FROM postings WHERE squad = ?1")?;
+            let rows = statement.query_map(params![squad_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            let mut postings = HashMap::new();
+            for row in rows {
+                let (message_id, channel_id) = row?;
+                postings.insert(
+                    MessageId(parse_id(message_id)?),
+                    ChannelId(parse_id(channel_id)?),
+                );
+            }
+            Ok(postings)
+        })
+        .await
+    }
+
+    async fn get_full_squads(&self) -> StoreResult<Vec<String>> {
+        self.run(move |con| {
+            let mut statement = con.prepare(
+                "SELECT id FROM squads WHERE NOT filled AND IFNULL(start, 0) <= ?1 AND capacity <=
+                (SELECT COUNT(*) FROM members WHERE squad = squads.id AND in_squad)",
+            )?;
+            let full_squads = statement
+                .query_map(params![now()], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            Ok(full_squads)
+        })
+        .await
+    }
+
+    async fn set_quorum(&self, squad_id: &str, quorum: u8) -> StoreResult<()> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            con.execute(
+                "UPDATE squads SET quorum = ?2 WHERE id = ?1",
+                params![squad_id, quorum],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_quorum(&self, squad_id: &str) -> StoreResult<Option<u8>> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let quorum: Option<u8> = con
+                .query_row(
+                    "SELECT quorum FROM squads WHERE id = ?1",
+                    params![squad_id],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .flatten();
+            Ok(quorum)
+        })
+        .await
+    }
+
+    async fn set_link_policy(&self, squad_id: &str, policy: LinkPolicy) -> StoreResult<()> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            con.execute(
+                "UPDATE squads SET link_policy = ?2 WHERE id = ?1",
+                params![squad_id, policy.as_str()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_link_policy(&self, squad_id: &str) -> StoreResult<LinkPolicy> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let policy: Option<String> = con
+                .query_row(
+                    "SELECT link_policy FROM squads WHERE id = ?1",
+                    params![squad_id],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .flatten();
+            Ok(policy.map_or_else(LinkPolicy::default, |policy| LinkPolicy::parse(&policy)))
+        })
+        .await
+    }
+
+    async fn ready_squad(&self, squad_id: &str) -> StoreResult<bool> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let ready = con.execute(
+                "UPDATE squads SET ready = 1
+                WHERE id = ?1 AND NOT filled AND NOT ready AND IFNULL(start, 0) <= ?2
+                AND quorum <= (SELECT COUNT(*) FROM members WHERE squad = ?1 AND in_squad)",
+                params![squad_id, now()],
+            )?;
+            Ok(ready > 0)
+        })
+        .await
+    }
+
+    async fn is_ready(&self, squad_id: &str) -> StoreResult<bool> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let ready: Option<bool> = con
+                .query_row(
+                    "SELECT ready FROM squads WHERE id = ?1",
+                    params![squad_id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(ready.unwrap_or(false))
+        })
+        .await
+    }
+
+    async fn fill_squad(&self, squad_id: &str) -> StoreResult<bool> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let tx = con.transaction()?;
+            let filled = tx.execute(
+                "UPDATE squads SET filled = 1
+                WHERE id = ?1 AND NOT filled AND IFNULL(start, 0) <= ?2 AND capacity <=
+                (SELECT COUNT(*) FROM members WHERE squad = ?1 AND in_squad)",
+                params![squad_id, now()],
+            )?;
+            if filled == 0 {
+                return Ok(false);
+            }
+            tx.execute(
+                "UPDATE squad_history SET filled = ?2
+                WHERE id = (SELECT history FROM squads WHERE id = ?1)",
+                params![squad_id, now()],
+            )?;
+            tx.commit()?;
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn close_squad(&self, squad_id: &str) -> StoreResult<bool> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let tx = con.transaction()?;
+            let closed = tx.execute(
+                "UPDATE squads SET filled = 1 WHERE id = ?1 AND NOT filled AND EXISTS
+                (SELECT 1 FROM members WHERE squad = ?1 AND in_squad)",
+                params![squad_id],
+            )?;
+            if closed == 0 {
+                return Ok(false);
+            }
+            tx.execute(
+                "UPDATE squad_history SET filled = ?2
+                WHERE id = (SELECT history FROM squads WHERE id = ?1)",
+                params![squad_id, now()],
+            )?;
+            tx.commit()?;
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn cancel_squad(&self, squad_id: &str) -> StoreResult<bool> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let tx = con.transaction()?;
+            let cancelled = tx.execute(
+                "UPDATE squads SET filled = 2 WHERE id = ?1 AND NOT filled",
+                params![squad_id],
+            )?;
+            if cancelled == 0 {
+                return Ok(false);
+            }
+            tx.execute(
+                "UPDATE squad_history SET cancelled = ?2
+                WHERE id = (SELECT history FROM squads WHERE id = ?1)",
+                params![squad_id, now()],
+            )?;
+            tx.commit()?;
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn extend_squad(&self, squad_id: &str, seconds: u64) -> StoreResult<bool> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let seconds = seconds as i64;
+            let tx = con.transaction()?;
+            let extended = tx.execute(
+                "UPDATE squads SET expires = expires + ?2 WHERE id = ?1 AND NOT filled",
+                params![squad_id, seconds],
+            )?;
+            if extended == 0 {
+                return Ok(false);
+            }
+            tx.execute(
+                "UPDATE postings SET expires = expires + ?2 WHERE squad = ?1",
+                params![squad_id, seconds],
+            )?;
+            tx.execute(
+                "UPDATE channels SET expires = expires + ?2 WHERE squad = ?1",
+                params![squad_id, seconds],
+            )?;
+            tx.commit()?;
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn get_owner(&self, squad_id: &str) -> StoreResult<Option<UserId>> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let owner: Option<String> = con
+                .query_row(
+                    "SELECT owner FROM squads WHERE id = ?1",
+                    params![squad_id],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .flatten();
+            match owner {
+                Some(owner) => Ok(Some(UserId(parse_id(owner)?))),
+                None => Ok(None),
+            }
+        })
+        .await
+    }
+
+    async fn get_start(&self, squad_id: &str) -> StoreResult<Option<i64>> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let start: Option<i64> = con
+                .query_row(
+                    "SELECT start FROM squads WHERE id = ?1",
+                    params![squad_id],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .flatten();
+            Ok(start)
+        })
+        .await
+    }
+
+    async fn get_squad_status(&self, squad_id: &str) -> StoreResult<SquadStatus> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| Ok(squad_status(con, &squad_id)?)).await
+    }
+
+    async fn get_role_id(&self, message_id: &str) -> StoreResult<Option<RoleId>> {
+        let message_id = message_id.to_owned();
+        self.run(move |con| {
+            let role: Option<String> = con
+                .query_row(
+                    "SELECT role FROM postings WHERE message = ?1",
+                    params![message_id],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .flatten();
+            match role {
+                Some(role) => Ok(Some(RoleId(parse_id(role)?))),
+                None => Ok(None),
+            }
+        })
+        .await
+    }
+
+    async fn get_channels(&self, squad_id: &str) -> StoreResult<Vec<ChannelId>> {
+        let squad_id = squad_id.to_owned();
+        self.run(move |con| {
+            let mut statement = con.prepare("SELECT channel FROM channels WHERE squad = ?1")?;
+            let rows = statement.query_map(params![squad_id], |row| row.get::<_, String>(0))?;
+            let mut channels = Vec::new();
+            for row in rows {
+                channels.push(ChannelId(parse_id(row?)?));
+            }
+            Ok(channels)
+        })
+        .await
+    }
+
+    async fn set_rooms(&self, message_id: &str, rooms: &Rooms) -> StoreResult<()> {
+        let message_id = message_id.to_owned();
+        let rooms = *rooms;
+        self.run(move |con| {
+            let voice = rooms.voice.map(|channel| channel.as_u64().to_string());
+            let thread = rooms.thread.map(|channel| channel.as_u64().to_string());
+            let tx = con.transaction()?;
+            let updated = tx.execute(
+                "UPDATE postings SET voice = ?2, thread = ?3 WHERE message = ?1",
+                params![message_id, voice, thread],
+            )?;
+            if updated == 0 {
+                return Err(SquadError::UnknownSquad);
+            }
+            if let Some(voice) = voice {
+                tx.execute(
+                    "INSERT OR IGNORE INTO voice_channels (channel) VALUES (?1)",
+                    params![voice],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_rooms(&self, message_id: &str) -> StoreResult<Rooms> {
+        let message_id = message_id.to_owned();
+        self.run(move |con| {
+            let rooms: Option<(Option<String>, Option<String>)> = con
+                .query_row(
+                    "SELECT voice, thread FROM postings WHERE message = ?1",
+                    params![message_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            let (voice, thread) = rooms.unwrap_or_default();
+            Ok(Rooms {
+                voice: voice.map(parse_id).transpose()?.map(ChannelId),
+                thread: thread.map(parse_id).transpose()?.map(ChannelId),
+            })
+        })
+        .await
+    }
+
+    async fn set_event(
+        &self,
+        message_id: &str,
+        event_id: Option<ScheduledEventId>,
+    ) -> StoreResult<()> {
+        let message_id = message_id.to_owned();
+        self.run(move |con| {
+            let event = event_id.map(|event| event.as_u64().to_string());
+            let updated = con.execute(
+                "UPDATE postings SET event = ?2 WHERE message = ?1",
+                params![message_id, event],
+            )?;
+            match updated {
+                0 => Err(SquadError::UnknownSquad),
+                _ => Ok(()),
+            }
+        })
+        .await
+    }
+
+    async fn get_event(&self, message_id: &str) -> StoreResult<Option<ScheduledEventId>> {
+        let message_id = message_id.to_owned();
+        self.run(move |con| {
+            let event: Option<String> = con
+                .query_row(
+                    "SELECT event FROM postings WHERE message = ?1",
+                    params![message_id],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .flatten();
+            Ok(event.map(parse_id).transpose()?.map(ScheduledEventId))
+        })
+        .await
+    }
+
+    async fn get_voice_channels(&self) -> StoreResult<Vec<ChannelId>> {
+        self.run(move |con| {
+            let mut statement = con.prepare("SELECT channel FROM voice_channels")?;
+            let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+            let mut channels = Vec::new();
+            for row in rows {
+                channels.push(ChannelId(parse_id(row?)?));
+            }
+            Ok(channels)
+        })
+        .await
+    }
+
+    async fn delete_voice_channel(&self, channel_id: ChannelId) -> StoreResult<()> {
+        self.run(move |con| {
+            con.execute(
+                "DELETE FROM voice_channels WHERE channel = ?1",
+                params![channel_id.as_u64().to_string()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_guild_settings(&self, guild_id: GuildId) -> StoreResult<GuildSettings> {
+        self.run(move |con| {
+            let settings = con
+                .query_row(
+                    "SELECT default_size, min_size, max_size, default_duration, max_duration,
+                        announce_channel, colour, creator_roles, dms, voice_category, threads,
+                        voice_cleanup, events, reminders, availability, locale
+                    FROM guild_settings WHERE guild = ?1",
+                    params![guild_id.as_u64().to_string()],
+                    |row| {
+                        let announce_channel: Option<String> = row.get(5)?;
+                        let creator_roles: Option<String> = row.get(7)?;
+                        let voice_category: Option<String> = row.get(9)?;
+                        let availability: Option<String> = row.get(14)?;
+                        let locale: Option<String> = row.get(15)?;
+                        Ok(GuildSettings {
+                            default_size: row.get(0)?,
+                            min_size: row.get(1)?,
+                            max_size: row.get(2)?,
+                            default_duration: row.get(3)?,
+                            max_duration: row.get(4)?,
+                            announce_channel: announce_channel
+                                .and_then(|channel| channel.parse().ok())
+                                .map(ChannelId),
+                            colour: row.get(6)?,
+                            creator_roles: creator_roles
+                                .as_deref()
+                                .map_or_else(Vec::new, parse_roles),
+                            dms: row.get(8)?,
+                            voice_category: voice_category
+                                .and_then(|channel| channel.parse().ok())
+                                .map(ChannelId),
+                            threads: row.get(10)?,
+                            voice_cleanup: row.get(11)?,
+                            events: row.get(12)?,
+                            reminders: row.get(13)?,
+                            availability: availability
+                                .as_deref()
+                                .map_or_else(Vec::new, parse_availability),
+                            locale: locale.as_deref().and_then(Locale::parse),
+                        })
+                    },
+                )
+                .optional()?;
+            Ok(settings.unwrap_or_default())
+        })
+        .await
+    }
+
+    async fn set_guild_settings(
+        &self,
+        guild_id: GuildId,
+        settings: &GuildSettings,
+    ) -> StoreResult<()> {
+        let settings = settings.clone();
+        self.run(move |con| {
+            con.execute(
+                "INSERT OR REPLACE INTO guild_settings (guild, default_size, min_size, max_size,
+                    default_duration, max_duration, announce_channel, colour, creator_roles, dms,
+                    voice_category, threads, voice_cleanup, events, reminders, availability,
+                    locale)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+                params![
+                    guild_id.as_u64().to_string(),
+                    settings.default_size,
+                    settings.min_size,
+                    settings.max_size,
+                    settings.default_duration,
+                    settings.max_duration,
+                    settings
+                        .announce_channel
+                        .map(|channel| channel.as_u64().to_string()),
+                    settings.colour,
+                    format_roles(&settings.creator_roles),
+                    settings.dms,
+                    settings
+                        .voice_category
+                        .map(|channel| channel.as_u64().to_string()),
+                    settings.threads,
+                    settings.voice_cleanup,
+                    settings.events,
+                    settings.reminders,
+                    format_availability(&settings.availability),
+                    settings.locale.map(|locale| locale.as_str())
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
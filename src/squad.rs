@@ -1,36 +1,60 @@
 use crate::embed;
-use crate::redis_io;
+use crate::error::SquadError;
+use crate::events;
+use crate::links;
+use crate::locale;
+use crate::locale::Text;
+use crate::notify;
+use crate::rooms;
+use crate::store;
+use crate::store::{Composition, GuildSettings, JoinResult, LinkPolicy, SquadStatus, SquadStore};
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
 use rand::Rng;
-use serenity::model::id::RoleId;
+use serenity::model::id::{ChannelId, GuildId, MessageId, RoleId, UserId};
 use serenity::model::interactions::application_command::{
     ApplicationCommand, ApplicationCommandInteraction, ApplicationCommandInteractionDataOption,
     ApplicationCommandInteractionDataOptionValue, ApplicationCommandOptionType,
 };
+use serenity::model::interactions::autocomplete::AutocompleteInteraction;
+use serenity::model::interactions::message_component::ActionRowComponent;
+use serenity::model::interactions::modal::ModalSubmitInteraction;
 use serenity::model::interactions::InteractionResponseType;
 use serenity::model::prelude::message_component::MessageComponentInteraction;
 use serenity::model::prelude::Message;
 use serenity::prelude::Context;
-use serenity::Error;
 use serenity::prelude::Mentionable;
-use std::error::Error as StdError;
+use serenity::Error;
+
+/// Furthest ahead in seconds that a squad can be scheduled.
+const MAX_START_SECONDS: i64 = 7 * 24 * 60 * 60;
+/// Shortest duration in seconds that a squad can be given.
+pub const MIN_DURATION_SECONDS: u64 = 5 * 60;
+
+/// Get the options given to the subcommand of a /squad command
+fn subcommand_options(
+    command: &ApplicationCommandInteraction,
+) -> &[ApplicationCommandInteractionDataOption] {
+    match command.data.options.first() {
+        Some(subcommand) => &subcommand.options,
+        None => &[],
+    }
+}
 
-/// Get squad size argument from /squad command
+/// Get squad size argument from /squad create command
 async fn parse_squad_size(
     command: &ApplicationCommandInteraction,
-) -> Result<Option<u8>, Box<dyn StdError>> {
-    let options: Vec<&ApplicationCommandInteractionDataOption> = command
-        .data
-        .options
+) -> Result<Option<u8>, SquadError> {
+    let options: Vec<&ApplicationCommandInteractionDataOption> = subcommand_options(command)
         .iter()
         .filter(|opt| opt.name == "size")
         .collect();
 
-    let option = options.get(0);
+    let option = options.first();
 
     let option = match option {
         Some(opt) => opt,
         None => {
-            return Ok(Some(5));
+            return Ok(None);
         }
     };
 
@@ -39,33 +63,80 @@ async fn parse_squad_size(
     let option = match option {
         Some(opt) => opt,
         None => {
-            return Ok(Some(5));
+            return Ok(None);
         }
     };
 
     let size = match option {
         ApplicationCommandInteractionDataOptionValue::Integer(size) => size,
         _ => {
-            return Err("Unable to parse size.".into());
+            return Err(SquadError::unparsable("size"));
+        }
+    };
+
+    Ok(Some(option_in_range("size", *size, store::SIZE_RANGE)?))
+}
+
+/// Get squad minimum argument from /squad create command
+async fn parse_squad_min(
+    command: &ApplicationCommandInteraction,
+) -> Result<Option<u8>, SquadError> {
+    let options: Vec<&ApplicationCommandInteractionDataOption> = subcommand_options(command)
+        .iter()
+        .filter(|opt| opt.name == "min")
+        .collect();
+
+    let option = match options.first().and_then(|opt| opt.resolved.as_ref()) {
+        Some(opt) => opt,
+        None => {
+            return Ok(None);
+        }
+    };
+
+    let min = match option {
+        ApplicationCommandInteractionDataOptionValue::Integer(min) => min,
+        _ => {
+            return Err(SquadError::unparsable("minimum"));
+        }
+    };
+
+    Ok(Some(option_in_range("min", *min, store::SIZE_RANGE)?))
+}
+
+/// Get link policy argument from /squad create and /squad links commands
+pub async fn parse_link_policy(
+    command: &ApplicationCommandInteraction,
+) -> Result<Option<LinkPolicy>, SquadError> {
+    let options: Vec<&ApplicationCommandInteractionDataOption> = subcommand_options(command)
+        .iter()
+        .filter(|opt| opt.name == "links")
+        .collect();
+
+    let option = match options.first().and_then(|opt| opt.resolved.as_ref()) {
+        Some(opt) => opt,
+        None => {
+            return Ok(None);
         }
     };
 
-    let size = u8::try_from(*size)?;
-    Ok(Some(size))
+    match option {
+        ApplicationCommandInteractionDataOptionValue::String(policy) => {
+            Ok(Some(LinkPolicy::parse(policy)))
+        }
+        _ => Err(SquadError::unparsable("links")),
+    }
 }
 
-/// Get squad role argument from /squad command
+/// Get squad role argument from /squad create command
 async fn parse_squad_role(
     command: &ApplicationCommandInteraction,
-) -> Result<Option<RoleId>, Box<dyn StdError>> {
-    let options: Vec<&ApplicationCommandInteractionDataOption> = command
-        .data
-        .options
+) -> Result<Option<RoleId>, SquadError> {
+    let options: Vec<&ApplicationCommandInteractionDataOption> = subcommand_options(command)
         .iter()
         .filter(|opt| opt.name == "role")
         .collect();
 
-    let option = options.get(0);
+    let option = options.first();
 
     let option = match option {
         Some(opt) => opt,
@@ -86,25 +157,23 @@ async fn parse_squad_role(
     let role = match option {
         ApplicationCommandInteractionDataOptionValue::Role(role) => role,
         _ => {
-            return Err("Unable to parse role.".into());
+            return Err(SquadError::unparsable("role"));
         }
     };
 
     Ok(Some(role.id))
 }
 
-/// Get squad role argument from /squad command
+/// Get squad id argument from /squad create, join, leave, info or extend command
 async fn parse_squad_id(
     command: &ApplicationCommandInteraction,
-) -> Result<Option<String>, Box<dyn StdError>> {
-    let options: Vec<&ApplicationCommandInteractionDataOption> = command
-        .data
-        .options
+) -> Result<Option<String>, SquadError> {
+    let options: Vec<&ApplicationCommandInteractionDataOption> = subcommand_options(command)
         .iter()
         .filter(|opt| opt.name == "id")
         .collect();
 
-    let option = options.get(0);
+    let option = options.first();
 
     let option = match option {
         Some(opt) => opt,
@@ -123,139 +192,1782 @@ async fn parse_squad_id(
     };
 
     let id: String = match option {
-        ApplicationCommandInteractionDataOptionValue::String(id) => id.to_string(),
+        ApplicationCommandInteractionDataOptionValue::String(id) => normalize_squad_id(id),
         _ => {
-            return Err("Unable to parse id.".into());
+            return Err(SquadError::unparsable("id"));
         }
     };
 
     Ok(Some(id))
 }
 
-/// Create initial squad posting
+/// Read a squad id as shown in the posting footer, also accepting it without the
+/// "squad:" prefix
+fn normalize_squad_id(input: &str) -> String {
+    let id = input.trim();
+    match id.starts_with("squad:") {
+        true => id.to_string(),
+        false => format!("squad:{}", id),
+    }
+}
+
+/// Get hours argument from /squad join or extend command
+async fn parse_squad_hours(
+    command: &ApplicationCommandInteraction,
+) -> Result<Option<u8>, SquadError> {
+    let options: Vec<&ApplicationCommandInteractionDataOption> = subcommand_options(command)
+        .iter()
+        .filter(|opt| opt.name == "hours")
+        .collect();
+
+    let option = options.first();
+
+    let option = match option {
+        Some(opt) => opt,
+        None => {
+            return Ok(None);
+        }
+    };
+
+    let option = option.resolved.as_ref();
+
+    let option = match option {
+        Some(opt) => opt,
+        None => {
+            return Ok(None);
+        }
+    };
+
+    let hours = match option {
+        ApplicationCommandInteractionDataOptionValue::Integer(hours) => hours,
+        _ => {
+            return Err(SquadError::unparsable("hours"));
+        }
+    };
+
+    Ok(Some(option_in_range("hours", *hours, store::HOURS_RANGE)?))
+}
+
+/// Convert the value of an integer option, rejecting values outside the given range
+fn option_in_range(option: &str, value: i64, range: (u8, u8)) -> Result<u8, SquadError> {
+    let (min, max) = range;
+    u8::try_from(value)
+        .ok()
+        .filter(|value| (min..=max).contains(value))
+        .ok_or_else(|| SquadError::invalid_with(|text| text.option_range, &[&option, &min, &max]))
+}
+
+/// Get role slot argument from /squad join command
+async fn parse_squad_slot(
+    command: &ApplicationCommandInteraction,
+) -> Result<Option<String>, SquadError> {
+    let options: Vec<&ApplicationCommandInteractionDataOption> = subcommand_options(command)
+        .iter()
+        .filter(|opt| opt.name == "slot")
+        .collect();
+
+    let option = options.first();
+
+    let option = match option {
+        Some(opt) => opt,
+        None => {
+            return Ok(None);
+        }
+    };
+
+    let option = option.resolved.as_ref();
+
+    let option = match option {
+        Some(opt) => opt,
+        None => {
+            return Ok(None);
+        }
+    };
+
+    let slot = match option {
+        ApplicationCommandInteractionDataOptionValue::String(slot) => slot.trim().to_lowercase(),
+        _ => {
+            return Err(SquadError::unparsable("slot"));
+        }
+    };
+
+    Ok(Some(slot))
+}
+
+/// Get squad start time argument from /squad create command as a unix timestamp
+async fn parse_squad_start(
+    command: &ApplicationCommandInteraction,
+) -> Result<Option<i64>, SquadError> {
+    let options: Vec<&ApplicationCommandInteractionDataOption> = subcommand_options(command)
+        .iter()
+        .filter(|opt| opt.name == "start")
+        .collect();
+
+    let option = options.first();
+
+    let option = match option {
+        Some(opt) => opt,
+        None => {
+            return Ok(None);
+        }
+    };
+
+    let option = option.resolved.as_ref();
+
+    let option = match option {
+        Some(opt) => opt,
+        None => {
+            return Ok(None);
+        }
+    };
+
+    let input = match option {
+        ApplicationCommandInteractionDataOptionValue::String(input) => input,
+        _ => {
+            return Err(SquadError::unparsable("start"));
+        }
+    };
+
+    let start = parse_start_time(input, Utc::now().timestamp())?;
+    Ok(Some(start))
+}
+
+/// Get squad composition argument from /squad create command, empty if none was given
+async fn parse_squad_composition(
+    command: &ApplicationCommandInteraction,
+) -> Result<Composition, SquadError> {
+    let options: Vec<&ApplicationCommandInteractionDataOption> = subcommand_options(command)
+        .iter()
+        .filter(|opt| opt.name == "slots")
+        .collect();
+
+    let option = options.first();
+
+    let option = match option {
+        Some(opt) => opt,
+        None => {
+            return Ok(Vec::new());
+        }
+    };
+
+    let option = option.resolved.as_ref();
+
+    let option = match option {
+        Some(opt) => opt,
+        None => {
+            return Ok(Vec::new());
+        }
+    };
+
+    let input = match option {
+        ApplicationCommandInteractionDataOptionValue::String(input) => input,
+        _ => {
+            return Err(SquadError::unparsable("slots"));
+        }
+    };
+
+    let composition = store::parse_composition(input)?;
+    let capacity: u32 = composition.iter().map(|(_, seats)| u32::from(*seats)).sum();
+    if capacity > u32::from(store::SIZE_RANGE.1) {
+        let max = store::SIZE_RANGE.1;
+        return Err(SquadError::invalid_with(
+            |text| text.too_many_members,
+            &[&max],
+        ));
+    }
+    Ok(composition)
+}
+
+/// Get squad duration argument from /squad create command in seconds
+async fn parse_squad_duration(
+    command: &ApplicationCommandInteraction,
+) -> Result<Option<u64>, SquadError> {
+    let options: Vec<&ApplicationCommandInteractionDataOption> = subcommand_options(command)
+        .iter()
+        .filter(|opt| opt.name == "duration")
+        .collect();
+
+    let option = options.first();
+
+    let option = match option {
+        Some(opt) => opt,
+        None => {
+            return Ok(None);
+        }
+    };
+
+    let option = option.resolved.as_ref();
+
+    let option = match option {
+        Some(opt) => opt,
+        None => {
+            return Ok(None);
+        }
+    };
+
+    let input = match option {
+        ApplicationCommandInteractionDataOptionValue::String(input) => input,
+        _ => {
+            return Err(SquadError::unparsable("duration"));
+        }
+    };
+
+    let seconds = parse_duration(&input.to_lowercase())?;
+    Ok(Some(seconds as u64))
+}
+
+/// Check a requested squad size against the size range of the guild
+fn check_size(capacity: u8, settings: &GuildSettings) -> Result<u8, SquadError> {
+    if capacity < settings.min_size() || capacity > settings.max_size() {
+        return Err(SquadError::invalid_with(
+            |text| text.size_range,
+            &[&settings.min_size(), &settings.max_size()],
+        ));
+    }
+    Ok(capacity)
+}
+
+/// Check whether the user of a command may create squads in the guild. Members with
+/// the Manage Server permission always can.
+fn can_create(command: &ApplicationCommandInteraction, settings: &GuildSettings) -> bool {
+    if settings.creator_roles.is_empty() {
+        return true;
+    }
+    match &command.member {
+        Some(member) => {
+            let manager = member
+                .permissions
+                .is_some_and(|permissions| permissions.manage_guild());
+            manager
+                || member
+                    .roles
+                    .iter()
+                    .any(|role| settings.creator_roles.contains(role))
+        }
+        None => true,
+    }
+}
+
+/// Check a requested squad duration against the limits of the guild
+fn check_duration(duration: u64, settings: &GuildSettings) -> Result<u64, SquadError> {
+    if duration < MIN_DURATION_SECONDS {
+        return Err(SquadError::invalid(|text| text.too_short));
+    }
+    if duration > settings.max_duration() {
+        let max = embed::format_ttl(settings.max_duration());
+        return Err(SquadError::invalid_with(|text| text.too_long, &[&max]));
+    }
+    Ok(duration)
+}
+
+/// Parse a start time into a unix timestamp. Accepts a Discord timestamp
+/// (<t:1700000000:F>), a unix timestamp, a relative time ("in 2h", "in 1h30m"), a date
+/// and time ("2024-05-01 21:00") or a time of day ("9pm", "tonight 9:30pm",
+/// "tomorrow 21:00"). Dates and times are read as UTC unless followed by an offset
+/// such as "utc+2" or "-05:00". A time of day that has already passed today is taken
+/// to mean tomorrow.
+pub fn parse_start_time(input: &str, now: i64) -> Result<i64, SquadError> {
+    let input = input.trim().to_lowercase();
+    let start = if let Some(timestamp) = input.strip_prefix("<t:") {
+        let timestamp = timestamp.trim_end_matches('>');
+        let timestamp = timestamp.split(':').next().unwrap_or_default();
+        timestamp
+            .parse()
+            .map_err(|_| SquadError::invalid(|text| text.invalid_start))?
+    } else if let Ok(timestamp) = input.parse::<i64>() {
+        timestamp
+    } else if let Some(duration) = input.strip_prefix("in ") {
+        // Far off starts are turned down below rather than overflowing
+        now.saturating_add(parse_duration(duration)?)
+    } else {
+        parse_date_time(&input, now)?
+    };
+    if start <= now {
+        return Err(SquadError::invalid(|text| text.start_in_past));
+    }
+    if start - now > MAX_START_SECONDS {
+        return Err(SquadError::invalid(|text| text.start_too_far));
+    }
+    Ok(start)
+}
+
+/// Parse a duration such as "2h", "1h30m" or "45m" into seconds. Durations too long
+/// to count in seconds fail like any other invalid duration.
+pub fn parse_duration(input: &str) -> Result<i64, SquadError> {
+    let invalid = || SquadError::invalid(|text| text.invalid_duration);
+    let mut seconds: i64 = 0;
+    let mut number = String::new();
+    for c in input.chars().filter(|c| !c.is_whitespace()) {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let value: i64 = number.parse().map_err(|_| invalid())?;
+        number.clear();
+        let unit = match c {
+            'd' => 24 * 60 * 60,
+            'h' => 60 * 60,
+            'm' => 60,
+            _ => return Err(invalid()),
+        };
+        seconds = value
+            .checked_mul(unit)
+            .and_then(|value| seconds.checked_add(value))
+            .ok_or_else(invalid)?;
+    }
+    if !number.is_empty() || seconds == 0 {
+        return Err(invalid());
+    }
+    Ok(seconds)
+}
+
+/// Parse the time a user is available until, given in the availability form, into
+/// the number of seconds from now. Accepts a time as for start times, optionally
+/// preceded by "until" ("11:30pm", "until 23:00 utc+2", "tomorrow 1am"), or a
+/// duration ("1h45m").
+pub fn parse_available_until(input: &str, now: i64) -> Result<u32, SquadError> {
+    let input = input.trim().to_lowercase();
+    // "until" may also be given in Spanish or Portuguese
+    let input = ["until", "hasta", "até"]
+        .iter()
+        .find_map(|word| input.strip_prefix(word))
+        .unwrap_or(&input)
+        .trim();
+    let seconds = match parse_duration(input) {
+        Ok(seconds) => seconds,
+        Err(_) => {
+            let until = parse_start_time(input, now)
+                .map_err(|_| SquadError::invalid(|text| text.invalid_availability))?;
+            until - now
+        }
+    };
+    if seconds > store::MAX_SQUAD_TTL as i64 {
+        return Err(SquadError::invalid(|text| text.availability_too_long));
+    }
+    Ok(u32::try_from(seconds)?)
+}
+
+/// Parse a UTC offset such as "utc+2", "+02:00" or "-0500" into seconds
+fn parse_offset(input: &str) -> Option<i64> {
+    let input = input
+        .strip_prefix("utc")
+        .or_else(|| input.strip_prefix("gmt"))
+        .unwrap_or(input);
+    if input.is_empty() {
+        return Some(0);
+    }
+    let (sign, input) = match input.split_at(1) {
+        ("+", rest) => (1, rest),
+        ("-", rest) => (-1, rest),
+        _ => return None,
+    };
+    let (hours, minutes) = match input.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if input.len() > 2 => input.split_at(input.len() - 2),
+        None => (input, "0"),
+    };
+    let hours: i64 = hours.parse().ok()?;
+    let minutes: i64 = minutes.parse().ok()?;
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes) * 60)
+}
+
+/// Parse a time of day such as "9pm", "9:30pm" or "21:00" into hours and minutes.
+/// Times without am/pm in the evening ("tonight 9") are read as pm.
+fn parse_time_of_day(input: &str, evening: bool) -> Option<(u32, u32)> {
+    let (input, meridiem) = if let Some(time) = input.strip_suffix("am") {
+        (time, Some(0))
+    } else if let Some(time) = input.strip_suffix("pm") {
+        (time, Some(12))
+    } else {
+        (input, None)
+    };
+    let (hours, minutes) = input.split_once(':').unwrap_or((input, "0"));
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    if minutes > 59 {
+        return None;
+    }
+    let hours = match meridiem {
+        Some(meridiem) if (1..=12).contains(&hours) => hours % 12 + meridiem,
+        Some(_) => return None,
+        None if evening && hours < 12 => hours + 12,
+        None if hours < 24 => hours,
+        None => return None,
+    };
+    Some((hours, minutes))
+}
+
+/// Parse an absolute date and time, or a time of day optionally preceded by today,
+/// tonight or tomorrow, into a unix timestamp
+fn parse_date_time(input: &str, now: i64) -> Result<i64, SquadError> {
+    let invalid = || SquadError::invalid(|text| text.invalid_start);
+    // Split "2024-05-01t21:00" and "9 pm" into the same tokens as "2024-05-01 21:00pm"
+    let mut tokens: Vec<String> = Vec::new();
+    for token in input.split_whitespace() {
+        match token {
+            "am" | "pm" if !tokens.is_empty() => {
+                let last = tokens.len() - 1;
+                tokens[last].push_str(token);
+            }
+            _ => match token.split_once('t') {
+                Some((date, time)) if date.len() == 10 && date.contains('-') => {
+                    tokens.push(date.to_string());
+                    tokens.push(time.to_string());
+                }
+                _ => tokens.push(token.to_string()),
+            },
+        }
+    }
+    // An offset may trail the time, e.g. "9pm utc+2" or "21:00 -05:00"
+    let mut offset = 0;
+    if tokens.len() > 1 {
+        if let Some(parsed) = tokens.last().and_then(|token| parse_offset(token)) {
+            offset = parsed;
+            tokens.pop();
+        }
+    }
+    let (day, time) = match tokens.as_slice() {
+        [time] => (None, time),
+        [day, time] => (Some(day.as_str()), time),
+        _ => return Err(invalid()),
+    };
+    let local_now = NaiveDateTime::from_timestamp_opt(now + offset, 0).ok_or_else(invalid)?;
+    let today = local_now.date();
+    let date = match day {
+        None | Some("today") | Some("tonight") => today,
+        Some("tomorrow") => today + Duration::days(1),
+        Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| invalid())?,
+    };
+    let (hours, minutes) = parse_time_of_day(time, day == Some("tonight")).ok_or_else(invalid)?;
+    let start = date.and_hms_opt(hours, minutes, 0).ok_or_else(invalid)?;
+    let mut start = start.timestamp() - offset;
+    // A bare time of day that has already passed means the same time tomorrow
+    if day.is_none() && start <= now {
+        start += 24 * 60 * 60;
+    }
+    Ok(start)
+}
+
+/// Create initial squad posting for a squad in the store
 async fn respond_squad_command(
     ctx: &Context,
     command: &ApplicationCommandInteraction,
-    squad_id: &String,
-    capacity: u8,
+    store: &dyn SquadStore,
+    squad_id: &str,
     role_id: Option<RoleId>,
-) -> Result<Message, Error> {
+    settings: &GuildSettings,
+) -> Result<Message, SquadError> {
+    let capacity = store.get_capacity(squad_id).await?;
+    let notify_at = store.get_quorum(squad_id).await?.unwrap_or(capacity);
+    let start = store.get_start(squad_id).await?;
+    let composition = store.get_composition(squad_id).await?;
     if let Some(role) = role_id {
-        command.channel_id.say(&ctx.http, format!("Squad forming! {}", role.mention())).await?;
+        let text = settings.locale().text();
+        let message = locale::fill(text.squad_forming, &[&role.mention()]);
+        command.channel_id.say(&ctx.http, message).await?;
     }
     command
         .create_interaction_response(&ctx.http, |response| {
             response
                 .kind(InteractionResponseType::ChannelMessageWithSource)
-                .interaction_response_data(|m| embed::build_embed(m, &squad_id, capacity, role_id))
+                .interaction_response_data(|m| {
+                    embed::build_embed(
+                        m,
+                        squad_id,
+                        notify_at,
+                        role_id,
+                        start,
+                        &composition,
+                        settings,
+                    )
+                })
         })
         .await?;
-    command.get_interaction_response(&ctx.http).await
+    Ok(command.get_interaction_response(&ctx.http).await?)
+}
+
+/// Post a squad in reply to /squad create, record the posting and announce it. Returns
+/// the message id of the posting. A posting that can't be recorded is deleted again so
+/// that it doesn't take members the squad never sees.
+async fn post_squad(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    store: &dyn SquadStore,
+    squad_id: &str,
+    role_id: Option<RoleId>,
+    settings: &GuildSettings,
+    ttl: u64,
+) -> Result<String, SquadError> {
+    let response = respond_squad_command(ctx, command, store, squad_id, role_id, settings).await?;
+    let channel_id = command.channel_id.as_u64().to_string();
+    let message_id = response.id.as_u64().to_string();
+    let recorded = store
+        .build_posting(
+            &channel_id,
+            command.guild_id,
+            &message_id,
+            role_id,
+            squad_id,
+            ttl,
+        )
+        .await;
+    if let Err(why) = recorded {
+        if let Err(why) = command
+            .delete_original_interaction_response(&ctx.http)
+            .await
+        {
+            eprintln!("Error deleting unrecorded squad posting: {}", why);
+        }
+        return Err(why);
+    }
+    let (user_id, channel_id) = (command.user.id, command.channel_id);
+    announce_squad(
+        ctx,
+        settings,
+        user_id,
+        channel_id,
+        command.guild_id,
+        &response,
+    )
+    .await;
+    Ok(message_id)
+}
+
+/// Link a new squad posting, posted by the given user in the given channel, in the
+/// announcement channel of the guild, unless it was posted there. Announcements that
+/// fail to send are logged and skipped.
+pub async fn announce_squad(
+    ctx: &Context,
+    settings: &GuildSettings,
+    user_id: UserId,
+    posted_in: ChannelId,
+    guild_id: Option<GuildId>,
+    response: &Message,
+) {
+    let channel_id = match settings.announce_channel {
+        Some(channel_id) if channel_id != posted_in => channel_id,
+        _ => return,
+    };
+    let link = response.id.link(posted_in, guild_id);
+    let message = locale::fill(
+        settings.locale().text().announce,
+        &[&user_id.mention(), &posted_in.mention(), &link],
+    );
+    let result = channel_id.say(&ctx.http, message).await;
+    if let Err(why) = result {
+        eprintln!("Error announcing squad: {}", why);
+    }
 }
 
-/// Globally register /squad command
+/// Globally register /squad command with its subcommands, along with their names and
+/// descriptions in the languages SquadBot speaks
 pub async fn register_squad_command(ctx: Context) -> Result<ApplicationCommand, Error> {
     ApplicationCommand::create_global_application_command(&ctx.http, |command| {
         command
             .name("squad")
-            .description("Create a new squad posting")
+            .description("Create and browse squad postings")
+            .create_option(|option| {
+                option
+                    .name("create")
+                    .description("Create a new squad posting")
+                    .kind(ApplicationCommandOptionType::SubCommand)
+                    .create_sub_option(|sub| {
+                            sub.name("size")
+                            .description("Number from 1 to 10")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(store::SIZE_RANGE.0)
+                            .max_int_value(store::SIZE_RANGE.1)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                            sub.name("role")
+                            .description("Tag a role e.g. @gamers, @valorant, etc.")
+                            .kind(ApplicationCommandOptionType::Role)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                            sub.name("id")
+                            .description("ID of another posting for cross-server squads.")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                            sub.name("slots")
+                            .description("Role slots instead of a size, e.g. tank:1 healer:1 dps:3")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                            sub.name("duration")
+                            .description("How long the squad stays open (after the start if scheduled), e.g. 30m or 2h")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                            sub.name("start")
+                            .description("Schedule for later, e.g. 21:00, 9pm, tomorrow 8pm, in 2h (UTC unless +02:00)")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                            sub.name("links")
+                            .description("Who may post this squad in other channels and servers (anyone unless set)")
+                            .kind(ApplicationCommandOptionType::String)
+                            .add_string_choice("open", "open")
+                            .add_string_choice("approval", "approval")
+                            .add_string_choice("disabled", "disabled")
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                            sub.name("min")
+                            .description("Notify everyone once this many joined, staying open up to the size")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(store::SIZE_RANGE.0)
+                            .max_int_value(store::SIZE_RANGE.1)
+                            .required(false)
+                    })
+            })
+            .create_option(|option| {
+                option
+                    .name("list")
+                    .description("Browse the squads forming in this server")
+                    .kind(ApplicationCommandOptionType::SubCommand)
+            })
+            .create_option(|option| {
+                option
+                    .name("join")
+                    .description("Join a squad by its ID")
+                    .kind(ApplicationCommandOptionType::SubCommand)
+                    .create_sub_option(|sub| {
+                        sub.name("id")
+                            .description("ID of the squad, e.g. squad:123456789")
+                            .kind(ApplicationCommandOptionType::String)
+                            .set_autocomplete(true)
+                            .required(true)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("hours")
+                            .description("Hours you are available, for as long as the squad is open if left out")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(store::HOURS_RANGE.0)
+                            .max_int_value(store::HOURS_RANGE.1)
+                            .required(false)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("slot")
+                            .description("Role slot to take in squads with slots, e.g. tank")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(false)
+                    })
+            })
             .create_option(|option| {
                 option
-                    .name("size")
-                    .description("Number from 1 to 10")
-                    .kind(ApplicationCommandOptionType::Integer)
-                    .min_int_value(1)
-                    .max_int_value(10)
-                    .required(false)
+                    .name("leave")
+                    .description("Leave a squad or its waitlist by its ID")
+                    .kind(ApplicationCommandOptionType::SubCommand)
+                    .create_sub_option(|sub| {
+                        sub.name("id")
+                            .description("ID of the squad, e.g. squad:123456789")
+                            .kind(ApplicationCommandOptionType::String)
+                            .set_autocomplete(true)
+                            .required(true)
+                    })
             })
             .create_option(|option| {
                 option
-                    .name("role")
-                    .description("Tag a role e.g. @gamers, @valorant, etc.")
-                    .kind(ApplicationCommandOptionType::Role)
-                    .required(false)
+                    .name("info")
+                    .description("Show the status and members of a squad by its ID")
+                    .kind(ApplicationCommandOptionType::SubCommand)
+                    .create_sub_option(|sub| {
+                        sub.name("id")
+                            .description("ID of the squad, e.g. squad:123456789")
+                            .kind(ApplicationCommandOptionType::String)
+                            .set_autocomplete(true)
+                            .required(true)
+                    })
             })
             .create_option(|option| {
                 option
-                    .name("id")
-                    .description("ID of another posting for cross-server squads.")
-                    .kind(ApplicationCommandOptionType::String)
-                    .required(false)
+                    .name("extend")
+                    .description("Stay available in a squad for more hours")
+                    .kind(ApplicationCommandOptionType::SubCommand)
+                    .create_sub_option(|sub| {
+                        sub.name("id")
+                            .description("ID of the squad, e.g. squad:123456789")
+                            .kind(ApplicationCommandOptionType::String)
+                            .set_autocomplete(true)
+                            .required(true)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("hours")
+                            .description("Hours to add to your availability")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(store::HOURS_RANGE.0)
+                            .max_int_value(store::HOURS_RANGE.1)
+                            .required(true)
+                    })
             })
+            .create_option(|option| {
+                option
+                    .name("links")
+                    .description("List and remove the postings of a squad you own")
+                    .kind(ApplicationCommandOptionType::SubCommand)
+                    .create_sub_option(|sub| {
+                        sub.name("id")
+                            .description("ID of the squad, e.g. squad:123456789")
+                            .kind(ApplicationCommandOptionType::String)
+                            .set_autocomplete(true)
+                            .required(true)
+                    })
+                    .create_sub_option(|sub| {
+                        sub.name("links")
+                            .description("Change who may post the squad in other channels and servers")
+                            .kind(ApplicationCommandOptionType::String)
+                            .add_string_choice("open", "open")
+                            .add_string_choice("approval", "approval")
+                            .add_string_choice("disabled", "disabled")
+                            .required(false)
+                    })
+            });
+        locale::localize_command(command, locale::SQUAD_COMMAND_TRANSLATIONS)
     })
     .await
 }
 
-/// Create data for new squad posting
+/// Dispatch a /squad command to the handler of its subcommand
 pub async fn handle_squad_command(
     ctx: &Context,
     command: &ApplicationCommandInteraction,
-) -> Result<(), Box<dyn StdError>> {
-    let capacity: Option<u8> = parse_squad_size(&command).await?;
-    let role_id: Option<RoleId> = parse_squad_role(&command).await?;
-    let squad_id: Option<String> = parse_squad_id(&command).await?;
-    let mut con = redis_io::get_redis_connection(&ctx).await?;
-    match squad_id {
+) -> Result<(), SquadError> {
+    let subcommand = match command.data.options.first() {
+        Some(subcommand) => subcommand,
+        None => {
+            return Err(SquadError::UnknownCommand);
+        }
+    };
+    match subcommand.name.as_str() {
+        "create" => handle_create_command(ctx, command).await,
+        "list" => handle_list_command(ctx, command).await,
+        "join" => handle_join_command(ctx, command).await,
+        "leave" => handle_leave_command(ctx, command).await,
+        "info" => handle_info_command(ctx, command).await,
+        "extend" => handle_extend_command(ctx, command).await,
+        "links" => links::handle_links_command(ctx, command).await,
+        _ => Err(SquadError::UnknownCommand),
+    }
+}
+
+/// Create data for new squad posting
+async fn handle_create_command(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+) -> Result<(), SquadError> {
+    let capacity: Option<u8> = parse_squad_size(command).await?;
+    let role_id: Option<RoleId> = parse_squad_role(command).await?;
+    let squad_id: Option<String> = parse_squad_id(command).await?;
+    let start: Option<i64> = parse_squad_start(command).await?;
+    let composition: Composition = parse_squad_composition(command).await?;
+    let duration: Option<u64> = parse_squad_duration(command).await?;
+    let min: Option<u8> = parse_squad_min(command).await?;
+    let link_policy: Option<LinkPolicy> = parse_link_policy(command).await?;
+    let store = store::get_store(ctx).await?;
+    let settings = match command.guild_id {
+        Some(guild_id) => store.get_guild_settings(guild_id).await?,
+        None => GuildSettings::default(),
+    };
+    if !can_create(command, &settings) {
+        return Err(SquadError::NotCreator);
+    }
+    let message_id = match squad_id {
         Some(id) => {
-            let capacity = redis_io::get_capacity(&mut con, &id)?;
-            let response = respond_squad_command(&ctx, &command, &id, capacity, role_id).await?;
-            let channel_id = command.channel_id.as_u64().to_string();
-            let message_id = response.id.as_u64().to_string();
-            redis_io::build_posting(&mut con, &channel_id, &message_id, role_id, &id)?;
+            // Posting an existing squad only picks the role to mention
+            let create_options = [
+                ("size", capacity.is_some()),
+                ("slots", !composition.is_empty()),
+                ("duration", duration.is_some()),
+                ("start", start.is_some()),
+                ("min", min.is_some()),
+            ];
+            if let Some((option, _)) = create_options.iter().find(|(_, given)| *given) {
+                return Err(SquadError::invalid_with(
+                    |text| text.option_on_link,
+                    &[option],
+                ));
+            }
+            if link_policy.is_some() {
+                return Err(SquadError::invalid(|text| text.links_on_link));
+            }
+            check_forming(store.get_squad_status(&id).await?)?;
+            // Anyone but the owner is held to the link policy of the squad
+            if let Some(owner) = store.get_owner(&id).await? {
+                if owner != command.user.id {
+                    match store.get_link_policy(&id).await? {
+                        LinkPolicy::Open => {}
+                        LinkPolicy::Approval => {
+                            return links::request_link(ctx, command, &id, owner, role_id).await;
+                        }
+                        LinkPolicy::Disabled => return Err(SquadError::LinksDisabled),
+                    }
+                }
+            }
+            let ttl = store.get_squad_ttl(&id).await? + store::POSTING_GRACE;
+            post_squad(ctx, command, store.as_ref(), &id, role_id, &settings, ttl).await?
         }
         None => {
             let id = generate_squad_id();
-            let capacity = match capacity {
-                Some(n) => n,
-                None => 5,
+            // Squads with role slots hold exactly as many members as they have seats
+            let capacity = match composition.is_empty() {
+                true => capacity.unwrap_or_else(|| settings.default_size()),
+                false => composition.iter().map(|(_, seats)| seats).sum(),
+            };
+            let capacity = check_size(capacity, &settings)?;
+            if min.is_some_and(|min| min >= capacity) {
+                return Err(SquadError::invalid(|text| text.min_too_large));
+            }
+            let duration = match duration {
+                Some(duration) => Some(check_duration(duration, &settings)?),
+                None => None,
             };
-            redis_io::build_squad(&mut con, &id, capacity)?;
-            let response = respond_squad_command(&ctx, &command, &id, capacity, role_id).await?;
-            let channel_id = command.channel_id.as_u64().to_string();
-            let message_id = response.id.as_u64().to_string();
-            redis_io::build_posting(&mut con, &channel_id, &message_id, role_id, &id)?;
+            // Scheduled squads stay open for the duration after their start time
+            let ttl = match start {
+                Some(start) => {
+                    let until_start = (start - Utc::now().timestamp()) as u64;
+                    until_start + duration.unwrap_or(store::START_GRACE)
+                }
+                None => duration.unwrap_or_else(|| settings.default_duration()),
+            };
+            let owner_id = command.user.id.as_u64().to_string();
+            store
+                .build_squad(&id, &owner_id, capacity, start, &composition, ttl)
+                .await?;
+            if let Some(min) = min {
+                store.set_quorum(&id, min).await?;
+            }
+            if let Some(link_policy) = link_policy {
+                store.set_link_policy(&id, link_policy).await?;
+            }
+            let ttl = ttl + store::POSTING_GRACE;
+            post_squad(ctx, command, store.as_ref(), &id, role_id, &settings, ttl).await?
         }
+    };
+    // Scheduled squads get their event as soon as they are posted
+    if let Err(why) = events::sync_event(ctx, store.as_ref(), command.channel_id, &message_id).await
+    {
+        eprintln!("Error creating squad event: {}", why);
     }
+    Ok(())
+}
 
+/// Fail with the reason a squad can no longer be joined or left, unless it is forming
+pub fn check_forming(squad_status: SquadStatus) -> Result<(), SquadError> {
+    match squad_status {
+        SquadStatus::Forming => Ok(()),
+        SquadStatus::Filled => Err(SquadError::SquadFilled),
+        SquadStatus::Cancelled => Err(SquadError::SquadCancelled),
+        SquadStatus::Expired => Err(SquadError::UnknownSquad),
+    }
+}
+
+/// Get the squad id required by /squad join, leave, info and extend
+pub async fn required_squad_id(
+    command: &ApplicationCommandInteraction,
+) -> Result<String, SquadError> {
+    match parse_squad_id(command).await? {
+        Some(id) => Ok(id),
+        None => Err(SquadError::invalid(|text| text.missing_id)),
+    }
+}
+
+/// Join the squad given to /squad join, taking the given role slot first in squads
+/// with slots, and update its postings
+async fn handle_join_command(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+) -> Result<(), SquadError> {
+    let squad_id = required_squad_id(command).await?;
+    let hours = parse_squad_hours(command).await?;
+    let slot = parse_squad_slot(command).await?;
+    let text = locale::interaction_locale(ctx, command.guild_id, &command.locale)
+        .await
+        .text();
+    let store = store::get_store(ctx).await?;
+    check_forming(store.get_squad_status(&squad_id).await?)?;
+    let origin = command_origin(ctx, store.as_ref(), &squad_id, command).await?;
+    if origin.is_none() {
+        check_join_policy(store.as_ref(), &squad_id, command.user.id).await?;
+    }
+    if let Some(slot) = slot {
+        let composition = store.get_composition(&squad_id).await?;
+        if !composition.iter().any(|(name, _)| *name == slot) {
+            return Err(SquadError::UnknownSlot);
+        }
+        let user_id = command.user.id.as_u64().to_string();
+        if !store.choose_slot(&squad_id, &user_id, &slot).await? {
+            return Err(SquadError::SlotTaken);
+        }
+    }
+    let expires = hours.map(|hours| u32::from(hours) * 60 * 60);
+    let result = add_member(store.as_ref(), &squad_id, command.user.id, expires).await?;
+    if let Some(origin) = origin {
+        if !matches!(result, JoinResult::Closed | JoinResult::NoSlot) {
+            let user_id = command.user.id.as_u64().to_string();
+            store.set_origin(&squad_id, &user_id, origin).await?;
+        }
+    }
+    if let JoinResult::Joined = result {
+        notify::notify_late_joiner(ctx, store.as_ref(), &squad_id, command.user.id).await?;
+    }
+    notify::promote_waitlist(ctx, store.as_ref(), &squad_id).await?;
+    embed::build_messages(ctx, store.as_ref(), &squad_id).await?;
+    let message = match result {
+        JoinResult::Joined => text.joined,
+        JoinResult::AlreadyMember => text.already_member,
+        JoinResult::NoSlot => text.no_slot_option,
+        _ => join_result_message(&result, text).unwrap_or_default(),
+    };
+    respond_command(ctx, command, message).await?;
     Ok(())
 }
 
-/// Create data for new squad member and update squad posting
+/// Find the channel of the posting that a user joining a squad with /squad join joins
+/// through: the posting in the channel of the command, or else a posting in the same
+/// server. None if the squad isn't posted where the command was given.
+async fn command_origin(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+    command: &ApplicationCommandInteraction,
+) -> Result<Option<ChannelId>, SquadError> {
+    let channels = store.get_channels(squad_id).await?;
+    let origin = match channels.contains(&command.channel_id) {
+        true => Some(command.channel_id),
+        false => channels.into_iter().find(|channel_id| {
+            ctx.cache
+                .guild_channel(*channel_id)
+                .is_some_and(|channel| Some(channel.guild_id) == command.guild_id)
+        }),
+    };
+    Ok(origin)
+}
+
+/// Hold a user joining a squad by id where it isn't posted to its link policy, as
+/// joining from anywhere would get around an owner who limits where it is posted.
+/// Owners, and users who are already in the squad or waiting to join, can always join.
+async fn check_join_policy(
+    store: &dyn SquadStore,
+    squad_id: &str,
+    user_id: UserId,
+) -> Result<(), SquadError> {
+    if store.get_owner(squad_id).await? == Some(user_id) {
+        return Ok(());
+    }
+    if let LinkPolicy::Open = store.get_link_policy(squad_id).await? {
+        return Ok(());
+    }
+    let member = store.get_members(squad_id).await?.contains_key(&user_id);
+    let waiting = store.get_waitlist(squad_id).await?.contains(&user_id);
+    match member || waiting {
+        true => Ok(()),
+        false => Err(SquadError::NotPosted),
+    }
+}
+
+/// Leave the squad given to /squad leave, or its waitlist, and update its postings
+async fn handle_leave_command(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+) -> Result<(), SquadError> {
+    let squad_id = required_squad_id(command).await?;
+    let store = store::get_store(ctx).await?;
+    check_forming(store.get_squad_status(&squad_id).await?)?;
+    let user_id = command.user.id;
+    let member = store.get_members(&squad_id).await?.contains_key(&user_id);
+    let waiting = store.get_waitlist(&squad_id).await?.contains(&user_id);
+    if !member && !waiting {
+        return Err(SquadError::NotMember);
+    }
+    store
+        .delete_member(&squad_id, &user_id.as_u64().to_string())
+        .await?;
+    rooms::dismiss_member(ctx, store.as_ref(), &squad_id, user_id).await?;
+    notify::promote_waitlist(ctx, store.as_ref(), &squad_id).await?;
+    embed::build_messages(ctx, store.as_ref(), &squad_id).await?;
+    let text = locale::interaction_locale(ctx, command.guild_id, &command.locale)
+        .await
+        .text();
+    respond_command(ctx, command, text.left).await?;
+    Ok(())
+}
+
+/// Answer /squad info with the status, owner and members of the given squad
+async fn handle_info_command(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+) -> Result<(), SquadError> {
+    let squad_id = required_squad_id(command).await?;
+    let store = store::get_store(ctx).await?;
+    let status = store.get_squad_status(&squad_id).await?;
+    if matches!(status, SquadStatus::Expired) {
+        return Err(SquadError::UnknownSquad);
+    }
+    let user_id = command.user.id;
+    let text = locale::interaction_locale(ctx, command.guild_id, &command.locale)
+        .await
+        .text();
+    let description =
+        embed::build_info(ctx, store.as_ref(), &squad_id, &status, user_id, text).await?;
+    let settings = match command.guild_id {
+        Some(guild_id) => store.get_guild_settings(guild_id).await?,
+        None => GuildSettings::default(),
+    };
+    command
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|m| {
+                    m.embed(|e| {
+                        e.title(text.info_title);
+                        e.description(description);
+                        e.colour(settings.colour());
+                        e.footer(|f| f.text(format!("ID: {}", &squad_id)));
+                        e
+                    })
+                    .ephemeral(true)
+                })
+        })
+        .await?;
+    Ok(())
+}
+
+/// Add the hours given to /squad extend to the availability of the user in the given
+/// squad and update its postings
+async fn handle_extend_command(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+) -> Result<(), SquadError> {
+    let squad_id = required_squad_id(command).await?;
+    let hours = parse_squad_hours(command).await?.unwrap_or(1);
+    let store = store::get_store(ctx).await?;
+    let seconds = extend_member(store.as_ref(), &squad_id, command.user.id, hours).await?;
+    embed::build_messages(ctx, store.as_ref(), &squad_id).await?;
+    let text = locale::interaction_locale(ctx, command.guild_id, &command.locale)
+        .await
+        .text();
+    let until = embed::format_timestamp(u64::from(seconds), 't');
+    let message = locale::fill(text.available_now, &[&until]);
+    respond_command(ctx, command, &message).await?;
+    Ok(())
+}
+
+/// Add hours to the availability of a member of a forming squad without a start time.
+/// Returns the seconds the member is now available for.
+async fn extend_member(
+    store: &dyn SquadStore,
+    squad_id: &str,
+    user_id: UserId,
+    hours: u8,
+) -> Result<u32, SquadError> {
+    check_forming(store.get_squad_status(squad_id).await?)?;
+    let members = store.get_members(squad_id).await?;
+    let ttl = match members.get(&user_id) {
+        Some(ttl) => *ttl,
+        None => {
+            return Err(SquadError::NotMember);
+        }
+    };
+    if store.get_start(squad_id).await?.is_some() {
+        return Err(SquadError::invalid(|text| text.scheduled_extend));
+    }
+    let seconds = u32::try_from(ttl)? + u32::from(hours) * 60 * 60;
+    if u64::from(seconds) > store::MAX_SQUAD_TTL {
+        return Err(SquadError::invalid(|text| text.availability_too_long));
+    }
+    let user_id = user_id.as_u64().to_string();
+    // The squad may have filled, closed or lost the member since it was checked
+    match store.add_member(squad_id, &user_id, seconds).await? {
+        JoinResult::Joined | JoinResult::AlreadyMember => Ok(seconds),
+        JoinResult::Closed => {
+            check_forming(store.get_squad_status(squad_id).await?)?;
+            Err(SquadError::UnknownSquad)
+        }
+        JoinResult::Waitlisted | JoinResult::NoSlot => Err(SquadError::NotMember),
+    }
+}
+
+/// Suggest squads for the id option of a /squad command that match what the user has
+/// typed so far. Squads are suggested when they are posted in the guild of the command
+/// or when the user is in them or waiting to join. Only squads the user is in are
+/// suggested for leave and extend, and only forming squads for anything but info.
+pub async fn handle_autocomplete(
+    ctx: &Context,
+    interaction: &AutocompleteInteraction,
+) -> Result<(), SquadError> {
+    let subcommand = match interaction.data.options.first() {
+        Some(subcommand) => subcommand,
+        None => {
+            return Err(SquadError::UnknownCommand);
+        }
+    };
+    let typed = subcommand
+        .options
+        .iter()
+        .find(|option| option.focused)
+        .and_then(|option| option.value.as_ref())
+        .and_then(|value| value.as_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let user_id = interaction.user.id;
+    let text = locale::interaction_locale(ctx, interaction.guild_id, &interaction.locale)
+        .await
+        .text();
+    let store = store::get_store(ctx).await?;
+    let mut candidates = store.get_user_squads(user_id).await?;
+    if let Some(guild_id) = interaction.guild_id {
+        for squad_id in store.get_guild_squads(guild_id).await? {
+            if !candidates.contains(&squad_id) {
+                candidates.push(squad_id);
+            }
+        }
+    }
+    let mut choices = Vec::new();
+    for squad_id in candidates {
+        // Discord shows at most 25 choices
+        if choices.len() == 25 {
+            break;
+        }
+        let status = store.get_squad_status(&squad_id).await?;
+        let forming = matches!(status, SquadStatus::Forming);
+        if matches!(status, SquadStatus::Expired) || (!forming && subcommand.name != "info") {
+            continue;
+        }
+        let members = store.get_members(&squad_id).await?;
+        let wanted = match subcommand.name.as_str() {
+            "leave" | "extend" => {
+                members.contains_key(&user_id)
+                    || store.get_waitlist(&squad_id).await?.contains(&user_id)
+            }
+            "links" => store.get_owner(&squad_id).await? == Some(user_id),
+            _ => true,
+        };
+        if !wanted {
+            continue;
+        }
+        let capacity = store.get_capacity(&squad_id).await?;
+        let state = match status {
+            SquadStatus::Forming => text.choice_forming,
+            SquadStatus::Filled => text.choice_filled,
+            SquadStatus::Cancelled => text.choice_cancelled,
+            SquadStatus::Expired => text.choice_expired,
+        };
+        let channel = store
+            .get_channels(&squad_id)
+            .await?
+            .into_iter()
+            .find_map(|channel_id| ctx.cache.guild_channel(channel_id))
+            .map(|channel| format!(" · #{}", channel.name))
+            .unwrap_or_default();
+        let label = format!(
+            "{} · {}/{} {}{}",
+            squad_id,
+            members.len(),
+            capacity,
+            state,
+            channel
+        );
+        if label.to_lowercase().contains(&typed) {
+            choices.push((squad_id, label.chars().take(100).collect::<String>()));
+        }
+    }
+    choices.sort();
+    interaction
+        .create_autocomplete_response(&ctx.http, |response| {
+            for (squad_id, label) in &choices {
+                response.add_string_choice(label, squad_id);
+            }
+            response
+        })
+        .await?;
+    Ok(())
+}
+
+/// Answer /squad list with the first page of squads forming in the guild
+async fn handle_list_command(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+) -> Result<(), SquadError> {
+    let text = locale::interaction_locale(ctx, command.guild_id, &command.locale)
+        .await
+        .text();
+    let lines = match command.guild_id {
+        Some(guild_id) => list_lines(ctx, guild_id, text).await?,
+        None => Vec::new(),
+    };
+    command
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|m| embed::list_page(m, &lines, 0, text))
+        })
+        .await?;
+    Ok(())
+}
+
+/// Turn the /squad list to the page picked with its Previous and Next buttons, in the
+/// given language
+pub async fn handle_list_page(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    page: usize,
+    text: &'static Text,
+) -> Result<(), SquadError> {
+    let lines = match interaction.guild_id {
+        Some(guild_id) => list_lines(ctx, guild_id, text).await?,
+        None => Vec::new(),
+    };
+    interaction
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|m| embed::list_page(m, &lines, page, text))
+        })
+        .await?;
+    Ok(())
+}
+
+/// Build the /squad list lines of every posting of a forming squad in the guild,
+/// closing soonest first, in the given language. Postings that expire while they are
+/// read are skipped.
+async fn list_lines(
+    ctx: &Context,
+    guild_id: GuildId,
+    text: &Text,
+) -> Result<Vec<String>, SquadError> {
+    let store = store::get_store(ctx).await?;
+    let mut entries = Vec::new();
+    for (message_id, channel_id) in store.get_postings().await? {
+        match ctx.cache.guild_channel(channel_id) {
+            Some(channel) if channel.guild_id == guild_id => {}
+            _ => continue,
+        }
+        let entry = list_entry(store.as_ref(), message_id, channel_id, guild_id, text).await;
+        if let Ok(Some(entry)) = entry {
+            entries.push(entry);
+        }
+    }
+    entries.sort_by_key(|(ttl, _)| *ttl);
+    Ok(entries.into_iter().map(|(_, line)| line).collect())
+}
+
+/// Get the time left and /squad list line of a posting, None unless its squad is forming
+async fn list_entry(
+    store: &dyn SquadStore,
+    message_id: MessageId,
+    channel_id: ChannelId,
+    guild_id: GuildId,
+    text: &Text,
+) -> Result<Option<(u64, String)>, SquadError> {
+    let posting = message_id.as_u64().to_string();
+    let squad_id = store.get_squad_id(&posting).await?;
+    if !matches!(
+        store.get_squad_status(&squad_id).await?,
+        SquadStatus::Forming
+    ) {
+        return Ok(None);
+    }
+    let capacity = store.get_capacity(&squad_id).await?;
+    let members = store.get_members(&squad_id).await?.len();
+    let ttl = store.get_squad_ttl(&squad_id).await?;
+    let role_id = store.get_role_id(&posting).await?;
+    let link = message_id.link(channel_id, Some(guild_id));
+    let line = embed::list_line(capacity, members, ttl, role_id, &link, text);
+    Ok(Some((ttl, line)))
+}
+
+/// Create data for new squad member and update squad posting, welcoming users who join
+/// a squad that's already playing. Members available for a number of seconds pass
+/// Some(seconds); members committing to a scheduled squad pass None and stay until the
+/// squad expires.
 pub async fn handle_add_member(
     ctx: &Context,
     interaction: &MessageComponentInteraction,
-    expires: u8,
-) -> Result<(), Box<dyn StdError>> {
-    let message_id = interaction.message.id.as_u64().to_string();
-    let user_id = interaction.user.id.as_u64().to_string();
-    let seconds: u32 = u32::from(expires) * 60 * 60;
-    let mut con = redis_io::get_redis_connection(&ctx).await?;
-    let squad_id = redis_io::get_squad_id(&mut con, &message_id)?;
-    redis_io::add_member(&mut con, &squad_id, &user_id, seconds)?;
-    embed::build_message(&ctx, &interaction.channel_id, &mut con, &message_id).await?;
+    expires: Option<u32>,
+) -> Result<JoinResult, SquadError> {
+    join_posting(
+        ctx,
+        interaction.message.id,
+        interaction.channel_id,
+        interaction.user.id,
+        expires,
+    )
+    .await
+}
+
+/// Open the form to give an exact end time of availability, picked from the custom
+/// option of the availability menu of a posting, in the given language.
+pub async fn handle_custom_availability(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    text: &Text,
+) -> Result<(), SquadError> {
+    let message_id = interaction.message.id;
+    interaction
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::Modal)
+                .interaction_response_data(|m| embed::availability_modal(m, message_id, text))
+        })
+        .await?;
     Ok(())
 }
 
+/// Add the user who filled in the availability form to the squad of the posting it
+/// was opened from, until the time they gave. Returns the explanation shown to the
+/// user, in the given language.
+pub async fn handle_availability_modal(
+    ctx: &Context,
+    modal: &ModalSubmitInteraction,
+    text: &'static Text,
+) -> Result<String, SquadError> {
+    let id = modal.data.custom_id.as_str();
+    let message_id = match id
+        .strip_prefix(embed::COMPONENT_VERSION)
+        .and_then(|id| id.strip_prefix(":until:"))
+    {
+        Some(message_id) => MessageId(message_id.parse()?),
+        None => return Err(SquadError::UnknownComponent),
+    };
+    let input = modal
+        .data
+        .components
+        .iter()
+        .flat_map(|row| &row.components)
+        .find_map(|component| match component {
+            ActionRowComponent::InputText(input) if input.custom_id == "until" => {
+                Some(input.value.as_str())
+            }
+            _ => None,
+        })
+        .unwrap_or_default();
+    let now = Utc::now().timestamp();
+    let seconds = parse_available_until(input, now)?;
+    let result = join_posting(
+        ctx,
+        message_id,
+        modal.channel_id,
+        modal.user.id,
+        Some(seconds),
+    )
+    .await?;
+    let message = match result {
+        JoinResult::Joined | JoinResult::AlreadyMember => {
+            let until = embed::discord_timestamp(now + i64::from(seconds), 't');
+            locale::fill(text.available_reply, &[&until])
+        }
+        _ => join_result_message(&result, text)
+            .unwrap_or_default()
+            .to_string(),
+    };
+    Ok(message)
+}
+
+/// Add a user to the squad of a posting, record the posting as the one they joined
+/// through and update the posting.
+async fn join_posting(
+    ctx: &Context,
+    message_id: MessageId,
+    channel_id: ChannelId,
+    user_id: UserId,
+    expires: Option<u32>,
+) -> Result<JoinResult, SquadError> {
+    let message_id = message_id.as_u64().to_string();
+    let store = store::get_store(ctx).await?;
+    let squad_id = store.get_squad_id(&message_id).await?;
+    let result = add_member(store.as_ref(), &squad_id, user_id, expires).await?;
+    if !matches!(result, JoinResult::Closed | JoinResult::NoSlot) {
+        let user_id = user_id.as_u64().to_string();
+        store.set_origin(&squad_id, &user_id, channel_id).await?;
+    }
+    if let JoinResult::Joined = result {
+        notify::notify_late_joiner(ctx, store.as_ref(), &squad_id, user_id).await?;
+    }
+    embed::build_message(ctx, &channel_id, store.as_ref(), &message_id).await?;
+    Ok(result)
+}
+
+/// Add a user to a squad for a number of seconds. Without seconds, and always for
+/// scheduled squads, the user stays until the squad expires.
+async fn add_member(
+    store: &dyn SquadStore,
+    squad_id: &str,
+    user_id: UserId,
+    expires: Option<u32>,
+) -> Result<JoinResult, SquadError> {
+    let scheduled = store.get_start(squad_id).await?.is_some();
+    let seconds: u32 = match expires {
+        Some(seconds) if !scheduled => seconds,
+        _ => u32::try_from(store.get_squad_ttl(squad_id).await?)?,
+    };
+    let user_id = user_id.as_u64().to_string();
+    store.add_member(squad_id, &user_id, seconds).await
+}
+
 /// Delete data for interacting user and update squad posting
 pub async fn handle_delete_member(
     ctx: &Context,
     interaction: &MessageComponentInteraction,
-) -> Result<(), Box<dyn StdError>> {
+) -> Result<(), SquadError> {
     let message_id = interaction.message.id.as_u64().to_string();
     let user_id = interaction.user.id.as_u64().to_string();
-    let mut con = redis_io::get_redis_connection(&ctx).await?;
-    let squad_id = redis_io::get_squad_id(&mut con, &message_id)?;
-    redis_io::delete_member(&mut con, &squad_id, &user_id)?;
-    embed::build_message(&ctx, &interaction.channel_id, &mut con, &message_id).await?;
+    let store = store::get_store(ctx).await?;
+    let squad_id = store.get_squad_id(&message_id).await?;
+    store.delete_member(&squad_id, &user_id).await?;
+    rooms::dismiss_member(ctx, store.as_ref(), &squad_id, interaction.user.id).await?;
+    notify::promote_waitlist(ctx, store.as_ref(), &squad_id).await?;
+    embed::build_message(ctx, &interaction.channel_id, store.as_ref(), &message_id).await?;
+    Ok(())
+}
+
+/// Record the role slot picked by the interacting user and update squad posting.
+/// Returns the explanation shown to the user in the given language, or fails if the
+/// slot is taken.
+pub async fn handle_choose_slot(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    slot: &str,
+    text: &'static Text,
+) -> Result<&'static str, SquadError> {
+    let message_id = interaction.message.id.as_u64().to_string();
+    let user_id = interaction.user.id.as_u64().to_string();
+    let store = store::get_store(ctx).await?;
+    let squad_id = store.get_squad_id(&message_id).await?;
+    if !store.choose_slot(&squad_id, &user_id, slot).await? {
+        return Err(SquadError::SlotTaken);
+    }
+    // Moving to another slot may free a seat for someone on the waitlist
+    notify::promote_waitlist(ctx, store.as_ref(), &squad_id).await?;
+    embed::build_message(ctx, &interaction.channel_id, store.as_ref(), &message_id).await?;
+    let members = store.get_members(&squad_id).await?;
+    match members.contains_key(&interaction.user.id) {
+        true => Ok(text.role_changed),
+        false => Ok(text.role_picked),
+    }
+}
+
+/// Fail unless the interacting user owns the squad
+pub async fn check_owner(
+    store: &dyn store::SquadStore,
+    squad_id: &str,
+    interaction: &MessageComponentInteraction,
+) -> Result<(), SquadError> {
+    let owner = store.get_owner(squad_id).await?;
+    match owner == Some(interaction.user.id) {
+        true => Ok(()),
+        false => Err(SquadError::NotOwner),
+    }
+}
+
+/// Fill the squad early with whoever has joined and DM them, if the interacting user
+/// owns the squad. Returns the explanation shown to the user in the given language,
+/// if any.
+pub async fn handle_close_squad(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    text: &'static Text,
+) -> Result<Option<&'static str>, SquadError> {
+    let message_id = interaction.message.id.as_u64().to_string();
+    let store = store::get_store(ctx).await?;
+    let squad_id = store.get_squad_id(&message_id).await?;
+    close_squad(ctx, store.as_ref(), &squad_id, interaction, text).await
+}
+
+/// Fill the given squad early with whoever has joined and DM them, picked from the
+/// reminder DM of its owner. Returns the explanation shown to the user in the given
+/// language.
+pub async fn handle_close_reminder(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    squad_id: &str,
+    text: &'static Text,
+) -> Result<Option<&'static str>, SquadError> {
+    let store = store::get_store(ctx).await?;
+    check_forming(store.get_squad_status(squad_id).await?)?;
+    let message = close_squad(ctx, store.as_ref(), squad_id, interaction, text).await?;
+    Ok(message.or(Some(text.squad_set)))
+}
+
+/// Fill a squad early with whoever has joined, DM them and update its postings, if the
+/// interacting user owns the squad. Returns the explanation shown to the user in the
+/// given language, if any.
+async fn close_squad(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    squad_id: &str,
+    interaction: &MessageComponentInteraction,
+    text: &'static Text,
+) -> Result<Option<&'static str>, SquadError> {
+    check_owner(store, squad_id, interaction).await?;
+    if !store.close_squad(squad_id).await? {
+        return Ok(Some(text.nobody_joined));
+    }
+    notify::notify_ready(ctx, store, squad_id).await?;
+    embed::build_messages(ctx, store, squad_id).await?;
+    Ok(None)
+}
+
+/// Keep the interacting user available for another hour in the given squad, picked
+/// from their reminder DM. Returns the explanation shown to the user in the given
+/// language.
+pub async fn handle_extend_reminder(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    squad_id: &str,
+    text: &'static Text,
+) -> Result<Option<&'static str>, SquadError> {
+    let store = store::get_store(ctx).await?;
+    extend_member(store.as_ref(), squad_id, interaction.user.id, 1).await?;
+    embed::build_messages(ctx, store.as_ref(), squad_id).await?;
+    Ok(Some(text.another_hour))
+}
+
+/// Keep the given squad open for another hour, picked from the reminder DM of its
+/// owner. Members of scheduled squads stay until it ends, so they are kept along
+/// with it. Returns the explanation shown to the user in the given language.
+pub async fn handle_extend_squad_reminder(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    squad_id: &str,
+    text: &'static Text,
+) -> Result<Option<&'static str>, SquadError> {
+    let store = store::get_store(ctx).await?;
+    check_owner(store.as_ref(), squad_id, interaction).await?;
+    check_forming(store.get_squad_status(squad_id).await?)?;
+    if !store.extend_squad(squad_id, 60 * 60).await? {
+        return Err(SquadError::UnknownSquad);
+    }
+    if store.get_start(squad_id).await?.is_some() {
+        let ttl = u32::try_from(store.get_squad_ttl(squad_id).await?)?;
+        for user_id in store.get_members(squad_id).await?.keys() {
+            let user_id = user_id.as_u64().to_string();
+            store.add_member(squad_id, &user_id, ttl).await?;
+        }
+    }
+    embed::build_messages(ctx, store.as_ref(), squad_id).await?;
+    Ok(Some(text.squad_extended))
+}
+
+/// Cancel the squad and DM its members, if the interacting user owns the squad.
+/// Returns the explanation shown to the user in the given language, if any.
+pub async fn handle_cancel_squad(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    text: &'static Text,
+) -> Result<Option<&'static str>, SquadError> {
+    let message_id = interaction.message.id.as_u64().to_string();
+    let store = store::get_store(ctx).await?;
+    let squad_id = store.get_squad_id(&message_id).await?;
+    check_owner(store.as_ref(), &squad_id, interaction).await?;
+    let members = store.get_members(&squad_id).await?;
+    if !store.cancel_squad(&squad_id).await? {
+        return Ok(Some(text.not_cancellable));
+    }
+    // The owner knows already
+    let members = members
+        .into_keys()
+        .filter(|user_id| *user_id != interaction.user.id)
+        .collect();
+    notify::notify_cancelled(ctx, store.as_ref(), &squad_id, members).await?;
+    embed::build_messages(ctx, store.as_ref(), &squad_id).await?;
+    Ok(None)
+}
+
+/// Show the owner an ephemeral menu of members to kick, in the given language. Fails
+/// for anyone else.
+pub async fn handle_kick_button(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    text: &'static Text,
+) -> Result<(), SquadError> {
+    let message_id = interaction.message.id.as_u64().to_string();
+    let store = store::get_store(ctx).await?;
+    let squad_id = store.get_squad_id(&message_id).await?;
+    check_owner(store.as_ref(), &squad_id, interaction).await?;
+    let mut members = Vec::new();
+    for user_id in store.get_members(&squad_id).await?.into_keys() {
+        if user_id == interaction.user.id {
+            continue;
+        }
+        let name = match user_id.to_user(ctx).await {
+            Ok(user) => user.tag(),
+            Err(_) => user_id.as_u64().to_string(),
+        };
+        members.push((user_id, name));
+    }
+    if members.is_empty() {
+        respond_component(ctx, interaction, Some(text.nobody_to_kick)).await?;
+        return Ok(());
+    }
+    interaction
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|m| embed::kick_menu(m, &message_id, &members, text))
+        })
+        .await?;
     Ok(())
 }
 
-/// Determine which button was pressed on the squad posting
-pub fn parse_component_id(interaction: &MessageComponentInteraction) -> embed::ButtonChoice {
-    let id = interaction.data.custom_id.clone();
-    match id.parse() {
-        Ok(expires) => embed::ButtonChoice::Hours(expires),
-        Err(_) => embed::ButtonChoice::Leave(id),
+/// Remove the member picked from the kick menu from the squad of the given posting,
+/// if the interacting user owns the squad. Returns the explanation shown to the user
+/// in the given language.
+pub async fn handle_kick_member(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    message_id: &str,
+    user_id: &str,
+    text: &'static Text,
+) -> Result<&'static str, SquadError> {
+    let store = store::get_store(ctx).await?;
+    let squad_id = store.get_squad_id(message_id).await?;
+    check_owner(store.as_ref(), &squad_id, interaction).await?;
+    let kicked = UserId(user_id.parse()?);
+    store.delete_member(&squad_id, user_id).await?;
+    rooms::dismiss_member(ctx, store.as_ref(), &squad_id, kicked).await?;
+    notify::promote_waitlist(ctx, store.as_ref(), &squad_id).await?;
+    embed::build_message(ctx, &interaction.channel_id, store.as_ref(), message_id).await?;
+    Ok(text.member_kicked)
+}
+
+/// Explanation shown to a user whose pick of their availability didn't add them, in
+/// the given language
+pub fn join_result_message(result: &JoinResult, text: &'static Text) -> Option<&'static str> {
+    match result {
+        JoinResult::Joined | JoinResult::AlreadyMember => None,
+        JoinResult::Waitlisted => Some(text.waitlisted),
+        JoinResult::Closed => Some(text.closed),
+        JoinResult::NoSlot => Some(text.no_slot),
+    }
+}
+
+/// Answer a command with an ephemeral message
+pub async fn respond_command(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    content: &str,
+) -> Result<(), Error> {
+    command
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|m| m.content(content).ephemeral(true))
+        })
+        .await
+}
+
+/// Acknowledge a button click, either silently or with an ephemeral message
+pub async fn respond_component(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    message: Option<&str>,
+) -> Result<(), Error> {
+    match message {
+        Some(content) => {
+            interaction
+                .create_interaction_response(&ctx.http, |response| {
+                    response
+                        .kind(InteractionResponseType::ChannelMessageWithSource)
+                        .interaction_response_data(|m| m.content(content).ephemeral(true))
+                })
+                .await
+        }
+        None => interaction.defer(&ctx.http).await,
+    }
+}
+
+/// Answer a submitted form with an ephemeral message
+pub async fn respond_modal(
+    ctx: &Context,
+    modal: &ModalSubmitInteraction,
+    content: &str,
+) -> Result<(), Error> {
+    modal
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|m| m.content(content).ephemeral(true))
+        })
+        .await
+}
+
+/// Determine which component of SquadBot was used from its custom id, such as
+/// "sq1:leave" or "sq1:kick:<message id>". The hour and leave buttons of postings
+/// sent before custom ids were versioned are still understood. Custom ids that are
+/// neither are rejected.
+pub fn parse_component_id(
+    interaction: &MessageComponentInteraction,
+) -> Result<embed::ButtonChoice, SquadError> {
+    let id = interaction.data.custom_id.as_str();
+    // Select menus report the picked option as their only value
+    let value = interaction.data.values.first().cloned().unwrap_or_default();
+    let choice = match id
+        .strip_prefix(embed::COMPONENT_VERSION)
+        .and_then(|id| id.strip_prefix(':'))
+    {
+        Some(id) => parse_choice(id, value),
+        None => parse_legacy_choice(id),
+    };
+    choice.ok_or(SquadError::UnknownComponent)
+}
+
+/// Read the custom id of a component without its version, e.g. "kick:<message id>",
+/// along with the option picked if it is a select menu.
+fn parse_choice(id: &str, value: String) -> Option<embed::ButtonChoice> {
+    let (name, argument) = match id.split_once(':') {
+        Some((name, argument)) => (name, Some(argument)),
+        None => (id, None),
+    };
+    let choice = match (name, argument) {
+        ("join", None) => match value.as_str() {
+            "custom" => embed::ButtonChoice::CustomAvailability,
+            seconds => embed::ButtonChoice::Availability(seconds.parse().ok()?),
+        },
+        ("commit", None) => embed::ButtonChoice::Commit,
+        ("leave", None) => embed::ButtonChoice::Leave,
+        ("slot", None) => embed::ButtonChoice::Slot(value),
+        ("close", None) => embed::ButtonChoice::Close,
+        ("cancel", None) => embed::ButtonChoice::Cancel,
+        ("kick", None) => embed::ButtonChoice::Kick,
+        ("kick", Some(message_id)) => embed::ButtonChoice::KickMember {
+            message_id: message_id.to_string(),
+            user_id: value,
+        },
+        ("extend", Some(squad_id)) => embed::ButtonChoice::Extend(squad_id.to_string()),
+        ("extend_squad", Some(squad_id)) => embed::ButtonChoice::ExtendSquad(squad_id.to_string()),
+        ("close_squad", Some(squad_id)) => embed::ButtonChoice::CloseSquad(squad_id.to_string()),
+        ("link_approve", Some(request)) => embed::ButtonChoice::ApproveLink(request.parse().ok()?),
+        ("link_deny", Some(request)) => embed::ButtonChoice::DenyLink(request.parse().ok()?),
+        ("unlink", Some(squad_id)) => embed::ButtonChoice::Unlink {
+            squad_id: squad_id.to_string(),
+            message_id: value,
+        },
+        ("list", Some(page)) => embed::ButtonChoice::Page(page.parse().ok()?),
+        _ => return None,
+    };
+    Some(choice)
+}
+
+/// Read the custom id of a button of a posting sent before custom ids were versioned,
+/// so that postings already in channels keep working: the hour buttons, whose id is
+/// the number of hours, and the "Leave Squad" button.
+fn parse_legacy_choice(id: &str) -> Option<embed::ButtonChoice> {
+    match id {
+        "Leave Squad" => Some(embed::ButtonChoice::Leave),
+        _ => match id.parse::<u32>() {
+            Ok(hours @ 1..=10) => Some(embed::ButtonChoice::Availability(hours * 60 * 60)),
+            _ => None,
+        },
     }
 }
 
@@ -263,5 +1975,134 @@ pub fn parse_component_id(interaction: &MessageComponentInteraction) -> embed::B
 pub fn generate_squad_id() -> String {
     let mut rng = rand::thread_rng();
     let rand_id: u32 = rng.gen();
-    format!("squad:{}", rand_id.to_string())
+    format!("squad:{}", rand_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embed::ButtonChoice;
+
+    /// 2023-11-14 22:13:20 UTC
+    const NOW: i64 = 1_700_000_000;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("2h").unwrap(), 2 * 60 * 60);
+        assert_eq!(parse_duration("1h30m").unwrap(), 90 * 60);
+        assert_eq!(parse_duration(" 1h 30m ").unwrap(), 90 * 60);
+        assert_eq!(parse_duration("45m").unwrap(), 45 * 60);
+        assert_eq!(parse_duration("1d").unwrap(), 24 * 60 * 60);
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for input in [
+            "",
+            "0m",
+            "90",
+            "2x",
+            "h",
+            "99999999999999999d",
+            "9223372036854775807m",
+        ] {
+            assert!(parse_duration(input).is_err(), "{:?} parsed", input);
+        }
+    }
+
+    #[test]
+    fn checks_option_ranges() {
+        assert_eq!(option_in_range("size", 1, (1, 10)).unwrap(), 1);
+        assert_eq!(option_in_range("size", 10, (1, 10)).unwrap(), 10);
+        for value in [0, 11, -1, 256, i64::MAX] {
+            let error = option_in_range("size", value, (1, 10)).unwrap_err();
+            assert!(
+                matches!(error, SquadError::InvalidInput(_)),
+                "{} accepted",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn parses_start_times() {
+        let start = |input| parse_start_time(input, NOW).unwrap();
+        assert_eq!(start("<t:1700003600:R>"), 1_700_003_600);
+        assert_eq!(start("1700003600"), 1_700_003_600);
+        assert_eq!(start("in 2h"), NOW + 2 * 60 * 60);
+        assert_eq!(start("23:00"), 1_700_002_800);
+        // Times of day that have passed are taken as tomorrow
+        assert_eq!(start("9pm"), 1_700_082_000);
+        assert_eq!(start("23:00 utc+2"), 1_700_082_000);
+        assert_eq!(start("tomorrow 1am"), 1_700_010_000);
+        assert_eq!(start("2023-11-15 01:00"), 1_700_010_000);
+    }
+
+    #[test]
+    fn rejects_invalid_start_times() {
+        for input in [
+            "1699999999",
+            "in 8d",
+            "in 99999999999999999d",
+            "whenever",
+            "25:00",
+        ] {
+            assert!(parse_start_time(input, NOW).is_err(), "{:?} parsed", input);
+        }
+    }
+
+    #[test]
+    fn parses_availability() {
+        let until = |input| parse_available_until(input, NOW);
+        assert_eq!(until("1h45m").unwrap(), 105 * 60);
+        assert_eq!(until("until 23:00").unwrap(), 2800);
+        assert_eq!(until("hasta 23:00").unwrap(), 2800);
+        assert!(until("25h").is_err());
+        assert!(until("later").is_err());
+    }
+
+    #[test]
+    fn parses_component_ids() {
+        let choice = |id, value: &str| parse_choice(id, value.to_string());
+        assert!(matches!(choice("leave", ""), Some(ButtonChoice::Leave)));
+        assert!(matches!(
+            choice("join", "3600"),
+            Some(ButtonChoice::Availability(3600))
+        ));
+        assert!(matches!(
+            choice("join", "custom"),
+            Some(ButtonChoice::CustomAvailability)
+        ));
+        assert!(matches!(
+            choice("kick:42", "7"),
+            Some(ButtonChoice::KickMember { message_id, user_id })
+                if message_id == "42" && user_id == "7"
+        ));
+        assert!(matches!(choice("list:2", ""), Some(ButtonChoice::Page(2))));
+        assert!(choice("list:next", "").is_none());
+        assert!(choice("join", "soon").is_none());
+        assert!(choice("unknown", "").is_none());
+    }
+
+    #[test]
+    fn parses_legacy_component_ids() {
+        assert!(matches!(
+            parse_legacy_choice("Leave Squad"),
+            Some(ButtonChoice::Leave)
+        ));
+        assert!(matches!(
+            parse_legacy_choice("3"),
+            Some(ButtonChoice::Availability(10800))
+        ));
+        for id in [
+            "0",
+            "11",
+            "availability",
+            "close:squad:1",
+            "kick:42",
+            "other:1",
+        ] {
+            assert!(parse_legacy_choice(id).is_none(), "{:?} parsed", id);
+        }
+    }
 }
@@ -0,0 +1,806 @@
+use crate::error::SquadError;
+use crate::locale::Locale;
+use serenity::async_trait;
+use serenity::model::id::{GuildId, ScheduledEventId, UserId};
+use serenity::model::prelude::{ChannelId, MessageId, RoleId};
+use serenity::prelude::Context;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedReceiver;
+use typemap_rev::TypeMapKey;
+
+/// Error type shared by every storage backend
+pub type StoreError = SquadError;
+/// Result type shared by every storage backend
+pub type StoreResult<T> = Result<T, StoreError>;
+
+pub struct Store;
+
+/// Globally available TypeMapKey to store the active storage backend
+impl TypeMapKey for Store {
+    type Value = Arc<dyn SquadStore>;
+}
+
+pub enum SquadStatus {
+    Expired,
+    Forming,
+    Filled,
+    /// The owner cancelled the squad before it filled.
+    Cancelled,
+}
+
+/// Outcome of a user trying to join a squad
+pub enum JoinResult {
+    /// The user was added to the squad.
+    Joined,
+    /// The user was already in the squad; their availability was updated.
+    AlreadyMember,
+    /// The squad is at capacity, so the user was queued on its waitlist, or their
+    /// availability was updated if they were already waiting.
+    Waitlisted,
+    /// The squad has expired or already been filled, nothing was written.
+    Closed,
+    /// The squad has role slots and the user hasn't picked one, nothing was written.
+    NoSlot,
+}
+
+/// Size of squads created without a size, unless a guild says otherwise.
+pub const DEFAULT_SIZE: u8 = 5;
+/// Smallest and largest size a squad can have, unless a guild narrows the range.
+pub const SIZE_RANGE: (u8, u8) = (1, 10);
+/// Colour of the side of squad postings, unless a guild says otherwise.
+pub const DEFAULT_COLOUR: u32 = 0x3BA55D;
+
+/// Settings of a guild. Unset values fall back to the defaults of the bot.
+#[derive(Clone, Default)]
+pub struct GuildSettings {
+    /// Size of squads created without a size.
+    pub default_size: Option<u8>,
+    /// Smallest size a squad can have.
+    pub min_size: Option<u8>,
+    /// Largest size a squad can have.
+    pub max_size: Option<u8>,
+    /// Lifetime in seconds of squads created without a duration.
+    pub default_duration: Option<u64>,
+    /// Longest lifetime in seconds that a squad can be given.
+    pub max_duration: Option<u64>,
+    /// Channel in which new squads are announced.
+    pub announce_channel: Option<ChannelId>,
+    /// Colour of the side of squad postings, as 0xRRGGBB.
+    pub colour: Option<u32>,
+    /// Roles allowed to create squads. Everyone can when empty.
+    pub creator_roles: Vec<RoleId>,
+    /// Whether squad members are sent direct messages.
+    pub dms: Option<bool>,
+    /// Category in which a voice channel is opened for each filled squad. No voice
+    /// channels are opened when unset.
+    pub voice_category: Option<ChannelId>,
+    /// Whether a thread is opened off the posting of each filled squad.
+    pub threads: Option<bool>,
+    /// Time in seconds that the voice channel of a filled squad may sit empty before
+    /// it is deleted.
+    pub voice_cleanup: Option<u64>,
+    /// Whether a Discord scheduled event is created for scheduled squads and for
+    /// squads once they fill.
+    pub events: Option<bool>,
+    /// Whether members are reminded by DM before their availability runs out, and
+    /// owners before their squad expires unfilled.
+    pub reminders: Option<bool>,
+    /// Times in seconds that members can pick from to say how long they are available
+    /// in squads without a start time, shortest first. The defaults when empty.
+    pub availability: Vec<u64>,
+    /// Language of squad postings and of replies and direct messages to members. Replies
+    /// follow the language of each user when unset.
+    pub locale: Option<Locale>,
+}
+
+impl GuildSettings {
+    /// Size of squads created without a size, within the size range.
+    pub fn default_size(&self) -> u8 {
+        self.default_size
+            .unwrap_or(DEFAULT_SIZE)
+            .clamp(self.min_size(), self.max_size())
+    }
+
+    /// Smallest size a squad can have.
+    pub fn min_size(&self) -> u8 {
+        self.min_size.unwrap_or(SIZE_RANGE.0)
+    }
+
+    /// Largest size a squad can have.
+    pub fn max_size(&self) -> u8 {
+        self.max_size.unwrap_or(SIZE_RANGE.1)
+    }
+
+    /// Lifetime in seconds of squads created without a duration, within the maximum.
+    pub fn default_duration(&self) -> u64 {
+        self.default_duration
+            .unwrap_or(SQUAD_TTL)
+            .min(self.max_duration())
+    }
+
+    /// Longest lifetime in seconds that a squad can be given.
+    pub fn max_duration(&self) -> u64 {
+        self.max_duration.unwrap_or(MAX_SQUAD_TTL)
+    }
+
+    /// Colour of the side of squad postings, as 0xRRGGBB.
+    pub fn colour(&self) -> u32 {
+        self.colour.unwrap_or(DEFAULT_COLOUR)
+    }
+
+    /// Whether squad members are sent direct messages.
+    pub fn dms(&self) -> bool {
+        self.dms.unwrap_or(true)
+    }
+
+    /// Whether a thread is opened off the posting of each filled squad.
+    pub fn threads(&self) -> bool {
+        self.threads.unwrap_or(false)
+    }
+
+    /// Time in seconds that the voice channel of a filled squad may sit empty.
+    pub fn voice_cleanup(&self) -> u64 {
+        self.voice_cleanup.unwrap_or(VOICE_CLEANUP)
+    }
+
+    /// Whether Discord scheduled events are created for squads.
+    pub fn events(&self) -> bool {
+        self.events.unwrap_or(false)
+    }
+
+    /// Whether reminders are sent before availability or squads run out.
+    pub fn reminders(&self) -> bool {
+        self.reminders.unwrap_or(false)
+    }
+
+    /// Times in seconds that members can pick their availability from, shortest first.
+    pub fn availability(&self) -> Vec<u64> {
+        match self.availability.is_empty() {
+            true => AVAILABILITY.to_vec(),
+            false => self.availability.clone(),
+        }
+    }
+
+    /// Language of squad postings and direct messages to members.
+    pub fn locale(&self) -> Locale {
+        self.locale.unwrap_or_default()
+    }
+}
+
+/// Voice channel and thread opened for a squad posting when its squad filled.
+#[derive(Clone, Copy, Default)]
+pub struct Rooms {
+    pub voice: Option<ChannelId>,
+    pub thread: Option<ChannelId>,
+}
+
+/// Who may post a squad in other channels and servers by linking to its id. The
+/// owner of a squad can always link it.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinkPolicy {
+    /// Anyone can link the squad.
+    #[default]
+    Open,
+    /// Links by anyone but the owner wait for the owner to approve them.
+    Approval,
+    /// Only the owner can link the squad.
+    Disabled,
+}
+
+impl LinkPolicy {
+    /// Name of the policy, as stored and given to commands.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkPolicy::Open => "open",
+            LinkPolicy::Approval => "approval",
+            LinkPolicy::Disabled => "disabled",
+        }
+    }
+
+    /// Parse the name of a policy. Unknown names are read as Open, which is how
+    /// squads created before link policies behave.
+    pub fn parse(name: &str) -> LinkPolicy {
+        match name {
+            "approval" => LinkPolicy::Approval,
+            "disabled" => LinkPolicy::Disabled,
+            _ => LinkPolicy::Open,
+        }
+    }
+}
+
+/// Retrieve the settings of the guild a channel belongs to. Settings are all unset
+/// for channels outside of guilds or missing from the cache.
+pub async fn get_channel_settings(
+    ctx: &Context,
+    store: &dyn SquadStore,
+    channel_id: ChannelId,
+) -> StoreResult<GuildSettings> {
+    match ctx.cache.guild_channel(channel_id) {
+        Some(channel) => store.get_guild_settings(channel.guild_id).await,
+        None => Ok(GuildSettings::default()),
+    }
+}
+
+/// Format role ids as stored alongside guild settings, separated by spaces.
+pub fn format_roles(roles: &[RoleId]) -> String {
+    roles
+        .iter()
+        .map(|role| role.as_u64().to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Parse role ids as stored alongside guild settings, skipping anything invalid.
+pub fn parse_roles(roles: &str) -> Vec<RoleId> {
+    roles
+        .split_whitespace()
+        .filter_map(|role| role.parse().ok())
+        .map(RoleId)
+        .collect()
+}
+
+/// Format availability presets as stored alongside guild settings, in seconds
+/// separated by spaces.
+pub fn format_availability(presets: &[u64]) -> String {
+    presets
+        .iter()
+        .map(|seconds| seconds.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Parse availability presets as stored alongside guild settings, skipping anything
+/// invalid.
+pub fn parse_availability(presets: &str) -> Vec<u64> {
+    presets
+        .split_whitespace()
+        .filter_map(|seconds| seconds.parse().ok())
+        .collect()
+}
+
+/// Named role slots of a squad and the number of members each holds, in the order
+/// they were given, e.g. tank:1 healer:1 dps:3. Empty for squads without slots.
+pub type Composition = Vec<(String, u8)>;
+
+/// Most slots a composition can have.
+const MAX_SLOTS: usize = 10;
+
+/// Parse a composition such as "tank:1 healer:1 dps:3", separated by spaces or commas.
+/// Slot names are lowercased and may contain letters, digits, dashes and underscores.
+pub fn parse_composition(input: &str) -> StoreResult<Composition> {
+    let mut composition: Composition = Vec::new();
+    for slot in input
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|slot| !slot.is_empty())
+    {
+        let (name, count) = slot
+            .split_once(':')
+            .ok_or_else(|| SquadError::invalid_with(|text| text.slot_format, &[&slot]))?;
+        let name = name.to_lowercase();
+        let valid_name = !name.is_empty()
+            && name.len() <= 20
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+        if !valid_name {
+            return Err(SquadError::invalid_with(|text| text.slot_name, &[&name]));
+        }
+        if composition.iter().any(|(existing, _)| *existing == name) {
+            return Err(SquadError::invalid_with(
+                |text| text.slot_repeated,
+                &[&name],
+            ));
+        }
+        let count: u8 = count
+            .parse()
+            .map_err(|_| SquadError::invalid_with(|text| text.slot_size, &[&name]))?;
+        if count == 0 {
+            return Err(SquadError::invalid_with(|text| text.slot_empty, &[&name]));
+        }
+        composition.push((name, count));
+    }
+    if composition.len() > MAX_SLOTS {
+        return Err(SquadError::invalid_with(
+            |text| text.too_many_slots,
+            &[&MAX_SLOTS],
+        ));
+    }
+    Ok(composition)
+}
+
+/// Format a composition the way parse_composition reads it.
+pub fn format_composition(composition: &Composition) -> String {
+    composition
+        .iter()
+        .map(|(name, count)| format!("{}:{}", name, count))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Expiration time in seconds for squad data, unless a guild or command says otherwise.
+pub const SQUAD_TTL: u64 = 10 * 60 * 60;
+/// Longest expiration time in seconds for squad data, unless a guild says otherwise.
+pub const MAX_SQUAD_TTL: u64 = 24 * 60 * 60;
+/// Fewest and most hours members can give for their availability in commands, which
+/// like the availability menu and modal can't go past the longest squad.
+pub const HOURS_RANGE: (u8, u8) = (1, (MAX_SQUAD_TTL / (60 * 60)) as u8);
+/// Times in seconds that members can pick their availability from, unless a guild
+/// says otherwise.
+pub const AVAILABILITY: [u64; 12] = [
+    30 * 60,
+    60 * 60,
+    90 * 60,
+    2 * 60 * 60,
+    150 * 60,
+    3 * 60 * 60,
+    4 * 60 * 60,
+    5 * 60 * 60,
+    6 * 60 * 60,
+    7 * 60 * 60,
+    8 * 60 * 60,
+    10 * 60 * 60,
+];
+/// Most availability presets a guild can have, leaving room in the select menu for
+/// the custom option.
+pub const MAX_AVAILABILITY_PRESETS: usize = 24;
+/// Time in seconds that postings outlive their squad, so the final status stays visible.
+pub const POSTING_GRACE: u64 = 60 * 60;
+/// Time in seconds that a scheduled squad stays open after its start time.
+pub const START_GRACE: u64 = 60 * 60;
+/// Time in seconds that the voice channel of a filled squad may sit empty before it
+/// is deleted, unless a guild says otherwise.
+pub const VOICE_CLEANUP: u64 = 15 * 60;
+
+/// Retrieve the storage backend from the global data context.
+pub async fn get_store(ctx: &Context) -> StoreResult<Arc<dyn SquadStore>> {
+    let data_read = ctx.data.read().await;
+    match data_read.get::<Store>() {
+        Some(store) => Ok(Arc::clone(store)),
+        None => Err(SquadError::store("Unable to get squad store.")),
+    }
+}
+
+/// Everything SquadBot needs to persist about squads, postings, members and the
+/// channels a squad was posted in. Squad and posting data is expected to expire on
+/// its own after the number of seconds given when it was built, and member data
+/// after the number of seconds given when the member joined.
+///
+/// A squad that was never built or has expired reads as empty: it has no members,
+/// waitlist, slots, origins, channels, postings or composition, no quorum, owner or
+/// start, the default link policy, isn't ready and is Expired. Changes to it are
+/// ignored, those reporting whether they took effect report that they didn't and
+/// add_member reports it Closed. Only get_capacity and get_squad_ttl, which have
+/// nothing to fall back on, fail with SquadError::UnknownSquad. Postings that don't
+/// exist read as unset too, while get_squad_id, set_rooms and set_event fail with
+/// SquadError::UnknownSquad for them.
+#[async_trait]
+pub trait SquadStore: Send + Sync {
+    /// Add new squad data with the given owner and capacity, expiring in ttl seconds.
+    /// Scheduled squads carry the unix timestamp at which they start. Squads with
+    /// role slots carry their composition, whose seats add up to the capacity.
+    async fn build_squad(
+        &self,
+        squad_id: &str,
+        owner_id: &str,
+        capacity: u8,
+        start: Option<i64>,
+        composition: &Composition,
+        ttl: u64,
+    ) -> StoreResult<()>;
+
+    /// Add new data for a squad posting, expiring in ttl seconds, and record the
+    /// channel and guild it was posted in.
+    async fn build_posting(
+        &self,
+        channel_id: &str,
+        guild_id: Option<GuildId>,
+        message_id: &str,
+        role_id: Option<RoleId>,
+        squad_id: &str,
+        ttl: u64,
+    ) -> StoreResult<()>;
+
+    /// Retrieve the squad id from a given posting (message id).
+    async fn get_squad_id(&self, message_id: &str) -> StoreResult<String>;
+
+    /// Atomically add a member to a forming squad for the given number of seconds.
+    /// When the squad, or the slot the user picked, is full the user is put at the
+    /// back of its waitlist instead, keeping the number of seconds for when they are
+    /// promoted. Squads with role slots require the user to have picked a slot.
+    async fn add_member(
+        &self,
+        squad_id: &str,
+        user_id: &str,
+        expires: u32,
+    ) -> StoreResult<JoinResult>;
+
+    /// Atomically remove a member from a squad, or from its waitlist.
+    async fn delete_member(&self, squad_id: &str, user_id: &str) -> StoreResult<()>;
+
+    /// Atomically move users from the front of the waitlist into the free spots of a
+    /// forming squad, after removing expired members. For squads with role slots,
+    /// users are only promoted into a free seat of the slot they picked. Returns the
+    /// promoted users.
+    async fn promote_members(&self, squad_id: &str) -> StoreResult<Vec<UserId>>;
+
+    /// Atomically record the slot a user picked in a forming squad with role slots.
+    /// Members can only move to a slot with a free seat. Returns whether the slot was
+    /// recorded.
+    async fn choose_slot(&self, squad_id: &str, user_id: &str, slot: &str) -> StoreResult<bool>;
+
+    /// Get the role slots of a given squad, empty if it has none.
+    async fn get_composition(&self, squad_id: &str) -> StoreResult<Composition>;
+
+    /// Get the slot picked by each user of a given squad, members or not.
+    async fn get_slots(&self, squad_id: &str) -> StoreResult<HashMap<UserId, String>>;
+
+    /// Record the channel of the posting through which a user joined a squad, or its
+    /// waitlist, kept for as long as the squad.
+    async fn set_origin(
+        &self,
+        squad_id: &str,
+        user_id: &str,
+        channel_id: ChannelId,
+    ) -> StoreResult<()>;
+
+    /// Get the channel of the posting through which each user joined a given squad,
+    /// for the users it was recorded for.
+    async fn get_origins(&self, squad_id: &str) -> StoreResult<HashMap<UserId, ChannelId>>;
+
+    /// Get the users on the waitlist of a given squad, first in line first.
+    async fn get_waitlist(&self, squad_id: &str) -> StoreResult<Vec<UserId>>;
+
+    /// Get the capacity of a given squad.
+    async fn get_capacity(&self, squad_id: &str) -> StoreResult<u8>;
+
+    /// Get the members and corresponding expiry times in seconds of a given squad.
+    /// Expired members are removed from the squad.
+    async fn get_members(&self, squad_id: &str) -> StoreResult<HashMap<UserId, u64>>;
+
+    /// Get the time-to-live in seconds of a given squad.
+    async fn get_squad_ttl(&self, squad_id: &str) -> StoreResult<u64>;
+
+    /// Get the channel and message ids of all current squad postings.
+    async fn get_postings(&self) -> StoreResult<HashMap<MessageId, ChannelId>>;
+
+    /// Get the ids of the squads with a current posting in a given guild.
+    async fn get_guild_squads(&self, guild_id: GuildId) -> StoreResult<Vec<String>>;
+
+    /// Get the ids of the squads a given user is a member of or waiting to join.
+    async fn get_user_squads(&self, user_id: UserId) -> StoreResult<Vec<String>>;
+
+    /// Forget a posting (message id), along with the channel it was posted in unless
+    /// another posting of its squad is in the same channel. Returns whether the
+    /// posting existed.
+    async fn delete_posting(&self, message_id: &str) -> StoreResult<bool>;
+
+    /// Get the channel and message ids of all current postings of a given squad.
+    async fn get_squad_postings(
+        &self,
+        squad_id: &str,
+    ) -> StoreResult<HashMap<MessageId, ChannelId>>;
+
+    /// Get the ids of all squads that are at capacity and haven't been flagged as
+    /// filled and notified. Scheduled squads are only included once they've started.
+    /// Since no slot ever holds more members than its seats, a squad with role slots
+    /// is only at capacity once every slot is filled.
+    async fn get_full_squads(&self) -> StoreResult<Vec<String>>;
+
+    /// Give a squad a quorum: the number of members at which it is ready to play,
+    /// while it stays open for more members up to its capacity.
+    async fn set_quorum(&self, squad_id: &str, quorum: u8) -> StoreResult<()>;
+
+    /// Get the quorum of a given squad, if it has one.
+    async fn get_quorum(&self, squad_id: &str) -> StoreResult<Option<u8>>;
+
+    /// Set who may post a given squad elsewhere by linking to its id.
+    async fn set_link_policy(&self, squad_id: &str, policy: LinkPolicy) -> StoreResult<()>;
+
+    /// Get who may post a given squad elsewhere by linking to its id.
+    async fn get_link_policy(&self, squad_id: &str) -> StoreResult<LinkPolicy>;
+
+    /// Atomically flag a forming squad with a quorum as ready, provided it has at
+    /// least as many members as its quorum and, if scheduled, has started. Returns
+    /// whether the squad was flagged by this call.
+    async fn ready_squad(&self, squad_id: &str) -> StoreResult<bool>;
+
+    /// Whether a given squad was flagged as ready, having reached its quorum.
+    async fn is_ready(&self, squad_id: &str) -> StoreResult<bool>;
+
+    /// Atomically flag a squad as filled, provided it is still forming, at capacity
+    /// and, if scheduled, has started. Returns whether the squad was flagged by this
+    /// call.
+    async fn fill_squad(&self, squad_id: &str) -> StoreResult<bool>;
+
+    /// Atomically flag a forming squad with at least one member as filled, regardless
+    /// of its capacity or start time. Returns whether the squad was flagged by this
+    /// call.
+    async fn close_squad(&self, squad_id: &str) -> StoreResult<bool>;
+
+    /// Atomically flag a forming squad as cancelled. Returns whether the squad was
+    /// flagged by this call.
+    async fn cancel_squad(&self, squad_id: &str) -> StoreResult<bool>;
+
+    /// Atomically push back the expiry of a forming squad, along with its postings and
+    /// the data kept for as long as the squad, by the given number of seconds. Members
+    /// keep their own expiry. Returns whether the squad was extended.
+    async fn extend_squad(&self, squad_id: &str, seconds: u64) -> StoreResult<bool>;
+
+    /// Get the user who created a given squad, if it was recorded.
+    async fn get_owner(&self, squad_id: &str) -> StoreResult<Option<UserId>>;
+
+    /// Get the unix timestamp at which a scheduled squad starts, if it is scheduled.
+    async fn get_start(&self, squad_id: &str) -> StoreResult<Option<i64>>;
+
+    /// Get the squad status of a given squad: Expired, Forming, Filled or Cancelled.
+    async fn get_squad_status(&self, squad_id: &str) -> StoreResult<SquadStatus>;
+
+    /// Get the role id that was posted with the given posting (message id).
+    async fn get_role_id(&self, message_id: &str) -> StoreResult<Option<RoleId>>;
+
+    /// Get the channels in which a squad is posted.
+    async fn get_channels(&self, squad_id: &str) -> StoreResult<Vec<ChannelId>>;
+
+    /// Record the voice channel and thread opened for a posting (message id) when its
+    /// squad filled. The voice channel is also kept, beyond the expiry of the posting,
+    /// among the voice channels to clean up.
+    async fn set_rooms(&self, message_id: &str, rooms: &Rooms) -> StoreResult<()>;
+
+    /// Get the voice channel and thread opened for a posting (message id), unset if
+    /// none were.
+    async fn get_rooms(&self, message_id: &str) -> StoreResult<Rooms>;
+
+    /// Record the Discord scheduled event created for a posting (message id), or
+    /// forget it when None is given.
+    async fn set_event(
+        &self,
+        message_id: &str,
+        event_id: Option<ScheduledEventId>,
+    ) -> StoreResult<()>;
+
+    /// Get the Discord scheduled event created for a posting (message id), if any.
+    async fn get_event(&self, message_id: &str) -> StoreResult<Option<ScheduledEventId>>;
+
+    /// Get the voice channels opened for filled squads that haven't been cleaned up.
+    async fn get_voice_channels(&self) -> StoreResult<Vec<ChannelId>>;
+
+    /// Forget a voice channel opened for a filled squad, once it has been deleted.
+    async fn delete_voice_channel(&self, channel_id: ChannelId) -> StoreResult<()>;
+
+    /// Get the settings of a given guild, all unset if none were saved.
+    async fn get_guild_settings(&self, guild_id: GuildId) -> StoreResult<GuildSettings>;
+
+    /// Save the settings of a given guild, replacing any saved before. Guild settings
+    /// never expire.
+    async fn set_guild_settings(
+        &self,
+        guild_id: GuildId,
+        settings: &GuildSettings,
+    ) -> StoreResult<()>;
+
+    /// Subscribe to the ids of squads whose members or status change, including
+    /// changes caused by data expiring. Returns None when the backend can't report
+    /// changes, in which case postings have to be polled.
+    async fn subscribe(&self) -> StoreResult<Option<UnboundedReceiver<String>>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Memory;
+    use crate::redis_io::Redis;
+    use crate::sqlite::Sqlite;
+    use crate::squad::generate_squad_id;
+
+    /// Define a test of every check below for one backend, each run against a fresh
+    /// store built by the given expression.
+    macro_rules! contract_tests {
+        ($(#[$attr:meta])* $backend:ident, $store:expr) => {
+            mod $backend {
+                use super::*;
+
+                contract_tests!(
+                    @checks [$(#[$attr])*] $store;
+                    join_and_leave, fill, waitlist_promotion, slots, owner_controls, ready,
+                    missing_squad
+                );
+            }
+        };
+        (@checks $attrs:tt $store:expr; $($check:ident),*) => {
+            $(contract_tests!(@check $attrs $store; $check);)*
+        };
+        (@check [$(#[$attr:meta])*] $store:expr; $check:ident) => {
+            #[tokio::test]
+            $(#[$attr])*
+            async fn $check() {
+                super::$check(&$store).await;
+            }
+        };
+    }
+
+    contract_tests!(memory, Memory::default());
+    contract_tests!(sqlite, Sqlite::open(":memory:").unwrap());
+    contract_tests!(
+        #[ignore = "needs a Redis server at REDIS_URL"]
+        redis,
+        Redis::open(&redis_url()).await.unwrap()
+    );
+
+    const OWNER: &str = "100";
+
+    fn redis_url() -> String {
+        std::env::var("REDIS_URL").unwrap_or_else(|_| String::from("redis://127.0.0.1/"))
+    }
+
+    /// Build a squad of the given capacity and role slots under a fresh id, so that
+    /// checks sharing a Redis server don't touch each other's squads.
+    async fn build_slotted(store: &dyn SquadStore, capacity: u8, slots: &str) -> String {
+        let squad_id = generate_squad_id();
+        let composition = parse_composition(slots).unwrap();
+        store
+            .build_squad(&squad_id, OWNER, capacity, None, &composition, 3600)
+            .await
+            .unwrap();
+        squad_id
+    }
+
+    /// Build a squad of the given capacity without role slots under a fresh id.
+    async fn build(store: &dyn SquadStore, capacity: u8) -> String {
+        build_slotted(store, capacity, "").await
+    }
+
+    async fn join_and_leave(store: &dyn SquadStore) {
+        let squad_id = build(store, 2).await;
+        let joined = store.add_member(&squad_id, "1", 600).await.unwrap();
+        assert!(matches!(joined, JoinResult::Joined));
+        let again = store.add_member(&squad_id, "1", 1200).await.unwrap();
+        assert!(matches!(again, JoinResult::AlreadyMember));
+        let members = store.get_members(&squad_id).await.unwrap();
+        assert_eq!(members.keys().collect::<Vec<_>>(), [&UserId(1)]);
+        assert!((601..=1200).contains(&members[&UserId(1)]));
+
+        store.delete_member(&squad_id, "1").await.unwrap();
+        assert!(store.get_members(&squad_id).await.unwrap().is_empty());
+    }
+
+    async fn fill(store: &dyn SquadStore) {
+        let status = store.get_squad_status("squad:unknown").await.unwrap();
+        assert!(matches!(status, SquadStatus::Expired));
+        let squad_id = build(store, 2).await;
+        store.add_member(&squad_id, "1", 600).await.unwrap();
+        assert!(!store.get_full_squads().await.unwrap().contains(&squad_id));
+        assert!(!store.fill_squad(&squad_id).await.unwrap());
+        let status = store.get_squad_status(&squad_id).await.unwrap();
+        assert!(matches!(status, SquadStatus::Forming));
+
+        store.add_member(&squad_id, "2", 600).await.unwrap();
+        assert!(store.get_full_squads().await.unwrap().contains(&squad_id));
+        assert!(store.fill_squad(&squad_id).await.unwrap());
+        assert!(!store.fill_squad(&squad_id).await.unwrap());
+        let status = store.get_squad_status(&squad_id).await.unwrap();
+        assert!(matches!(status, SquadStatus::Filled));
+        assert!(!store.get_full_squads().await.unwrap().contains(&squad_id));
+        let late = store.add_member(&squad_id, "3", 600).await.unwrap();
+        assert!(matches!(late, JoinResult::Closed));
+    }
+
+    async fn waitlist_promotion(store: &dyn SquadStore) {
+        let squad_id = build(store, 1).await;
+        store.add_member(&squad_id, "1", 600).await.unwrap();
+        let waiting = store.add_member(&squad_id, "2", 600).await.unwrap();
+        assert!(matches!(waiting, JoinResult::Waitlisted));
+        let waiting = store.add_member(&squad_id, "3", 600).await.unwrap();
+        assert!(matches!(waiting, JoinResult::Waitlisted));
+        let waitlist = store.get_waitlist(&squad_id).await.unwrap();
+        assert_eq!(waitlist, [UserId(2), UserId(3)]);
+        assert!(store.promote_members(&squad_id).await.unwrap().is_empty());
+
+        store.delete_member(&squad_id, "1").await.unwrap();
+        let promoted = store.promote_members(&squad_id).await.unwrap();
+        assert_eq!(promoted, [UserId(2)]);
+        let members = store.get_members(&squad_id).await.unwrap();
+        assert_eq!(members.keys().collect::<Vec<_>>(), [&UserId(2)]);
+        assert_eq!(store.get_waitlist(&squad_id).await.unwrap(), [UserId(3)]);
+    }
+
+    async fn slots(store: &dyn SquadStore) {
+        let squad_id = build_slotted(store, 2, "tank:1 dps:1").await;
+        let picked = store.add_member(&squad_id, "1", 600).await.unwrap();
+        assert!(matches!(picked, JoinResult::NoSlot));
+        assert!(!store.choose_slot(&squad_id, "1", "healer").await.unwrap());
+        assert!(store.choose_slot(&squad_id, "1", "tank").await.unwrap());
+        let joined = store.add_member(&squad_id, "1", 600).await.unwrap();
+        assert!(matches!(joined, JoinResult::Joined));
+
+        // The tank seat is taken, so a second tank waits even with a free dps seat
+        assert!(store.choose_slot(&squad_id, "2", "tank").await.unwrap());
+        let waiting = store.add_member(&squad_id, "2", 600).await.unwrap();
+        assert!(matches!(waiting, JoinResult::Waitlisted));
+        let slots = store.get_slots(&squad_id).await.unwrap();
+        assert_eq!(slots.get(&UserId(1)).map(String::as_str), Some("tank"));
+    }
+
+    async fn owner_controls(store: &dyn SquadStore) {
+        let squad_id = build(store, 2).await;
+        assert_eq!(store.get_owner(&squad_id).await.unwrap(), Some(UserId(100)));
+        assert!(!store.close_squad(&squad_id).await.unwrap());
+        store.add_member(&squad_id, "1", 600).await.unwrap();
+        assert!(store.close_squad(&squad_id).await.unwrap());
+        let status = store.get_squad_status(&squad_id).await.unwrap();
+        assert!(matches!(status, SquadStatus::Filled));
+
+        let squad_id = build(store, 2).await;
+        assert!(store.cancel_squad(&squad_id).await.unwrap());
+        assert!(!store.cancel_squad(&squad_id).await.unwrap());
+        let status = store.get_squad_status(&squad_id).await.unwrap();
+        assert!(matches!(status, SquadStatus::Cancelled));
+        let late = store.add_member(&squad_id, "1", 600).await.unwrap();
+        assert!(matches!(late, JoinResult::Closed));
+    }
+
+    async fn ready(store: &dyn SquadStore) {
+        let squad_id = build(store, 3).await;
+        store.set_quorum(&squad_id, 2).await.unwrap();
+        assert_eq!(store.get_quorum(&squad_id).await.unwrap(), Some(2));
+        store.add_member(&squad_id, "1", 600).await.unwrap();
+        assert!(!store.ready_squad(&squad_id).await.unwrap());
+        assert!(!store.is_ready(&squad_id).await.unwrap());
+
+        store.add_member(&squad_id, "2", 600).await.unwrap();
+        assert!(store.ready_squad(&squad_id).await.unwrap());
+        assert!(!store.ready_squad(&squad_id).await.unwrap());
+        assert!(store.is_ready(&squad_id).await.unwrap());
+        let status = store.get_squad_status(&squad_id).await.unwrap();
+        assert!(matches!(status, SquadStatus::Forming));
+    }
+
+    async fn missing_squad(store: &dyn SquadStore) {
+        let squad_id = generate_squad_id();
+        store.set_quorum(&squad_id, 2).await.unwrap();
+        store
+            .set_link_policy(&squad_id, LinkPolicy::Disabled)
+            .await
+            .unwrap();
+        store.delete_member(&squad_id, "1").await.unwrap();
+        assert_eq!(store.get_quorum(&squad_id).await.unwrap(), None);
+        assert_eq!(store.get_owner(&squad_id).await.unwrap(), None);
+        assert_eq!(store.get_start(&squad_id).await.unwrap(), None);
+        assert!(store.get_composition(&squad_id).await.unwrap().is_empty());
+        let policy = store.get_link_policy(&squad_id).await.unwrap();
+        assert!(matches!(policy, LinkPolicy::Open));
+        let joined = store.add_member(&squad_id, "1", 600).await.unwrap();
+        assert!(matches!(joined, JoinResult::Closed));
+        assert!(store.get_members(&squad_id).await.unwrap().is_empty());
+        assert!(store.promote_members(&squad_id).await.unwrap().is_empty());
+        assert!(!store.choose_slot(&squad_id, "1", "tank").await.unwrap());
+        assert!(!store.cancel_squad(&squad_id).await.unwrap());
+        assert!(!store.extend_squad(&squad_id, 600).await.unwrap());
+        let capacity = store.get_capacity(&squad_id).await;
+        assert!(matches!(capacity, Err(SquadError::UnknownSquad)));
+        let ttl = store.get_squad_ttl(&squad_id).await;
+        assert!(matches!(ttl, Err(SquadError::UnknownSquad)));
+    }
+
+    #[test]
+    fn parses_compositions() {
+        let composition = parse_composition("Tank:1, DPS:2 healer:1").unwrap();
+        let expected = [("tank", 1), ("dps", 2), ("healer", 1)];
+        assert_eq!(
+            composition,
+            expected.map(|(name, count)| (name.to_string(), count))
+        );
+        assert!(parse_composition("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_compositions() {
+        for input in ["tank", "tank:0", "tank:x", "tank:1 TANK:1", "bad!:1", ":1"] {
+            assert!(parse_composition(input).is_err(), "{:?} parsed", input);
+        }
+        let too_many: Vec<String> = (0..=MAX_SLOTS).map(|i| format!("slot{}:1", i)).collect();
+        assert!(parse_composition(&too_many.join(" ")).is_err());
+    }
+}